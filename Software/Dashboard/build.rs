@@ -0,0 +1,13 @@
+//! Puts `memory.x` on the linker search path, for cortex-m-rt's `link.x`
+//!
+//! The project's own layout is used rather than embassy-stm32's, so the trip storage page is
+//! kept out of the firmware's flash region.
+
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out.join("memory.x"), include_bytes!("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+}
@@ -0,0 +1,150 @@
+//! Derive macro for the dashboard's CAN packages
+//!
+//! `#[derive(FdcanPackage)]` implements `eco_can::FDCANPack` from an `#[fdcan(...)]` attribute,
+//! and checks the package's length at compile time:
+//! ```rust,ignore
+//! #[derive(bincode::Encode, bincode::Decode, FdcanPackage)]
+//! #[fdcan(id = 0x040)]
+//! #[repr(C)]
+//! pub struct FDCAN_PACKAGE_NAME {
+//!     pub in_curr: u32,
+//!     pub in_volt: u32,
+//! }
+//! ```
+//!
+//! `FDCAN_BYTES` is the sum of the field sizes, which is the length bincode encodes with fixed
+//! int encoding. It fails to compile if that isn't one of the `FDCANLength` variants, or if the
+//! struct has padding, so its size doesn't match. A fieldless enum, such as a `#[repr(u8)]`
+//! state, is the size of its representation.
+//!
+//! The attribute takes:
+//! - `id`, the package's CAN ID, required
+//! - `min_bytes`, the shortest frame that can be decoded, see `FDCANPack::MIN_BYTES`
+//! - `classic`, always send the package in a classic frame, see `FDCANPack::PREFER_CLASSIC`
+//!
+//! Each package also claims its ID with `eco_can::IdClaim`, so two packages given the same ID
+//! fail to compile with conflicting implementations.
+//!
+//! The generated code refers to `crate::eco_can`, so the macro is only used inside the
+//! dashboard crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Expr, Fields, LitInt, parse_macro_input};
+
+/// The highest standard (11 bit) CAN ID
+const MAX_STANDARD_ID: u32 = 0x7FF;
+
+#[proc_macro_derive(FdcanPackage, attributes(fdcan))]
+pub fn derive_fdcan_package(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The values given in `#[fdcan(...)]`
+struct FdcanAttr {
+    id: LitInt,
+    min_bytes: Option<Expr>,
+    classic: bool,
+}
+
+fn parse_attr(input: &DeriveInput) -> syn::Result<FdcanAttr> {
+    let mut id = None;
+    let mut min_bytes = None;
+    let mut classic = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("fdcan"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                let lit: LitInt = meta.value()?.parse()?;
+                if lit.base10_parse::<u32>()? > MAX_STANDARD_ID {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        "CAN IDs are 11 bits, up to 0x7FF",
+                    ));
+                }
+                id = Some(lit);
+                Ok(())
+            } else if meta.path.is_ident("min_bytes") {
+                min_bytes = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("classic") {
+                classic = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `id`, `min_bytes` or `classic`"))
+            }
+        })?;
+    }
+    let id = id.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing `#[fdcan(id = ...)]` attribute")
+    })?;
+    Ok(FdcanAttr {
+        id,
+        min_bytes,
+        classic,
+    })
+}
+
+/// The encoded length of the package, as an expression
+fn encoded_len(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    match &input.data {
+        Data::Struct(data) => {
+            let types = match &data.fields {
+                Fields::Named(fields) => fields.named.iter().map(|field| &field.ty).collect(),
+                Fields::Unnamed(fields) => fields.unnamed.iter().map(|field| &field.ty).collect(),
+                Fields::Unit => Vec::new(),
+            };
+            Ok(quote! { 0 #(+ ::core::mem::size_of::<#types>())* })
+        }
+        Data::Enum(data) => {
+            if let Some(variant) = data.variants.iter().find(|v| !v.fields.is_empty()) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "only fieldless enums can be sent as a package",
+                ));
+            }
+            Ok(quote! { ::core::mem::size_of::<Self>() })
+        }
+        Data::Union(data) => Err(syn::Error::new_spanned(
+            data.union_token,
+            "unions can't be sent as a package",
+        )),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let FdcanAttr {
+        id,
+        min_bytes,
+        classic,
+    } = parse_attr(input)?;
+    let len = encoded_len(input)?;
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "packages can't be generic, their length must be known",
+        ));
+    }
+    let min_bytes = min_bytes.map(|min_bytes| quote! { const MIN_BYTES: usize = #min_bytes; });
+    let classic = classic.then(|| quote! { const PREFER_CLASSIC: bool = true; });
+
+    Ok(quote! {
+        impl crate::eco_can::FDCANPack for #name {
+            const FDCAN_BYTES: crate::eco_can::FDCANLength =
+                crate::eco_can::FDCANLength::from_len(#len);
+            const FDCAN_ID: u32 = #id;
+            #min_bytes
+            #classic
+        }
+        const _: () = crate::eco_can::assert_len::<#name>();
+        impl crate::eco_can::IdClaim<{ #id }> for crate::eco_can::CanIds {}
+    })
+}
@@ -0,0 +1,8 @@
+/* STM32G491KE, the same as embassy-stm32's generated layout except the last pages of flash */
+MEMORY
+{
+    /* 512K less the two 2K pages storage_mod keeps the touch calibration and the trip in, see
+       CALIBRATION_OFFSET */
+    FLASH : ORIGIN = 0x08000000, LENGTH = 508K
+    RAM   : ORIGIN = 0x20000000, LENGTH = 112K /* SRAM1 + SRAM2 + CCMRAM_DCODE */
+}
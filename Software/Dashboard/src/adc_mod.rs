@@ -0,0 +1,90 @@
+//! Module for monitoring the dashboard's own supply voltage
+//!
+//! The dashboard runs off the car's power, so it measures its own supply with the ADC,
+//! independent of any CAN data. This lets it warn of a brownout even when CAN is down, with an
+//! indicator on every screen and an entry in the event log.
+//!
+//! The ADC measures the internal voltage reference (VREFINT), which is a fixed ~1.2 V
+//! regardless of the supply. The supply voltage (VDDA) is then calculated by comparing the
+//! reading against the factory calibration value, which was measured with VDDA = 3.0 V:
+//!
+//! `VDDA = 3.0 V * VREFINT_CAL / VREFINT_DATA`
+//!
+//! See section 21.4.34 of the
+//! [STM32G4 Reference Manual](https://www.st.com/resource/en/reference_manual/rm0440-stm32g4-series-advanced-armbased-32bit-mcus-stmicroelectronics.pdf).
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering::Relaxed};
+
+use embassy_stm32::adc::{Adc, SampleTime};
+use embassy_stm32::peripherals::ADC1;
+use embassy_time::Timer;
+
+use crate::event_log_mod::log_event;
+use crate::log_mod::{info, trace, warn};
+use crate::warning_mod::update_low_warning;
+
+/// Address of the factory VREFINT calibration value
+const VREFINT_CAL_ADDR: usize = 0x1FFF_75AA;
+/// Supply voltage the VREFINT calibration value was measured at
+const VREFINT_CAL_MV: u32 = 3_000;
+
+/// Time between supply voltage samples
+pub const SUPPLY_SAMPLE_PERIOD_MS: u64 = 100;
+/// Supply voltage below which a brownout warning is raised
+pub const SUPPLY_LOW_MV: u32 = 3_100;
+/// The warning is cleared once the supply recovers above `SUPPLY_LOW_MV + SUPPLY_HYST_MV`
+pub const SUPPLY_HYST_MV: u32 = 50;
+/// Weight of a new sample in the smoothed voltage, as a power of two (1/4)
+const SMOOTHING_SHIFT: u32 = 2;
+
+/// Smoothed supply voltage in millivolts, 0 until the first sample
+pub static SUPPLY_MV: AtomicU32 = AtomicU32::new(0);
+/// True while the supply voltage is low, shown as a warning indicator
+pub static SUPPLY_LOW: AtomicBool = AtomicBool::new(false);
+
+/// Calculates the supply voltage in millivolts from a VREFINT reading
+pub fn vrefint_to_supply_mv(vrefint_cal: u16, vrefint_data: u16) -> u32 {
+    if vrefint_data == 0 {
+        return 0;
+    }
+    VREFINT_CAL_MV * vrefint_cal as u32 / vrefint_data as u32
+}
+
+/// Samples the supply voltage, and raises a warning if it is low
+#[embassy_executor::task]
+pub async fn adc_task(mut adc: Adc<'static, ADC1>) {
+    let mut vrefint = adc.enable_vrefint();
+    // VREFINT requires a sampling time of at least 4us
+    adc.set_sample_time(SampleTime::CYCLES247_5);
+    // Wait for the reference to start up
+    Timer::after_millis(1).await;
+
+    // SAFETY: The calibration value is programmed into system memory at the factory, and is
+    // read only
+    let vrefint_cal = unsafe { core::ptr::read_volatile(VREFINT_CAL_ADDR as *const u16) };
+    info!("VREFINT calibration value: {}", vrefint_cal);
+
+    let mut smoothed_mv = 0;
+    loop {
+        let supply_mv = vrefint_to_supply_mv(vrefint_cal, adc.blocking_read(&mut vrefint));
+        smoothed_mv = if smoothed_mv == 0 {
+            supply_mv
+        } else {
+            smoothed_mv - (smoothed_mv >> SMOOTHING_SHIFT) + (supply_mv >> SMOOTHING_SHIFT)
+        };
+        SUPPLY_MV.store(smoothed_mv, Relaxed);
+        trace!("Supply voltage: {} mV", smoothed_mv);
+
+        // Apply hysteresis so the warning doesn't flicker
+        if update_low_warning(&SUPPLY_LOW, smoothed_mv, SUPPLY_LOW_MV, SUPPLY_HYST_MV) {
+            if SUPPLY_LOW.load(Relaxed) {
+                warn!("Supply voltage low: {} mV", smoothed_mv);
+                log_event("Supply voltage low");
+            } else {
+                info!("Supply voltage recovered: {} mV", smoothed_mv);
+            }
+        }
+
+        Timer::after_millis(SUPPLY_SAMPLE_PERIOD_MS).await;
+    }
+}
@@ -5,46 +5,218 @@
 //! not match the actual source code for the `exti` module. The `exti` module
 //! is actually the same as version "0.3.0".
 //!
-//! Note that **Non-Blocking** delays are used to handle signal bouncing.
+//! Signal bounce is handled with edge timestamps rather than sleeping after each edge, so a
+//! press during the bounce window of the previous release isn't missed. Gestures are published
+//! to [`BUTTON_EVENTS`] as soon as they are known, and each subscriber buffers them until it
+//! is ready.
 //!
-use defmt::info;
+use core::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+use defmt::Format;
 use embassy_stm32::exti::ExtiInput;
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
-use embassy_time::Timer;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pubsub::PubSubChannel};
+use embassy_time::{Duration, Instant, Timer, with_deadline, with_timeout};
+
+use crate::log_mod::info;
 
-/// A delay to handle signal bounce. Default 50ms.
+/// Default debounce time, edges within this time of the last accepted change are treated as
+/// bounce
 pub const BOUNCE_DELAY: u64 = 100;
+/// Debounce time of button 1, in ms
+pub const BUTTON1_BOUNCE_MS: u64 = BOUNCE_DELAY;
+/// Debounce time of button 2, in ms
+pub const BUTTON2_BOUNCE_MS: u64 = BOUNCE_DELAY;
+/// How often an idle button's pin is sampled, in case an edge was missed
+const BUTTON_RESAMPLE_MS: u64 = 50;
+/// A press held at least this long is a long press
+pub const LONG_PRESS_MS: u64 = 800;
+/// A second press starting within this time of a release is a double press
+pub const DOUBLE_PRESS_MS: u64 = 300;
+/// Number of events buffered for each subscriber, if a subscriber falls further behind
+/// its oldest events are dropped
+pub const BUTTON_EVENT_CAPACITY: usize = 8;
+/// A button with auto-repeat held this long starts sending [`ButtonEvent::Repeat`]
+pub const REPEAT_DELAY_MS: u64 = 1_500;
+/// Time between [`ButtonEvent::Repeat`] events while the button is held
+pub const REPEAT_INTERVAL_MS: u64 = 500;
+/// Button 1 toggles the relay state, so it never repeats
+pub const BUTTON1_AUTO_REPEAT: bool = false;
+/// Holding button 2 keeps switching pages
+pub const BUTTON2_AUTO_REPEAT: bool = true;
+/// Maximum number of tasks subscribed to [`BUTTON_EVENTS`]
+pub const BUTTON_EVENT_SUBSCRIBERS: usize = 4;
 
-pub static BTN_SIGNAL: Signal<ThreadModeRawMutex, bool> = Signal::new();
+// The long press is always sent before the repeats start
+const _: () = assert!(REPEAT_DELAY_MS > LONG_PRESS_MS);
 
-#[embassy_executor::task]
-pub async fn btn1_task(mut btn1: ExtiInput<'static>) {
-    let mut i = 0;
-    loop {
-        btn1.wait_for_falling_edge().await;
-        info!("Btn 1 Pressed!");
-        Timer::after_millis(BOUNCE_DELAY).await;
+/// Identifies a button on the dashboard
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ButtonId {
+    Button1,
+    Button2,
+}
+
+/// A gesture detected on a button
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ButtonEvent {
+    ShortPress,
+    /// Held for at least [`LONG_PRESS_MS`]
+    LongPress,
+    /// Pressed again within [`DOUBLE_PRESS_MS`] of being released
+    DoublePress,
+    /// Still held, sent every [`REPEAT_INTERVAL_MS`] after [`REPEAT_DELAY_MS`], only if
+    /// auto-repeat is enabled for the button
+    Repeat,
+    /// Pressed while the other button is held, sent instead of any other gesture
+    Chord,
+}
 
-        BTN_SIGNAL.signal(true);
+/// True while each button is pressed, indexed by [`ButtonId`]
+static HELD: [AtomicBool; 2] = [AtomicBool::new(false), AtomicBool::new(false)];
 
-        i += 1;
-        btn1.wait_for_high().await;
-        Timer::after_millis(BOUNCE_DELAY).await;
-        info!("Btn 1 Released {} times!", i);
+impl ButtonId {
+    const fn other(self) -> Self {
+        match self {
+            ButtonId::Button1 => ButtonId::Button2,
+            ButtonId::Button2 => ButtonId::Button1,
+        }
     }
 }
 
-#[embassy_executor::task]
-pub async fn btn2_task(mut btn2: ExtiInput<'static>) {
-    let mut i = 0;
+/// Button events, published by the button tasks.
+///
+/// Each subscriber receives every event:
+/// ```rust,ignore
+/// let mut buttons = BUTTON_EVENTS.subscriber().unwrap();
+/// if let Some((ButtonId::Button1, ButtonEvent::LongPress)) = buttons.try_next_message_pure() {
+///     // reset the trip meter
+/// }
+/// ```
+pub static BUTTON_EVENTS: PubSubChannel<
+    ThreadModeRawMutex,
+    (ButtonId, ButtonEvent),
+    BUTTON_EVENT_CAPACITY,
+    BUTTON_EVENT_SUBSCRIBERS,
+    0,
+> = PubSubChannel::new();
+
+/// A button's debounced level, tracked from edge timestamps
+///
+/// A change is accepted as soon as it is seen, then changes within `bounce` of it are bounce.
+/// The pin is sampled again once the bounce window ends, so a change during it isn't lost.
+struct DebouncedButton {
+    btn: ExtiInput<'static>,
+    id: ButtonId,
+    bounce: Duration,
+    pressed: bool,
+    last_change: Instant,
+}
+
+impl DebouncedButton {
+    fn new(btn: ExtiInput<'static>, id: ButtonId, bounce_ms: u64) -> Self {
+        Self {
+            btn,
+            id,
+            bounce: Duration::from_millis(bounce_ms),
+            pressed: false,
+            last_change: Instant::MIN,
+        }
+    }
+
+    /// Waits for the debounced level to change
+    async fn next_change(&mut self) {
+        loop {
+            let pressed = self.btn.is_low();
+            if pressed != self.pressed {
+                let settled = self.last_change + self.bounce;
+                let now = Instant::now();
+                if now >= settled {
+                    self.pressed = pressed;
+                    HELD[self.id as usize].store(pressed, Relaxed);
+                    self.last_change = now;
+                    return;
+                }
+                // Still bouncing from the last change, look again once it has settled
+                Timer::at(settled).await;
+            } else {
+                // Sampled periodically too, in case the edge came before the interrupt was armed
+                let resample = Duration::from_millis(BUTTON_RESAMPLE_MS);
+                let _ = with_timeout(resample, self.btn.wait_for_any_edge()).await;
+            }
+        }
+    }
+
+    /// Waits for the button to be pressed or released, returns when it changed
+    async fn wait_for(&mut self, pressed: bool) -> Instant {
+        while self.pressed != pressed {
+            self.next_change().await;
+        }
+        self.last_change
+    }
+}
+
+/// Publishes a button's gestures to [`BUTTON_EVENTS`]
+///
+/// Edges within `bounce_ms` of the last accepted change are ignored as bounce.
+///
+/// Events are published as soon as they are known: a long press once the button has been held
+/// for [`LONG_PRESS_MS`], and a short press once [`DOUBLE_PRESS_MS`] has passed without a
+/// second press. A held button only publishes [`ButtonEvent::Repeat`] if `auto_repeat` is set.
+async fn run_button(btn: ExtiInput<'static>, id: ButtonId, bounce_ms: u64, auto_repeat: bool) -> ! {
+    let mut btn = DebouncedButton::new(btn, id, bounce_ms);
+    let publish = |event: ButtonEvent| {
+        info!("{} {}", id, event);
+        BUTTON_EVENTS
+            .immediate_publisher()
+            .publish_immediate((id, event));
+    };
+
     loop {
-        btn2.wait_for_falling_edge().await;
-        info!("Btn 2 Pressed!");
-        Timer::after_millis(BOUNCE_DELAY).await;
-
-        i += 1;
-        btn2.wait_for_high().await;
-        Timer::after_millis(BOUNCE_DELAY).await;
-        info!("Btn 2 Released {} times!", i);
+        let pressed_at = btn.wait_for(true).await;
+        if HELD[id.other() as usize].load(Relaxed) {
+            publish(ButtonEvent::Chord);
+            btn.wait_for(false).await;
+            continue;
+        }
+
+        // Long press if the button is still held
+        let long_press = pressed_at + Duration::from_millis(LONG_PRESS_MS);
+        let Ok(released_at) = with_deadline(long_press, btn.wait_for(false)).await else {
+            publish(ButtonEvent::LongPress);
+            if auto_repeat {
+                let mut repeat_at = pressed_at + Duration::from_millis(REPEAT_DELAY_MS);
+                while with_deadline(repeat_at, btn.wait_for(false)).await.is_err() {
+                    publish(ButtonEvent::Repeat);
+                    repeat_at += Duration::from_millis(REPEAT_INTERVAL_MS);
+                }
+            } else {
+                btn.wait_for(false).await;
+            }
+            continue;
+        };
+
+        // Double press if it is pressed again soon after release
+        let double_press = released_at + Duration::from_millis(DOUBLE_PRESS_MS);
+        if with_deadline(double_press, btn.wait_for(true))
+            .await
+            .is_err()
+        {
+            publish(ButtonEvent::ShortPress);
+            continue;
+        }
+        publish(ButtonEvent::DoublePress);
+        btn.wait_for(false).await;
     }
 }
+
+/// `bounce_ms` - Debounce time, [`BUTTON1_BOUNCE_MS`] unless the button hardware changes
+#[embassy_executor::task]
+pub async fn btn1_task(btn1: ExtiInput<'static>, bounce_ms: u64) {
+    run_button(btn1, ButtonId::Button1, bounce_ms, BUTTON1_AUTO_REPEAT).await
+}
+
+/// `bounce_ms` - Debounce time, [`BUTTON2_BOUNCE_MS`] unless the button hardware changes
+#[embassy_executor::task]
+pub async fn btn2_task(btn2: ExtiInput<'static>, bounce_ms: u64) {
+    run_button(btn2, ButtonId::Button2, bounce_ms, BUTTON2_AUTO_REPEAT).await
+}
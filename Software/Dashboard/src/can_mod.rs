@@ -12,158 +12,1104 @@
 //! dropped before then. See <a href="https://stackoverflow.com/questions/57467555/will-the-non-lexical-lifetime-borrow-checker-release-locks-prematurely">here</a>
 //! for more information.
 //! </div>
+//!
+//! ## Acceptance Filters
+//!
+//! [`configure_rx_filters`] only accepts the reserved ID blocks the dashboard decodes, see
+//! [`id_range_mask`](crate::eco_can::id_range_mask). Everything else is rejected by the
+//! peripheral, so it never interrupts the CPU.
+//!
+//! Each block is stored in the FIFO [`rx_fifo`] picks for it. The blocks in [`FIFO0_BLOCKS`]
+//! go into FIFO 0, and everything else into FIFO 1:
+//!
+//! | Block         | FIFO | Contents                              |
+//! |---------------|------|---------------------------------------|
+//! | 0x000 - 0x00F | 0    | Safety messages, such as the H2 alarm |
+//! | Others        | 1    | Telemetry                             |
+//!
+//! The driver always empties FIFO 0 before reading FIFO 1, so a safety message is handled next
+//! even when a burst of telemetry is already queued. Both FIFOs are read through the one
+//! [`CanRx`], so they share [`can_receive_task`], and moving a block to FIFO 0 is all it takes
+//! for it to be read first.
+//!
+//! ## Error States
+//!
+//! The controller counts transmit and receive errors, and moves from error-active to
+//! error-warning at 96 and error-passive at 128, before going bus-off at 256. An error-passive
+//! node can still send and receive, but the bus is degrading. [`can_receive_task`] reads the
+//! state every [`BUS_STATUS_INTERVAL`] into [`CAN_BUS_STATUS`], and logs every change.
 
 use bincode::{
     Decode, Encode,
-    config::Configuration,
     error::{DecodeError, EncodeError},
 };
-use defmt::*;
-use embassy_stm32::can::{CanRx, CanTx, Frame, frame::FdFrame};
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering::Relaxed};
+use core::task::Poll;
+use defmt::Format;
+use embassy_futures::poll_once;
+use embassy_futures::select::{Either3, select3};
+use embassy_stm32::can::{
+    CanConfigurator, CanRx, CanTx, Frame, OperatingMode, Properties,
+    config::GlobalFilter,
+    enums::{BusError, BusErrorMode},
+    filter::{Action, EXTENDED_FILTER_MAX, ExtendedFilter, FilterType, StandardFilter},
+    frame::{FdEnvelope, FdFrame, Header},
+};
+use embassy_stm32::pac;
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
-use embassy_time::Timer;
+use embassy_time::{Duration, Instant, Ticker, Timer, with_timeout};
 use embedded_can::Id;
 
+#[cfg(debug_assertions)]
+use crate::fixtures_mod::package_fixtures;
 use crate::{
-    btn_mod::BTN_SIGNAL,
+    btn_mod::{BUTTON_EVENTS, ButtonEvent, ButtonId},
+    can_stats_mod::{CAN_STATS, FrameRateMeter},
+    can_timing_mod::{CanSettings, CanTimings, FDCAN_KERNEL_CLOCK},
+    checksum_mod::{ChecksumError, has_checksum, strip_checksum},
+    demo_mod::demo_mode,
     eco_can::{
-        ECOCAN_H2Pack1_t, ECOCAN_H2Pack2_t, FDCAN_BOOSTPack1_t, FDCAN_BOOSTPack2_t,
+        DASH_Heartbeat_t, DASH_IndicatorCmd_t, DASH_RelayCmd_t, ECOCAN_H2Pack1_t, ECOCAN_H2Pack2_t,
+        ECOCAN_RelPackChrg_t, FDCAN_BATTPack2_t, FDCAN_BOOSTPack1_t, FDCAN_BOOSTPack2_t,
         FDCAN_BOOSTPack3_t, FDCAN_FccPack1_t, FDCAN_FccPack2_t, FDCAN_FccPack3_t, FDCAN_FetPack_t,
-        FDCAN_RelPackCap_t, FDCAN_RelPackFc_t, FDCAN_RelPackMtr_t, FDCANPack, RelayState,
+        FDCAN_H2ALARM_ID, FDCAN_RelPackCap_t, FDCAN_RelPackFc_t, FDCAN_RelPackMtr_t,
+        FDCAN_RelPackNrg_t, FDCAN_SYNCLED_ID, FDCANPack, PackageFields, RelayState, decode_package,
+        encode_package, id_matches_mask, id_range_mask,
     },
+    event_log_mod::log_event,
+    led_mod::set_indicator,
+    log_mod::{debug, error, info, trace, verbose, warn},
+    peak_mod::{record_cap_peaks, record_fc_peaks, record_fcc_peaks, record_mtr_peaks},
+    rate_limit_mod::UNKNOWN_ID_LOG,
+    safe_state_mod::{Fault, SAFE_STATE_SIGNAL, enter_safe_state, safe_state},
+    timed_state_mod::TimedStateMachine,
+    timestamp_mod::{CAN_TIMEBASE, wrap_period},
+    trip_mod::{record_trip_charge, record_trip_energy},
+    warning_mod::{update_fc_temp, update_fc_voltage, update_fcc_bme_temp, update_h2_bme_temp},
+    watchdog_mod::{CriticalTask, check_in},
+};
+
+/// Bitrates the CAN peripheral is configured with
+pub const CAN_SETTINGS: CanSettings = CanSettings::DEFAULT;
+/// Nominal bit rate of the CAN bus
+pub const CAN_BAUD_RATE: u32 = CAN_SETTINGS.nominal_bitrate;
+
+/// Maximum frames processed before the receive task yields to other tasks
+///
+/// Sally uses ~50 messages per second, so this is only reached during a storm of frames.
+const RX_DRAIN_LIMIT: u32 = 16;
+/// Longest the receive task waits for a frame before checking in with the watchdog, and reading
+/// the error state
+const RX_IDLE_CHECK_IN: Duration = BUS_STATUS_INTERVAL;
+// The FIFOs are seen empty at least this often on a quiet bus, so a frame's timestamp can't
+// have wrapped unnoticed, see timestamp_mod
+const _: () = assert!(RX_IDLE_CHECK_IN.as_ticks() < wrap_period(CAN_BAUD_RATE).as_ticks());
+
+/// Consecutive CAN receive errors, reset when a frame is decoded
+pub static CAN_ERROR_COUNT: AtomicU32 = AtomicU32::new(0);
+/// CAN transmit errors since boot
+pub static CAN_TX_ERROR_COUNT: AtomicU32 = AtomicU32::new(0);
+/// Consecutive receive errors before the CAN peripheral is restarted
+pub const CAN_ERROR_LIMIT: u32 = 16;
+/// Maximum time to wait for the CAN peripheral to rejoin the bus after bus-off
+const BUS_OFF_RECOVERY_TIMEOUT: Duration = Duration::from_millis(100);
+/// Maximum time for the CAN peripheral to enter or leave init mode, it takes a few clock cycles
+const CAN_INIT_TIMEOUT: Duration = Duration::from_millis(1);
+/// Time to wait after a failed restart before handling more errors
+const CAN_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+/// Times the CAN peripheral was restarted since boot, see [`recover_can_bus`]
+pub static CAN_RESTART_COUNT: AtomicU32 = AtomicU32::new(0);
+/// The FDCAN instance the bus is on, must be the peripheral `main` starts CAN on
+const CAN_REGS: pac::can::Fdcan = pac::FDCAN2;
+/// How often the controller's error state is read
+pub const BUS_STATUS_INTERVAL: Duration = Duration::from_millis(250);
+/// An error counter at or above this puts the controller in [`CanBusState::Warning`]
+pub const ERROR_WARNING_LIMIT: u8 = 96;
+
+/// The controller's error state, from least to most severe
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CanBusState {
+    Active,
+    /// Still error-active, but an error counter has reached [`ERROR_WARNING_LIMIT`]
+    Warning,
+    Passive,
+    BusOff,
+}
+
+impl CanBusState {
+    pub const fn name(self) -> &'static str {
+        match self {
+            CanBusState::Active => "error-active",
+            CanBusState::Warning => "error-warning",
+            CanBusState::Passive => "error-passive",
+            CanBusState::BusOff => "bus-off",
+        }
+    }
+
+    /// Shown in the on-screen event log when the state is entered
+    pub const fn event(self) -> &'static str {
+        match self {
+            CanBusState::Active => "CAN error-active",
+            CanBusState::Warning => "CAN error-warning",
+            CanBusState::Passive => "CAN error-passive",
+            CanBusState::BusOff => "CAN bus-off",
+        }
+    }
+}
+
+/// The controller's error state and counters
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub struct CanBusStatus {
+    pub state: CanBusState,
+    /// Transmit error counter, TEC
+    pub tx_errors: u8,
+    /// Receive error counter, REC
+    pub rx_errors: u8,
+}
+
+impl CanBusStatus {
+    pub const ACTIVE: Self = Self {
+        state: CanBusState::Active,
+        tx_errors: 0,
+        rx_errors: 0,
+    };
+
+    /// Reads the state and counters from the peripheral
+    ///
+    /// The driver doesn't expose the error warning flag, so it is worked out from the counters.
+    /// Reading the state clears the last error code, so the driver may miss reporting an error
+    /// that happens at the same time.
+    pub fn read(properties: &Properties) -> Self {
+        let tx_errors = properties.tx_error_count();
+        let rx_errors = properties.rx_error_count();
+        let state = match properties.bus_error_mode() {
+            BusErrorMode::BusOff => CanBusState::BusOff,
+            BusErrorMode::ErrorPassive => CanBusState::Passive,
+            BusErrorMode::ErrorActive if tx_errors.max(rx_errors) >= ERROR_WARNING_LIMIT => {
+                CanBusState::Warning
+            }
+            BusErrorMode::ErrorActive => CanBusState::Active,
+        };
+        Self {
+            state,
+            tx_errors,
+            rx_errors,
+        }
+    }
+}
+
+/// The controller's error state, as last read by [`can_receive_task`]
+pub static CAN_BUS_STATUS: Mutex<ThreadModeRawMutex, CanBusStatus> =
+    Mutex::new(CanBusStatus::ACTIVE);
+
+/// Reads the controller's error state into [`CAN_BUS_STATUS`], logging any change
+async fn update_bus_status(properties: &Properties) {
+    let status = CanBusStatus::read(properties);
+    let mut current = CAN_BUS_STATUS.lock().await;
+    let previous = current.state;
+    *current = status;
+    drop(current);
+
+    if status.state > previous {
+        log_event(status.state.event());
+        warn!(
+            "CAN is {} (was {}), tx errors: {}, rx errors: {}",
+            status.state.name(),
+            previous.name(),
+            status.tx_errors,
+            status.rx_errors,
+        );
+    } else if status.state < previous {
+        info!("CAN is {} (was {})", status.state.name(), previous.name());
+    }
+}
+
+/// Every ID the dashboard decodes, the H2 alarm, sync LED and relay state followed by
+/// [`PACKAGE_IDS`]
+pub const RX_IDS: [u32; PACKAGE_IDS.len() + 3] = {
+    let mut ids = [0; PACKAGE_IDS.len() + 3];
+    ids[0] = FDCAN_H2ALARM_ID as u32;
+    ids[1] = FDCAN_SYNCLED_ID as u32;
+    ids[2] = RelayState::FDCAN_ID;
+    let mut i = 0;
+    while i < PACKAGE_IDS.len() {
+        ids[i + 3] = PACKAGE_IDS[i];
+        i += 1;
+    }
+    ids
 };
 
-const BINCODE_CONFIG: Configuration<bincode::config::BigEndian, bincode::config::Fixint> =
-    bincode::config::standard()
-        .with_big_endian()
-        .with_fixed_int_encoding();
+/// Upper bits of an extended ID, which must be zero for the dashboard's IDs
+const EXTENDED_HIGH_BITS: u32 = 0x1FFF_F800;
+
+/// The reserved block of the highest priority messages, which every board must accept
+const SAFETY_BLOCK: u32 = id_range_mask(FDCAN_H2ALARM_ID as u32).0;
+
+/// The two receive FIFOs of the FDCAN peripheral
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum RxFifo {
+    /// Read before FIFO 1, for the messages that must be handled first
+    Fifo0,
+    Fifo1,
+}
+
+impl RxFifo {
+    /// The filter action storing a frame in this FIFO
+    const fn action(self) -> Action {
+        match self {
+            RxFifo::Fifo0 => Action::StoreInFifo0,
+            RxFifo::Fifo1 => Action::StoreInFifo1,
+        }
+    }
+}
+
+/// Reserved blocks stored in FIFO 0, so they are read before any telemetry
+///
+/// Add a block here to have it handled with the same priority as the safety messages. Keep it
+/// short, every block added here can delay the H2 alarm.
+pub const FIFO0_BLOCKS: &[u32] = &[SAFETY_BLOCK];
+
+/// The FIFO frames with `id` are stored in
+pub const fn rx_fifo(id: u32) -> RxFifo {
+    let block = id_range_mask(id).0;
+    let mut i = 0;
+    while i < FIFO0_BLOCKS.len() {
+        if FIFO0_BLOCKS[i] == block {
+            return RxFifo::Fifo0;
+        }
+        i += 1;
+    }
+    RxFifo::Fifo1
+}
+
+// The safety block, with the H2 alarm and sync LED, jumps the queue, telemetry doesn't
+const _: () = {
+    core::assert!(matches!(rx_fifo(FDCAN_H2ALARM_ID as u32), RxFifo::Fifo0));
+    core::assert!(matches!(rx_fifo(FDCAN_SYNCLED_ID as u32), RxFifo::Fifo0));
+    core::assert!(matches!(rx_fifo(PACKAGE_IDS[0]), RxFifo::Fifo1));
+};
+
+/// Sets up acceptance filters for the reserved blocks containing `ids`
+///
+/// The safety block is always accepted, and each block goes into the FIFO [`rx_fifo`] picks.
+/// One standard and one extended bit mask filter is used per block, so frames are accepted
+/// whichever ID format the sender uses. Frames not matching a filter must be rejected with
+/// the global filter for this to have any effect.
+pub fn configure_rx_filters(properties: &Properties, ids: &[u32]) {
+    let mut slot: u8 = 0;
+    let blocks = core::iter::once(SAFETY_BLOCK).chain(ids.iter().copied());
+    for (i, id) in blocks.clone().enumerate() {
+        let (filter, mask) = id_range_mask(id);
+        // Skip blocks that already have a filter
+        if blocks
+            .clone()
+            .take(i)
+            .any(|prev| id_range_mask(prev).0 == filter)
+        {
+            continue;
+        }
+        let fifo = rx_fifo(filter);
+        let action = fifo.action();
+        // There are fewer extended filter slots than standard
+        core::assert!(slot < EXTENDED_FILTER_MAX, "Not enough CAN filter slots");
+        properties.set_standard_filter(
+            slot.into(),
+            StandardFilter {
+                filter: FilterType::BitMask {
+                    filter: filter as u16,
+                    mask: mask as u16,
+                },
+                action,
+            },
+        );
+        properties.set_extended_filter(
+            slot.into(),
+            ExtendedFilter {
+                filter: FilterType::BitMask {
+                    filter,
+                    mask: mask | EXTENDED_HIGH_BITS,
+                },
+                action,
+            },
+        );
+        debug!(
+            "CAN filter {}: ID {:#05x} mask {:#05x} into {}",
+            slot, filter, mask, fifo
+        );
+        slot += 1;
+    }
+}
+
+/// Bit timings for [`CAN_SETTINGS`], derived from the FDCAN kernel clock and checked at
+/// compile time
+pub const CAN_TIMINGS: CanTimings = CAN_SETTINGS.timings(FDCAN_KERNEL_CLOCK);
+
+/// Configures the filters and bit timings, and joins the bus
+///
+/// Only the reserved blocks of [`RX_IDS`] are accepted, each into the FIFO [`rx_fifo`] picks, so
+/// other traffic doesn't interrupt the CPU. Both FIFOs are read through the returned [`CanRx`].
+pub fn start_can(
+    mut can: CanConfigurator<'static>,
+) -> (CanTx<'static>, CanRx<'static>, Properties) {
+    configure_rx_filters(can.properties(), &RX_IDS);
+    can.set_config(
+        CAN_TIMINGS
+            .apply(can.config())
+            .set_global_filter(GlobalFilter::reject_all()),
+    );
+    debug!(
+        "CAN settings: {}, bit timings: {}",
+        CAN_SETTINGS, CAN_TIMINGS
+    );
+    can.start(OperatingMode::NormalOperationMode).split()
+}
+
+/// How long a package can go without being received before it is considered stale
+pub const STALE_AFTER: Duration = Duration::from_secs(1);
+
+/// A CAN package, and the time it was last received
+///
+/// Derefs to the package, so its fields can be accessed directly.
+#[derive(Clone, Debug)]
+pub struct Timestamped<T> {
+    pub value: T,
+    /// `None` if the package has never been received
+    pub last_seen: Option<Instant>,
+}
+
+impl<T> Timestamped<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            last_seen: None,
+        }
+    }
+
+    /// Time since the package was last received, `None` if it has never been received
+    pub fn age(&self, now: Instant) -> Option<Duration> {
+        self.last_seen
+            .map(|last_seen| now.saturating_duration_since(last_seen))
+    }
+
+    /// Returns true if the package has not been received within [`STALE_AFTER`]
+    pub fn is_stale(&self, now: Instant) -> bool {
+        self.age(now).is_none_or(|age| age > STALE_AFTER)
+    }
+}
+
+impl<T> Deref for Timestamped<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Timestamped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
 
 pub static RELAY_STATE: Mutex<ThreadModeRawMutex, RelayState> = Mutex::new(RelayState::RELAY_RUN);
 
-pub static FET_DATA: Mutex<ThreadModeRawMutex, FDCAN_FetPack_t> = Mutex::new(FDCAN_FetPack_t {
-    fet_config: 0,
-    input_volt: 0,
-    cap_volt: 0,
-    cap_curr: 0,
-    res_curr: 0,
-    out_curr: 0,
-});
-
-pub static FCC_PACK1_DATA: Mutex<ThreadModeRawMutex, FDCAN_FccPack1_t> =
-    Mutex::new(FDCAN_FccPack1_t {
+/// State of the H2 alarm latch
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum H2AlarmState {
+    Clear,
+    /// The alarm has been broadcast since the latch was last cleared
+    Tripped,
+}
+
+/// The H2 alarm latch, tripped once the alarm is broadcast and latched until the safe state is
+/// cleared, see [`clear_safe_state`](crate::safe_state_mod::clear_safe_state)
+///
+/// The machine's timer is how long the alarm has been latched, or clear.
+pub static H2_ALARM: Mutex<ThreadModeRawMutex, TimedStateMachine<H2AlarmState>> = Mutex::new(
+    TimedStateMachine::new(H2AlarmState::Clear, Instant::from_ticks(0)),
+);
+
+/// True while the H2 alarm is latched
+pub async fn h2_alarm_tripped() -> bool {
+    H2_ALARM.lock().await.state() == H2AlarmState::Tripped
+}
+
+/// Latches the H2 alarm, returns false if it already was
+async fn trip_h2_alarm() -> bool {
+    let mut alarm = H2_ALARM.lock().await;
+    if alarm.state() == H2AlarmState::Tripped {
+        return false;
+    }
+    alarm.transition(H2AlarmState::Tripped, Instant::now());
+    true
+}
+
+/// Clears the latched H2 alarm
+pub async fn clear_h2_alarm() {
+    let now = Instant::now();
+    let mut alarm = H2_ALARM.lock().await;
+    let latched = alarm.elapsed(now);
+    alarm.transition(H2AlarmState::Clear, now);
+    info!("H2 alarm cleared after {} s", latched.as_secs());
+}
+
+/// True while the sync LED broadcast commands the LEDs on
+///
+/// Every board receives the broadcast at the same moment, so the LED task uses it to blink in
+/// unison with the other boards.
+pub static SYNC_LED: Mutex<ThreadModeRawMutex, bool> = Mutex::new(false);
+
+pub static FET_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_FetPack_t>> =
+    Mutex::new(Timestamped::new(FDCAN_FetPack_t {
+        fet_config: 0,
+        input_volt: 0,
+        cap_volt: 0,
+        cap_curr: 0,
+        res_curr: 0,
+        out_curr: 0,
+    }));
+
+pub static FCC_PACK1_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_FccPack1_t>> =
+    Mutex::new(Timestamped::new(FDCAN_FccPack1_t {
         fc_press: 0,
         fc_temp: 0,
-    });
-pub static FCC_PACK2_DATA: Mutex<ThreadModeRawMutex, FDCAN_FccPack2_t> =
-    Mutex::new(FDCAN_FccPack2_t {
+    }));
+pub static FCC_PACK2_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_FccPack2_t>> =
+    Mutex::new(Timestamped::new(FDCAN_FccPack2_t {
         fan_rpm1: 0,
         fan_rpm2: 0,
-    });
-pub static FCC_PACK3_DATA: Mutex<ThreadModeRawMutex, FDCAN_FccPack3_t> =
-    Mutex::new(FDCAN_FccPack3_t {
+    }));
+pub static FCC_PACK3_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_FccPack3_t>> =
+    Mutex::new(Timestamped::new(FDCAN_FccPack3_t {
         bme_temp: 0,
         bme_humid: 0,
-    });
+    }));
 
-pub static H2_PACK1_DATA: Mutex<ThreadModeRawMutex, ECOCAN_H2Pack1_t> =
-    Mutex::new(ECOCAN_H2Pack1_t {
+pub static H2_PACK1_DATA: Mutex<ThreadModeRawMutex, Timestamped<ECOCAN_H2Pack1_t>> =
+    Mutex::new(Timestamped::new(ECOCAN_H2Pack1_t {
         h2_sense_1: 0,
         h2_sense_2: 0,
         h2_sense_3: 0,
         h2_sense_4: 0,
-    });
-pub static H2_PACK2_DATA: Mutex<ThreadModeRawMutex, ECOCAN_H2Pack2_t> =
-    Mutex::new(ECOCAN_H2Pack2_t {
+    }));
+pub static H2_PACK2_DATA: Mutex<ThreadModeRawMutex, Timestamped<ECOCAN_H2Pack2_t>> =
+    Mutex::new(Timestamped::new(ECOCAN_H2Pack2_t {
         bme_temp: 0,
         bme_humid: 0,
         imon_7v: 0,
         imon_12v: 0,
-    });
+    }));
 
-pub static BOOST_PACK1_DATA: Mutex<ThreadModeRawMutex, FDCAN_BOOSTPack1_t> =
-    Mutex::new(FDCAN_BOOSTPack1_t {
+pub static BOOST_PACK1_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_BOOSTPack1_t>> =
+    Mutex::new(Timestamped::new(FDCAN_BOOSTPack1_t {
         in_curr: 0,
         in_volt: 0,
-    });
-pub static BOOST_PACK2_DATA: Mutex<ThreadModeRawMutex, FDCAN_BOOSTPack2_t> =
-    Mutex::new(FDCAN_BOOSTPack2_t {
+    }));
+pub static BOOST_PACK2_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_BOOSTPack2_t>> =
+    Mutex::new(Timestamped::new(FDCAN_BOOSTPack2_t {
         out_curr: 0,
         out_volt: 0,
-    });
-pub static BOOST_PACK3_DATA: Mutex<ThreadModeRawMutex, FDCAN_BOOSTPack3_t> =
-    Mutex::new(FDCAN_BOOSTPack3_t {
+    }));
+pub static BOOST_PACK3_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_BOOSTPack3_t>> =
+    Mutex::new(Timestamped::new(FDCAN_BOOSTPack3_t {
         efficiency: 0,
         joules: 0,
-    });
+    }));
 
 /// Fuel Cell Reading
-pub static REL_FC_PACK: Mutex<ThreadModeRawMutex, FDCAN_RelPackFc_t> =
-    Mutex::new(FDCAN_RelPackFc_t {
+pub static REL_FC_PACK: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_RelPackFc_t>> =
+    Mutex::new(Timestamped::new(FDCAN_RelPackFc_t {
         fc_volt: 0,
         fc_curr: 0,
-    });
-pub static REL_CAP_PACK: Mutex<ThreadModeRawMutex, FDCAN_RelPackCap_t> =
-    Mutex::new(FDCAN_RelPackCap_t {
+    }));
+pub static REL_CAP_PACK: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_RelPackCap_t>> =
+    Mutex::new(Timestamped::new(FDCAN_RelPackCap_t {
         cap_volt: 0,
         cap_curr: 0,
-    });
-pub static RELAY_MOTOR_PACK: Mutex<ThreadModeRawMutex, FDCAN_RelPackMtr_t> =
-    Mutex::new(FDCAN_RelPackMtr_t {
+    }));
+pub static REL_NRG_PACK: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_RelPackNrg_t>> =
+    Mutex::new(Timestamped::new(FDCAN_RelPackNrg_t {
+        fc_joules: 0,
+        cap_joules: 0,
+    }));
+pub static REL_CHRG_PACK: Mutex<ThreadModeRawMutex, Timestamped<ECOCAN_RelPackChrg_t>> =
+    Mutex::new(Timestamped::new(ECOCAN_RelPackChrg_t {
+        fc_coloumbs: 0,
+        cap_coloumbs: 0,
+    }));
+pub static RELAY_MOTOR_PACK: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_RelPackMtr_t>> =
+    Mutex::new(Timestamped::new(FDCAN_RelPackMtr_t {
         mtr_volt: 0,
         mtr_curr: 0,
-    });
+    }));
+
+/// Battery Reading
+pub static BATT_PACK2_DATA: Mutex<ThreadModeRawMutex, Timestamped<FDCAN_BATTPack2_t>> =
+    Mutex::new(Timestamped::new(FDCAN_BATTPack2_t {
+        out_curr: 0,
+        out_volt: 0,
+    }));
+
+/// The last turn signal command received, see [`INDICATOR`](crate::led_mod::INDICATOR)
+pub static INDICATOR_CMD: Mutex<ThreadModeRawMutex, Timestamped<DASH_IndicatorCmd_t>> =
+    Mutex::new(Timestamped::new(DASH_IndicatorCmd_t { state: 0 }));
 
 /// Responsible for handling the reception of CAN messages
+///
+/// Frames are drained from the RX FIFO until it is empty, so a frame is only dropped if the FIFO
+/// overflows while this task is not running. To keep a storm of frames from starving the other
+/// tasks, the task yields after [`RX_DRAIN_LIMIT`] frames.
 #[embassy_executor::task]
-pub async fn can_receive_task(mut can: CanRx<'static>) {
+pub async fn can_receive_task(can: CanRx<'static>, properties: Properties) {
+    #[cfg(debug_assertions)]
+    check_package_encoding();
+
+    // Replay the recorded session instead of reading the bus
+    #[cfg(feature = "sim")]
+    {
+        let _ = (can, properties);
+        crate::sim_mod::replay_bus_log().await
+    }
+    #[cfg(not(feature = "sim"))]
+    read_bus(can, properties).await
+}
+
+/// Decodes frames from the bus forever
+#[cfg_attr(feature = "sim", allow(dead_code))]
+async fn read_bus(mut can: CanRx<'static>, properties: Properties) -> ! {
     // Use the FD API's even if we don't get FD packets.
     let debug = false;
     if debug {
         _debug_can_rx(&mut can).await;
     }
+    let mut next_status = Instant::now();
     loop {
-        // Await CAN frame
-        match can.read_fd().await {
-            Ok(envelope) => process_rx_can_frame(&envelope.frame).await,
-            Err(err) => error!("Error in frame: {}", err),
+        check_in(CriticalTask::CanRx);
+        if Instant::now() >= next_status {
+            update_bus_status(&properties).await;
+            next_status = Instant::now() + BUS_STATUS_INTERVAL;
+        }
+        // Await CAN frame, waking up to check in with the watchdog if the bus is quiet
+        let Ok(result) = with_timeout(RX_IDLE_CHECK_IN, can.read_fd()).await else {
+            CAN_TIMEBASE.lock().await.fifo_empty(Instant::now());
+            continue;
+        };
+        handle_rx_result(result, &properties).await;
+
+        // Drain any frames that arrived while processing
+        let mut drained = 1;
+        while let Poll::Ready(result) = poll_once(can.read_fd()) {
+            handle_rx_result(result, &properties).await;
+            drained += 1;
+            if drained >= RX_DRAIN_LIMIT {
+                trace!("CAN RX drain limit reached, yielding");
+                check_in(CriticalTask::CanRx);
+                Timer::after_millis(1).await;
+                drained = 0;
+            }
+        }
+        // Frames read from here on arrived after now, which bounds how far they can have wrapped
+        CAN_TIMEBASE.lock().await.fifo_empty(Instant::now());
+    }
+}
+
+/// Processes a received frame or error
+async fn handle_rx_result(result: Result<FdEnvelope, BusError>, properties: &Properties) {
+    match result {
+        Ok(envelope) => {
+            let mut timebase = CAN_TIMEBASE.lock().await;
+            let received = timebase.update(envelope.ts, embassy_time::Instant::now());
+            if timebase.is_backlogged() {
+                warn!("CAN frames are read too late, timestamps may have wrapped");
+            }
+            drop(timebase);
+            if process_rx_can_frame(&envelope.frame).await {
+                CAN_ERROR_COUNT.store(0, Relaxed);
+            }
+            // Timed once handled, so the latency includes acting on the frame
+            let id = frame_id(&envelope.frame);
+            if id_matches_mask(id, id_range_mask(SAFETY_BLOCK)) {
+                CAN_STATS
+                    .lock()
+                    .await
+                    .record_safety_frame(id, received, Instant::now());
+            }
+        }
+        Err(err) => {
+            error!("Error in frame: {}", err);
+            let count = CAN_ERROR_COUNT.fetch_add(1, Relaxed) + 1;
+            // Only the start of a run of errors, so it doesn't push everything else out
+            if count == 1 {
+                log_event("CAN receive error");
+            }
+            if count.is_multiple_of(CAN_ERROR_LIMIT) {
+                recover_can_bus(properties, count).await;
+            }
+        }
+    }
+}
+
+/// Recovers the CAN peripheral after repeated errors
+///
+/// When the peripheral goes bus-off, the driver's interrupt restarts it, but it only rejoins
+/// the bus after seeing 128 sequences of 11 recessive bits. This waits for that to happen
+/// so a disconnected bus doesn't flood the log with frame errors.
+///
+/// If it is still bus-off after [`BUS_OFF_RECOVERY_TIMEOUT`], or the errors came without it
+/// going bus-off, the peripheral is restarted with [`restart_can_peripheral`], and counted in
+/// [`CAN_RESTART_COUNT`]. If the restart fails, this waits [`CAN_RESTART_BACKOFF`] before
+/// returning, so the next errors don't retry it straight away.
+async fn recover_can_bus(properties: &Properties, error_count: u32) {
+    warn!(
+        "{} consecutive CAN errors ({}), tx errors: {}, rx errors: {}. Restarting CAN",
+        error_count,
+        properties.bus_error_mode(),
+        properties.tx_error_count(),
+        properties.rx_error_count(),
+    );
+
+    let bus_off = matches!(properties.bus_error_mode(), BusErrorMode::BusOff);
+    if bus_off && wait_for_bus_on(properties).await {
+        info!("CAN rejoined the bus ({})", properties.bus_error_mode());
+        return;
+    }
+    if bus_off {
+        error!("CAN is still bus-off after {}", BUS_OFF_RECOVERY_TIMEOUT);
+    }
+
+    if let Err(err) = restart_can_peripheral() {
+        error!(
+            "CAN restart failed: {}, retrying in {}",
+            err, CAN_RESTART_BACKOFF
+        );
+        log_event("CAN restart failed");
+        Timer::after(CAN_RESTART_BACKOFF).await;
+        return;
+    }
+    let restarts = CAN_RESTART_COUNT.fetch_add(1, Relaxed) + 1;
+    log_event("CAN restarted");
+    if wait_for_bus_on(properties).await {
+        info!(
+            "CAN restarted ({}), {} restarts",
+            properties.bus_error_mode(),
+            restarts
+        );
+    } else {
+        error!("CAN is still bus-off after restart {}", restarts);
+    }
+}
+
+/// Waits up to [`BUS_OFF_RECOVERY_TIMEOUT`] for the peripheral to leave bus-off, returns false
+/// if it didn't
+async fn wait_for_bus_on(properties: &Properties) -> bool {
+    let start = Instant::now();
+    while matches!(properties.bus_error_mode(), BusErrorMode::BusOff) {
+        if start.elapsed() > BUS_OFF_RECOVERY_TIMEOUT {
+            return false;
         }
-        // Sally uses ~50 messages per second
         Timer::after_millis(1).await;
     }
+    true
 }
 
-/// Responsible for handling the reception of CAN messages
+/// The CAN peripheral didn't acknowledge a change of init mode within [`CAN_INIT_TIMEOUT`]
+#[derive(Debug, Format)]
+pub enum CanRestartError {
+    /// It didn't enter init mode, so it is still on the bus
+    EnterInit,
+    /// It didn't leave init mode, so it is off the bus
+    LeaveInit,
+}
+
+/// Restarts the FDCAN protocol controller, keeping its configuration
+///
+/// Entering init mode takes the controller off the bus. Setting CCE as well flushes the receive
+/// FIFOs and cancels any pending transmissions, but keeps the bit timings, filters and the rest
+/// of the configuration. Leaving init mode starts the controller again, and it rejoins the bus
+/// once it sees 11 recessive bits.
+fn restart_can_peripheral() -> Result<(), CanRestartError> {
+    CAN_REGS.cccr().modify(|w| w.set_init(true));
+    if !wait_for_init(true) {
+        return Err(CanRestartError::EnterInit);
+    }
+    CAN_REGS.cccr().modify(|w| w.set_cce(true));
+    CAN_REGS.cccr().modify(|w| w.set_cce(false));
+    CAN_REGS.cccr().modify(|w| w.set_init(false));
+    if !wait_for_init(false) {
+        return Err(CanRestartError::LeaveInit);
+    }
+    Ok(())
+}
+
+/// Busy waits up to [`CAN_INIT_TIMEOUT`] for CCCR.INIT to read `init`, returns false if it didn't
+fn wait_for_init(init: bool) -> bool {
+    let deadline = Instant::now() + CAN_INIT_TIMEOUT;
+    while CAN_REGS.cccr().read().init() != init {
+        if Instant::now() > deadline {
+            return false;
+        }
+    }
+    true
+}
+
+/// A package owned by the dashboard, which it broadcasts on the bus
+///
+/// The relay state is the relay board's own package, the dashboard only asks for a change with
+/// [`DASH_RelayCmd_t`].
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum TxPackage {
+    /// [`DASH_RelayCmd_t`], only sent while a command is pending
+    RelayCommand,
+    /// [`DASH_Heartbeat_t`]
+    Heartbeat,
+}
+
+impl TxPackage {
+    /// The package's [`FDCANPack::PREFER_CLASSIC`]
+    const fn prefer_classic(self) -> bool {
+        match self {
+            TxPackage::RelayCommand => DASH_RelayCmd_t::PREFER_CLASSIC,
+            TxPackage::Heartbeat => DASH_Heartbeat_t::PREFER_CLASSIC,
+        }
+    }
+}
+
+/// How often the dashboard's heartbeat is broadcast, see [`DASH_Heartbeat_t`]
+pub const HEARTBEAT_PERIOD: Duration = Duration::from_secs(1);
+
+/// How often each dashboard-owned package is broadcast
+pub const TX_SCHEDULE: &[(TxPackage, Duration)] = &[
+    (TxPackage::RelayCommand, Duration::from_millis(100)),
+    (TxPackage::Heartbeat, HEARTBEAT_PERIOD),
+];
+
+/// Parses a version number from the package version at compile time
+const fn parse_version(digits: &str) -> u8 {
+    let digits = digits.as_bytes();
+    let mut value: u8 = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        core::assert!(digits[i].is_ascii_digit(), "version numbers are digits");
+        value = value * 10 + (digits[i] - b'0');
+        i += 1;
+    }
+    value
+}
+
+/// The firmware version sent in the heartbeat, the major version in the high nibble and the
+/// minor in the low, from the crate's version
+pub const FIRMWARE_VERSION: u8 = {
+    let major = parse_version(env!("CARGO_PKG_VERSION_MAJOR"));
+    let minor = parse_version(env!("CARGO_PKG_VERSION_MINOR"));
+    core::assert!(
+        major < 16 && minor < 16,
+        "the version must fit in a nibble each"
+    );
+    (major << 4) | minor
+};
+
+/// Heartbeats sent since boot, wrapping back to 0
+static HEARTBEAT_COUNTER: AtomicU16 = AtomicU16::new(0);
+
+/// The next heartbeat, counting it
+fn next_heartbeat() -> DASH_Heartbeat_t {
+    DASH_Heartbeat_t {
+        uptime_s: Instant::now().as_secs() as u32,
+        // Wraps on overflow, which receivers expect
+        counter: HEARTBEAT_COUNTER.fetch_add(1, Relaxed),
+        fw_version: FIRMWARE_VERSION,
+        reserved: 0,
+    }
+}
+
+/// How long a relay command is repeated for, if the relay board doesn't switch
+pub const RELAY_COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The relay state the dashboard is asking for, and when it was requested
+static RELAY_COMMAND: Mutex<ThreadModeRawMutex, Option<(RelayState, Instant)>> = Mutex::new(None);
+
+/// Reasons a relay command was refused
+#[derive(Debug, Format)]
+pub enum RelayCommandError {
+    /// The relay state can't be changed in the safe state, see [`crate::safe_state_mod`]
+    SafeState(Fault),
+}
+
+/// Asks the relay board to switch to `target`
+///
+/// The command is repeated by [`can_transmit_task`] until the relay board reports the new
+/// state, or [`RELAY_COMMAND_TIMEOUT`] passes.
+pub async fn request_relay_state(target: RelayState) -> Result<(), RelayCommandError> {
+    if let Some(fault) = safe_state().await {
+        warn!("Relay command ignored in the safe state ({})", fault);
+        return Err(RelayCommandError::SafeState(fault));
+    }
+    info!("Requesting relay state {}", target);
+    *RELAY_COMMAND.lock().await = Some((target, Instant::now()));
+    Ok(())
+}
+
+/// The state button 1 asks for next: standby to charging to running, and back to standby
+pub const fn next_relay_state(state: &RelayState) -> RelayState {
+    match state {
+        RelayState::RELAY_STRTP | RelayState::RELAY_STBY => RelayState::RELAY_CHRGE,
+        RelayState::RELAY_CHRGE => RelayState::RELAY_RUN,
+        RelayState::RELAY_RUN => RelayState::RELAY_STBY,
+    }
+}
+
+// Button 1 steps through charging and running, then back to standby
+const _: () = {
+    core::assert!(matches!(
+        next_relay_state(&RelayState::RELAY_STBY),
+        RelayState::RELAY_CHRGE
+    ));
+    core::assert!(matches!(
+        next_relay_state(&RelayState::RELAY_CHRGE),
+        RelayState::RELAY_RUN
+    ));
+    core::assert!(matches!(
+        next_relay_state(&RelayState::RELAY_RUN),
+        RelayState::RELAY_STBY
+    ));
+};
+
+/// The relay command to send, clearing it once the relay board has switched or it times out
+///
+/// Standby is commanded for as long as the dashboard is in the safe state.
+async fn pending_relay_command() -> Option<RelayState> {
+    if safe_state().await.is_some() {
+        *RELAY_COMMAND.lock().await = None;
+        return Some(RelayState::RELAY_STBY);
+    }
+    // Read first, so no other lock is taken while the command is held
+    let relay_state = RELAY_STATE.lock().await.clone();
+
+    let mut command = RELAY_COMMAND.lock().await;
+    let (target, requested) = command.clone()?;
+    if relay_state == target {
+        debug!("Relay board switched to {}", target);
+        *command = None;
+    } else if requested.elapsed() > RELAY_COMMAND_TIMEOUT {
+        warn!("Relay board didn't switch to {}", target);
+        *command = None;
+    }
+    command.as_ref().map(|(target, _)| target.clone())
+}
+
+/// How often the schedule is checked, periods in [`TX_SCHEDULE`] should be a multiple of this
+const TX_TICK: Duration = Duration::from_millis(10);
+
+/// Encodes a dashboard-owned package, returns its CAN ID and length, or `None` if there is
+/// nothing to send
+async fn encode_tx_package(
+    package: TxPackage,
+    tx_data: &mut [u8],
+) -> Result<Option<(u32, usize)>, EncodeError> {
+    match package {
+        TxPackage::RelayCommand => {
+            let Some(target) = pending_relay_command().await else {
+                return Ok(None);
+            };
+            let tx_len = encode_package(&DASH_RelayCmd_t::new(target), tx_data)?;
+            Ok(Some((DASH_RelayCmd_t::FDCAN_ID, tx_len)))
+        }
+        TxPackage::Heartbeat => {
+            let tx_len = encode_package(&next_heartbeat(), tx_data)?;
+            Ok(Some((DASH_Heartbeat_t::FDCAN_ID, tx_len)))
+        }
+    }
+}
+
+/// Reasons a CAN package could not be sent
+#[derive(Debug, Format)]
+pub enum TxError {
+    /// The package could not be encoded
+    Encode,
+    /// The encoded package doesn't fit in a frame
+    Frame,
+    /// A lower priority frame with this ID was dropped from the mailbox to make room
+    Dropped(u32),
+}
+
+/// A frame to send, classic or FD, see [`tx_frame`]
+pub enum TxFrame {
+    Classic(Frame),
+    Fd(FdFrame),
+}
+
+impl TxFrame {
+    pub fn header(&self) -> &Header {
+        match self {
+            TxFrame::Classic(frame) => frame.header(),
+            TxFrame::Fd(frame) => frame.header(),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self {
+            TxFrame::Classic(frame) => frame.data(),
+            TxFrame::Fd(frame) => frame.data(),
+        }
+    }
+}
+
+/// True if FD frames can be sent, set by [`CAN_SETTINGS`]
+const FD_ENABLED: bool = CAN_TIMINGS.data.is_some();
+
+/// Builds the frame `data` is sent in under `id`
+///
+/// A classic frame is built unless FD is enabled, or if `prefer_classic` is set for nodes that
+/// only accept classic CAN. FD is only enabled together with bit rate switching, so even a short
+/// FD frame gains from the faster data phase. Data over [`CLASSIC_MAX_BYTES`] can only be sent
+/// over FD.
+///
+/// [`CLASSIC_MAX_BYTES`]: crate::eco_can::CLASSIC_MAX_BYTES
+pub fn tx_frame(id: u32, data: &[u8], prefer_classic: bool) -> Result<TxFrame, TxError> {
+    let frame = if prefer_classic || !FD_ENABLED {
+        Frame::new_extended(id, data).map(TxFrame::Classic)
+    } else {
+        FdFrame::new_extended(id, data).map(TxFrame::Fd)
+    };
+    frame.map_err(|_| TxError::Frame)
+}
+
+/// Queues a frame, an error if a lower priority frame was dropped to make room
+async fn write_frame(can: &mut CanTx<'static>, frame: &TxFrame) -> Result<(), TxError> {
+    let dropped = match frame {
+        TxFrame::Classic(frame) => can.write(frame).await.map(|dropped| *dropped.header()),
+        TxFrame::Fd(frame) => can.write_fd(frame).await.map(|dropped| *dropped.header()),
+    };
+    match dropped {
+        None => Ok(()),
+        Some(dropped) => Err(TxError::Dropped(header_id(&dropped))),
+    }
+}
+
+/// Counts and logs a transmit error
+fn log_tx_result(id: u32, result: Result<(), TxError>) -> Result<(), TxError> {
+    if let Err(err) = &result {
+        CAN_TX_ERROR_COUNT.fetch_add(1, Relaxed);
+        error!("CAN Transmit Error for {:#05x}: {}", id, err);
+        log_event("CAN transmit error");
+    }
+    result
+}
+
+/// Sends a frame, counting and logging any error
+async fn send_frame(
+    can: &mut CanTx<'static>,
+    id: u32,
+    data: &[u8],
+    prefer_classic: bool,
+) -> Result<(), TxError> {
+    let result = match tx_frame(id, data, prefer_classic) {
+        Ok(frame) => write_frame(can, &frame).await,
+        Err(err) => Err(err),
+    };
+    log_tx_result(id, result)
+}
+
+/// Encodes a package into a frame with the package's own ID
+///
+/// The ID comes from the package type, so a package can't be sent under another package's ID.
+/// The frame is classic or FD as [`tx_frame`] picks for the package.
+pub fn package_frame<T: FDCANPack + Encode>(package: &T) -> Result<TxFrame, TxError> {
+    let mut tx_data = [0; 64];
+    let tx_len = encode_package(package, &mut tx_data).map_err(|_| TxError::Encode)?;
+    tx_frame(T::FDCAN_ID, &tx_data[..tx_len], T::PREFER_CLASSIC)
+}
+
+/// Encodes a package and sends it with its own ID
+pub async fn send_package<T: FDCANPack + Encode>(
+    can: &mut CanTx<'static>,
+    package: &T,
+) -> Result<(), TxError> {
+    let result = match package_frame(package) {
+        Ok(frame) => write_frame(can, &frame).await,
+        Err(err) => Err(err),
+    };
+    log_tx_result(T::FDCAN_ID, result)
+}
+
+/// Encodes and sends a dashboard-owned package
+async fn transmit_package(can: &mut CanTx<'static>, package: TxPackage) {
+    let mut tx_data = [0; 64];
+    let (id, tx_len) = match encode_tx_package(package, &mut tx_data).await {
+        Ok(Some(encoded)) => encoded,
+        Ok(None) => return,
+        Err(_) => {
+            CAN_TX_ERROR_COUNT.fetch_add(1, Relaxed);
+            error!("CAN Encode Error for {}", package);
+            return;
+        }
+    };
+    if send_frame(can, id, &tx_data[..tx_len], package.prefer_classic())
+        .await
+        .is_ok()
+    {
+        verbose!("Sent CAN package: {}", package);
+    }
+}
+
+/// Responsible for handling the transmission of CAN messages
+///
+/// Broadcasts the dashboard's own packages according to [`TX_SCHEDULE`]. A button 1 short
+/// press requests the next relay state, see [`next_relay_state`], and sends the command
+/// immediately. Its other gestures are used by the display, and never step the relay. Entering
+/// the safe state sends the standby command immediately too.
 #[embassy_executor::task]
 pub async fn can_transmit_task(mut can: CanTx<'static>) {
     // Use the FD API's even if we don't get FD packets.
-    let debug = true;
+    let debug = false;
     if debug {
         _debug_can_tx(&mut can).await;
     }
 
+    let mut last_sent: [Option<Instant>; TX_SCHEDULE.len()] = [None; TX_SCHEDULE.len()];
+    let mut ticker = Ticker::every(TX_TICK);
+    let mut buttons = BUTTON_EVENTS.subscriber().unwrap();
     loop {
-        let _ = BTN_SIGNAL.wait().await;
-
-        // Update the relay state
-        let mut relay_state = RELAY_STATE.lock().await;
-        *relay_state = if *relay_state == RelayState::RELAY_STBY {
-            RelayState::RELAY_STRTP
-        } else {
-            RelayState::RELAY_STBY
-        };
-
-        let frame =
-            Frame::new_extended(RelayState::FDCAN_ID, &[relay_state.clone() as u8]).unwrap();
-        drop(relay_state);
-        let _ = can.write(&frame).await;
+        let pressed = buttons.next_message_pure();
+        match select3(ticker.next(), pressed, SAFE_STATE_SIGNAL.wait()).await {
+            Either3::First(_) => {
+                let now = Instant::now();
+                for (&(package, period), last_sent) in TX_SCHEDULE.iter().zip(&mut last_sent) {
+                    if last_sent.is_none_or(|t| now.saturating_duration_since(t) >= period) {
+                        transmit_package(&mut can, package).await;
+                        *last_sent = Some(now);
+                    }
+                }
+            }
+            Either3::Second((ButtonId::Button1, ButtonEvent::ShortPress)) => {
+                let target = next_relay_state(&*RELAY_STATE.lock().await);
+                if request_relay_state(target).await.is_err() {
+                    continue;
+                }
+                send_relay_command_now(&mut can, &mut last_sent).await;
+            }
+            Either3::Second(_) => (),
+            Either3::Third(_) => send_relay_command_now(&mut can, &mut last_sent).await,
+        }
+    }
+}
 
-        trace!("Sent CAN frame");
-        Timer::after_millis(10).await;
+/// Sends the relay command straight away, rather than waiting for its next period
+async fn send_relay_command_now(
+    can: &mut CanTx<'static>,
+    last_sent: &mut [Option<Instant>; TX_SCHEDULE.len()],
+) {
+    transmit_package(can, TxPackage::RelayCommand).await;
+    if let Some(i) = TX_SCHEDULE
+        .iter()
+        .position(|&(package, _)| package == TxPackage::RelayCommand)
+    {
+        last_sent[i] = Some(Instant::now());
     }
 }
 
 async fn _debug_can_rx(can: &mut CanRx<'static>) {
-    let mut last_read_ts = embassy_time::Instant::now();
+    let mut frame_rate = FrameRateMeter::new();
     Timer::after_millis(10).await;
 
     loop {
@@ -172,19 +1118,19 @@ async fn _debug_can_rx(can: &mut CanRx<'static>) {
         match can.read_fd().await {
             Ok(envelope) => {
                 let (ts, rx_frame) = (envelope.ts, envelope.frame);
-                let delta = (ts - last_read_ts).as_millis();
-                last_read_ts = ts;
+                let delta = frame_rate.record(ts).map_or(0, |delta| delta.as_millis());
                 let id = match rx_frame.header().id() {
                     Id::Standard(id) => u32::from(id.as_raw()),
                     Id::Extended(id) => id.as_raw(),
                 };
                 // info!("Received: {}", rx_frame);
                 info!(
-                    "Received id: {:#08x} data len: {} data: {:#04x} --- {}ms",
+                    "Received id: {:#08x} data len: {} data: {:#04x} --- {}ms, {} Hz",
                     id,
                     rx_frame.header().len(),
                     rx_frame.data(),
                     delta,
+                    frame_rate.rate_hz(ts),
                 );
                 process_rx_can_frame(&rx_frame).await;
             }
@@ -195,7 +1141,6 @@ async fn _debug_can_rx(can: &mut CanRx<'static>) {
 }
 
 async fn _debug_can_tx(can: &mut CanTx<'static>) {
-    let mut tx_data = [0; 64];
     loop {
         let mut pack = RELAY_MOTOR_PACK.lock().await;
         pack.mtr_curr += 1;
@@ -204,94 +1149,431 @@ async fn _debug_can_tx(can: &mut CanTx<'static>) {
         if pack.mtr_curr > 100 {
             pack.mtr_curr = 0;
         }
+        let package = pack.value.clone();
         drop(pack);
 
-        let tx_len = match encode_can_package(&RELAY_MOTOR_PACK, &mut tx_data).await {
-            Ok(tx_len) => tx_len,
-            Err(_) => {
-                error!("CAN Encode Error");
-                continue;
-            }
-        };
-        let frame = Frame::new_extended(FDCAN_RelPackCap_t::FDCAN_ID, &tx_data[..tx_len]).unwrap();
         info!("Sending CAN frame...");
-        let _ = can.write(&frame).await;
-
-        info!("Sent CAN Frame");
+        if send_package(can, &package).await.is_ok() {
+            info!("Sent CAN Frame");
+        }
         Timer::after_millis(500).await;
     }
 }
 
 /// Decodes a CAN frame and handles decode errors
-async fn process_rx_can_frame(rx_frame: &FdFrame) {
-    if let Err(_) = decode_can_frame(&rx_frame).await {
-        error!("CAN Decode Error");
+///
+/// Returns true if the frame was decoded
+pub(crate) async fn process_rx_can_frame(rx_frame: &FdFrame) -> bool {
+    let decoded = match decode_can_frame(rx_frame).await {
+        Ok(()) => true,
+        Err(err) => {
+            error!(
+                "CAN decode error, {:#05x} with {} bytes: {}",
+                err.id, err.len, err.reason
+            );
+            verbose!(
+                "Undecoded frame data: {:02x}",
+                &rx_frame.data()[..rx_frame.header().len() as usize]
+            );
+            log_event("CAN decode error");
+            false
+        }
+    };
+    CAN_STATS
+        .lock()
+        .await
+        .record_frame(frame_id(rx_frame), decoded, Instant::now());
+    decoded
+}
+
+/// The ID of a CAN frame, standard or extended
+fn frame_id(frame: &FdFrame) -> u32 {
+    header_id(frame.header())
+}
+
+/// The ID in a CAN frame header, standard or extended
+fn header_id(header: &Header) -> u32 {
+    match header.id() {
+        Id::Standard(id) => u32::from(id.as_raw()),
+        Id::Extended(id) => id.as_raw(),
+    }
+}
+
+/// A received CAN frame that could not be decoded, with the ID and length it arrived with
+#[derive(Debug, Format)]
+pub struct FrameDecodeError {
+    pub id: u32,
+    /// The frame's length, including any checksum byte
+    pub len: usize,
+    pub reason: CanDecodeError,
+}
+
+/// Reasons a received CAN frame could not be decoded
+#[derive(Debug, Format)]
+pub enum CanDecodeError {
+    /// The frame's length doesn't match the package for its ID
+    UnexpectedLength { expected: usize, received: usize },
+    /// The data ended partway through the package, `missing` is roughly how many bytes short
+    TooShort { missing: usize },
+    /// A field held a value its type doesn't allow, such as an unknown state
+    InvalidValue(&'static str),
+    /// The frame's data is not a valid package for another reason
+    InvalidData,
+    /// The frame's checksum byte doesn't match, see [`checksum_mod`](crate::checksum_mod)
+    Checksum(ChecksumError),
+}
+
+impl From<ChecksumError> for CanDecodeError {
+    fn from(err: ChecksumError) -> Self {
+        CanDecodeError::Checksum(err)
+    }
+}
+
+impl From<DecodeError> for CanDecodeError {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::UnexpectedEnd { additional } => CanDecodeError::TooShort {
+                missing: additional,
+            },
+            DecodeError::UnexpectedVariant { type_name, .. } => {
+                CanDecodeError::InvalidValue(type_name)
+            }
+            DecodeError::InvalidBooleanValue(_) => CanDecodeError::InvalidValue("bool"),
+            DecodeError::Other(reason) => CanDecodeError::InvalidValue(reason),
+            _ => CanDecodeError::InvalidData,
+        }
+    }
+}
+
+/// What a received frame holds, decided from its ID and data alone
+#[derive(Debug, Format, PartialEq)]
+pub enum RxFrame {
+    /// The H2 alarm, `tripped` if the alarm is set
+    H2Alarm { tripped: bool },
+    /// The sync LED broadcast, `on` if the LEDs are commanded on
+    SyncLed { on: bool },
+    /// The relay board's state
+    Relay(RelayState),
+    /// Any other ID, possibly a package registered with `register_can_packages!`
+    Other,
+}
+
+/// Classifies a frame by its ID, decoding the packages that aren't stored as is
+///
+/// This has no side effects, the result is applied by [`decode_can_frame`].
+pub fn classify_frame(id: u32, rx_data: &[u8]) -> Result<RxFrame, CanDecodeError> {
+    const H2_ALARM_ID: u32 = FDCAN_H2ALARM_ID as u32;
+    const SYNC_LED_ID: u32 = FDCAN_SYNCLED_ID as u32;
+    match id {
+        // 1 indicates a tripped alarm
+        H2_ALARM_ID => Ok(RxFrame::H2Alarm {
+            tripped: rx_data.first() == Some(&1),
+        }),
+
+        // 1 indicates the LEDs are on
+        SYNC_LED_ID => Ok(RxFrame::SyncLed {
+            on: rx_data.first() == Some(&1),
+        }),
+
+        RelayState::FDCAN_ID => {
+            let [state] = rx_data else {
+                return Err(CanDecodeError::UnexpectedLength {
+                    expected: RelayState::FDCAN_BYTES as usize,
+                    received: rx_data.len(),
+                });
+            };
+            Ok(RxFrame::Relay(RelayState::try_from(*state)?))
+        }
+
+        _ => Ok(RxFrame::Other),
     }
 }
 
 /// Decodes a CAN frame into its corresponding CAN package
 ///
-/// Returns an error if the frame cannot be decoded.
-async fn decode_can_frame(frame: &FdFrame) -> Result<(), DecodeError> {
+/// Returns an error with the frame's ID and length if the frame cannot be decoded.
+async fn decode_can_frame(frame: &FdFrame) -> Result<(), FrameDecodeError> {
     // Get ID
-    let id = match frame.header().id() {
-        Id::Standard(id) => u32::from(id.as_raw()),
-        Id::Extended(id) => id.as_raw(),
-    };
+    let id = frame_id(frame);
     // Get data of CAN package (up to 64 bytes)
     let rx_data = &frame.data()[..frame.header().len() as usize];
+    let error = |reason| FrameDecodeError {
+        id,
+        len: rx_data.len(),
+        reason,
+    };
+    // Opted in packages are rejected before anything is decoded from them
+    let rx_data = if has_checksum(id) {
+        strip_checksum(id, rx_data).map_err(|err| error(err.into()))?
+    } else {
+        rx_data
+    };
 
-    // Match ID to CAN package, and decode
-    match id {
-        RelayState::FDCAN_ID => {
-            let mut relay_state = RELAY_STATE.lock().await;
-            *relay_state = RelayState::try_from(rx_data[0])?;
-            debug!("Updated Relay State: {:?}", *relay_state);
+    match classify_frame(id, rx_data).map_err(error)? {
+        RxFrame::H2Alarm { tripped: false } => Ok(()),
+        RxFrame::H2Alarm { tripped: true } => {
+            // The alarm stays latched until cleared
+            if trip_h2_alarm().await {
+                error!("H2 alarm tripped");
+                enter_safe_state(Fault::H2Alarm).await;
+            }
             Ok(())
         }
 
-        FDCAN_FccPack1_t::FDCAN_ID => decode_can_data(&FCC_PACK1_DATA, rx_data).await,
-        FDCAN_FccPack2_t::FDCAN_ID => decode_can_data(&FCC_PACK2_DATA, rx_data).await,
-        FDCAN_FccPack3_t::FDCAN_ID => decode_can_data(&FCC_PACK3_DATA, rx_data).await,
+        RxFrame::SyncLed { on } => {
+            let was_on = core::mem::replace(&mut *SYNC_LED.lock().await, on);
+            if was_on != on {
+                debug!("Sync LED {}", if on { "on" } else { "off" });
+            }
+            Ok(())
+        }
 
-        FDCAN_FetPack_t::FDCAN_ID => decode_can_data(&FET_DATA, rx_data).await,
+        RxFrame::Relay(state) => {
+            *RELAY_STATE.lock().await = state.clone();
+            verbose!("Updated Relay State: {:?}", state);
+            Ok(())
+        }
 
-        FDCAN_RelPackMtr_t::FDCAN_ID => decode_can_data(&RELAY_MOTOR_PACK, rx_data).await,
-        FDCAN_RelPackCap_t::FDCAN_ID => decode_can_data(&REL_CAP_PACK, rx_data).await,
-        FDCAN_RelPackFc_t::FDCAN_ID => decode_can_data(&REL_FC_PACK, rx_data).await,
+        RxFrame::Other => match decode_registered_package(id, rx_data).await {
+            Some(result) => result.map_err(error),
+            None => {
+                // Rate limited, a busy bus can carry many IDs we don't decode
+                if let Some(suppressed) = UNKNOWN_ID_LOG.lock().await.check(id, Instant::now()) {
+                    verbose!(
+                        "Non-Relevant ID: {:016b} ({} more since last logged)",
+                        id,
+                        suppressed
+                    );
+                }
+                Ok(())
+            }
+        },
+    }
+}
 
-        ECOCAN_H2Pack1_t::FDCAN_ID => decode_can_data(&H2_PACK1_DATA, rx_data).await,
-        ECOCAN_H2Pack2_t::FDCAN_ID => decode_can_data(&H2_PACK2_DATA, rx_data).await,
+/// Registers packages that are decoded straight into a static with [`decode_can_data`]
+///
+/// Generates [`PACKAGE_IDS`], `decode_registered_package`, which dispatches on the package's ID,
+/// and [`write_package`].
+/// A package listed twice, or with an ID already used, is an unreachable pattern warning.
+///
+/// An optional `=> hook` async function is called with a copy of the package each time it is
+/// decoded.
+macro_rules! register_can_packages {
+    ($($package:ident => $data:ident $(=> $hook:path)?),* $(,)?) => {
+        /// The IDs of the packages registered with `register_can_packages!`
+        pub const PACKAGE_IDS: [u32; [$($package::FDCAN_ID),*].len()] = [$($package::FDCAN_ID),*];
 
-        FDCAN_BOOSTPack1_t::FDCAN_ID => decode_can_data(&BOOST_PACK1_DATA, rx_data).await,
-        FDCAN_BOOSTPack2_t::FDCAN_ID => decode_can_data(&BOOST_PACK2_DATA, rx_data).await,
-        FDCAN_BOOSTPack3_t::FDCAN_ID => decode_can_data(&BOOST_PACK3_DATA, rx_data).await,
+        /// Decodes a registered package, returns `None` if no package is registered for `id`
+        async fn decode_registered_package(
+            id: u32,
+            rx_data: &[u8],
+        ) -> Option<Result<(), CanDecodeError>> {
+            match id {
+                $($package::FDCAN_ID => {
+                    let result = decode_can_data(&$data, rx_data).await;
+                    // Hooks are given the decoded copy, so the package isn't locked again
+                    $(if let Ok(package) = &result {
+                        $hook(package).await;
+                    })?
+                    Some(result.map(|_| ()))
+                })*
+                _ => None,
+            }
+        }
 
-        _ => {
-            trace!("Non-Relevant ID: {:016b}", id);
-            Ok(())
+        /// Writes a registered package's ID and each of its fields, returns true if it is stale
+        ///
+        /// The fields are laid out from the package's [`PackageFields`] table.
+        ///
+        /// Returns `None` if no package is registered for `id`.
+        pub async fn write_package(
+            id: u32,
+            now: Instant,
+            out: &mut impl core::fmt::Write,
+        ) -> Option<bool> {
+            match id {
+                $($package::FDCAN_ID => {
+                    // Copied out, so the lock isn't held while formatting
+                    let (value, stale) = {
+                        let package = $data.lock().await;
+                        (package.value.clone(), package.is_stale(now))
+                    };
+                    let _ = core::write!(out, "{:#05x}", id);
+                    for field in <$package as PackageFields>::FIELDS {
+                        let _ = out.write_str("  ");
+                        let _ = field.write(&value, out);
+                    }
+                    Some(stale)
+                })*
+                _ => None,
+            }
         }
+
+        /// Time a registered package was last received
+        ///
+        /// Returns `None` if no package is registered for `id`, and `Some(None)` if it has never
+        /// been received.
+        pub async fn package_last_seen(id: u32) -> Option<Option<Instant>> {
+            match id {
+                $($package::FDCAN_ID => Some($data.lock().await.last_seen),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+register_can_packages! {
+    FDCAN_FccPack1_t => FCC_PACK1_DATA => on_fcc_pack1,
+    FDCAN_FccPack2_t => FCC_PACK2_DATA,
+    FDCAN_FccPack3_t => FCC_PACK3_DATA => update_fcc_bme_temp,
+
+    FDCAN_FetPack_t => FET_DATA,
+
+    FDCAN_RelPackMtr_t => RELAY_MOTOR_PACK => record_mtr_peaks,
+    FDCAN_RelPackCap_t => REL_CAP_PACK => record_cap_peaks,
+    FDCAN_RelPackFc_t => REL_FC_PACK => on_fc_pack,
+    FDCAN_RelPackNrg_t => REL_NRG_PACK => record_trip_energy,
+    ECOCAN_RelPackChrg_t => REL_CHRG_PACK => record_trip_charge,
+
+    ECOCAN_H2Pack1_t => H2_PACK1_DATA,
+    ECOCAN_H2Pack2_t => H2_PACK2_DATA => update_h2_bme_temp,
+
+    FDCAN_BOOSTPack1_t => BOOST_PACK1_DATA,
+    FDCAN_BOOSTPack2_t => BOOST_PACK2_DATA,
+    FDCAN_BOOSTPack3_t => BOOST_PACK3_DATA,
+
+    FDCAN_BATTPack2_t => BATT_PACK2_DATA,
+
+    DASH_IndicatorCmd_t => INDICATOR_CMD => on_indicator_cmd,
+}
+
+/// Switches the turn signals to a received command
+async fn on_indicator_cmd(cmd: &DASH_IndicatorCmd_t) {
+    match cmd.indicator() {
+        Ok(state) => set_indicator(state).await,
+        Err(_) => warn!("Invalid indicator state {}", cmd.state),
     }
 }
 
-/// Decodes a byte array into a CAN package
-async fn decode_can_data<T: Decode<()> + Format>(
-    package: &Mutex<ThreadModeRawMutex, T>,
+/// Checks the fuel cell temperature and records its peak from a received FCC package
+async fn on_fcc_pack1(pack: &FDCAN_FccPack1_t) {
+    update_fc_temp(pack).await;
+    record_fcc_peaks(pack).await;
+}
+
+/// Raises the low voltage warning and records the peaks from a received fuel cell package
+async fn on_fc_pack(pack: &FDCAN_RelPackFc_t) {
+    update_fc_voltage(pack.fc_volt);
+    record_fc_peaks(pack).await;
+}
+
+/// Decodes a byte array into a CAN package, and records when it was received
+///
+/// The frame must be between [`FDCANPack::MIN_BYTES`] and [`FDCANPack::FDCAN_BYTES`] long.
+/// A shorter frame, such as a classic CAN frame, only updates the package's leading fields.
+///
+/// The package is decoded into a local copy, and only locked to read the previous values and to
+/// store the result. Only the receive task writes packages, so nothing changes in between.
+/// Returns the decoded package.
+///
+/// In demo mode the package isn't stored, so it doesn't overwrite the synthetic data, but it is
+/// still returned for the warnings and safe state to act on, see [`demo_mod`](crate::demo_mod).
+async fn decode_can_data<T: FDCANPack + Encode + Decode<()> + Format + Clone>(
+    package: &Mutex<ThreadModeRawMutex, Timestamped<T>>,
     rx_data: &[u8],
-) -> Result<(), DecodeError> {
-    // Decode received package bytes into the desired package struct and update can package
-    let mut p = package.lock().await;
-    *p = bincode::decode_from_slice(&rx_data, BINCODE_CONFIG)?.0;
-    trace!("Received CAN Package: {:?}", *p);
+) -> Result<T, CanDecodeError> {
+    let expected = T::FDCAN_BYTES as usize;
+    let length_error = CanDecodeError::UnexpectedLength {
+        expected,
+        received: rx_data.len(),
+    };
+    if rx_data.len() < T::MIN_BYTES || rx_data.len() > expected {
+        return Err(length_error);
+    }
 
-    Ok(())
+    // Decode received package bytes into the desired package struct
+    let mut package_data = [0; 64];
+    if rx_data.len() < expected {
+        // Keep the previous values of the fields that weren't received
+        let previous = package.lock().await.value.clone();
+        encode_package(&previous, &mut package_data).map_err(|_| length_error)?;
+    }
+    package_data[..rx_data.len()].copy_from_slice(rx_data);
+    let value: T = decode_package(&package_data[..expected])?;
+    verbose!("Received CAN Package: {:?}", value);
+
+    // Then update the CAN package, unless synthetic data stands in for it
+    if !demo_mode() {
+        let mut p = package.lock().await;
+        p.value = value.clone();
+        p.last_seen = Some(Instant::now());
+    }
+
+    Ok(value)
 }
 
-/// Encodes a CAN package into a byte array, stored in tx_data
-async fn encode_can_package<T: Encode + Clone>(
-    package: &Mutex<ThreadModeRawMutex, T>,
-    mut tx_data: &mut [u8],
-) -> Result<usize, EncodeError> {
-    let p = package.lock().await;
-    bincode::encode_into_slice(p.clone(), &mut tx_data, BINCODE_CONFIG)
+/// Encodes a package, checks it fills exactly `FDCAN_BYTES`, and decodes it back
+#[cfg(debug_assertions)]
+fn check_round_trip<T: FDCANPack + Encode + Decode<()> + PartialEq + Format>(package: T) {
+    let mut tx_data = [0; 64];
+    let tx_len = encode_package(&package, &mut tx_data).unwrap();
+    defmt::assert_eq!(tx_len, T::FDCAN_BYTES as usize, "{}", package);
+    let decoded: T = decode_package(&tx_data[..tx_len]).unwrap();
+    defmt::assert_eq!(decoded, package);
+    // Truncated data is an error, not a partially decoded package
+    defmt::assert!(decode_package::<T>(&tx_data[..tx_len - 1]).is_err());
+    // Packages that fit in a classic frame are sent under their own ID
+    if tx_len <= crate::eco_can::CLASSIC_MAX_BYTES {
+        let frame = package_frame(&package).unwrap();
+        defmt::assert_eq!(header_id(frame.header()), T::FDCAN_ID);
+        defmt::assert_eq!(frame.data(), &tx_data[..tx_len]);
+    }
+}
+
+/// Checks that every package encodes to its declared length and decodes back unchanged.
+///
+/// Catches field reorders or type changes that would silently break wire compatibility with
+/// the other boards. Only run in debug builds, since it panics on a mismatch.
+#[cfg(debug_assertions)]
+pub fn check_package_encoding() {
+    macro_rules! round_trip_all {
+        ($($package:expr $(=> [$($byte:expr),* $(,)?])?),* $(,)?) => {
+            $(check_round_trip($package);)*
+        };
+    }
+    package_fixtures!(round_trip_all);
+
+    check_wire_layout();
+    debug!("CAN package encoding checked");
+}
+
+/// Encodes a package, and checks the bytes match `expected` exactly
+#[cfg(debug_assertions)]
+fn check_wire_bytes<T: Encode + Decode<()> + PartialEq + Format>(package: T, expected: &[u8]) {
+    let mut tx_data = [0; 64];
+    let tx_len = encode_package(&package, &mut tx_data).unwrap();
+    defmt::assert_eq!(&tx_data[..tx_len], expected, "{}", package);
+    defmt::assert_eq!(decode_package::<T>(expected).unwrap(), package);
+}
+
+/// Checks packages against the byte layout the other boards send
+///
+/// This is the interop contract: fields in declaration order, each big-endian, with no padding
+/// or length prefixes. `#[repr(C)]` only fixes the in-memory layout, which bincode ignores, so
+/// this is what actually has to match the C firmware.
+///
+/// The expected bytes are written out by hand from that contract, with realistic values, in
+/// the shared [`package_fixtures!`] table. No frames have been captured from the C senders yet.
+/// When they are, replace these with the captured bytes and note the sender's firmware version
+/// there. A little-endian sender, such as one that `memcpy`s its struct, would show up as every
+/// field's bytes reversed.
+#[cfg(debug_assertions)]
+fn check_wire_layout() {
+    macro_rules! check_wire_bytes_all {
+        ($($package:expr $(=> [$($byte:expr),* $(,)?])?),* $(,)?) => {
+            $($(check_wire_bytes($package, &[$($byte),*]);)?)*
+        };
+    }
+    package_fixtures!(check_wire_bytes_all);
 }
@@ -0,0 +1,116 @@
+//! Module for the CAN loopback self-test
+//!
+//! Enabled with the `can-self-test` feature, so a normal boot isn't delayed by it. Before CAN
+//! joins the bus, the peripheral is started in internal loopback mode, where every frame it
+//! sends is received straight back without reaching the transceiver. One of each package is
+//! sent, read back and decoded, and has to equal the package that was sent.
+//!
+//! This checks the peripheral, its message RAM and the package encoding together, without
+//! another board on the bus. The result is shown on the boot splash and logged. A failure
+//! doesn't stop the boot, since the dashboard is still useful without CAN.
+//!
+//! The loopback peripheral is dropped at the end, and [`start_can`](crate::can_mod::start_can)
+//! configures it again from reset for the bus.
+
+use bincode::{Decode, Encode};
+use defmt::Format;
+use embassy_stm32::can::{
+    Can, CanConfigurator, OperatingMode, config::FrameTransmissionConfig, enums::BusError,
+    frame::FdFrame,
+};
+use embassy_time::{Duration, with_timeout};
+use embedded_can::Id;
+
+use crate::{
+    can_mod::CAN_TIMINGS,
+    eco_can::{FDCANPack, decode_package, encode_package},
+    event_log_mod::log_event,
+    fixtures_mod::package_fixtures,
+    log_mod::{debug, error, info},
+};
+
+/// Longest to wait for a frame to come back
+const LOOPBACK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Reasons a package failed the self-test, each with the package's ID
+#[derive(Debug, Format)]
+pub enum SelfTestError {
+    /// The package could not be encoded into a frame
+    Encode(u32),
+    /// The frame wasn't received back within [`LOOPBACK_TIMEOUT`]
+    Timeout(u32),
+    /// The peripheral reported an error reading the frame back
+    Bus(u32, BusError),
+    /// A frame with another ID was received back
+    WrongId { sent: u32, received: u32 },
+    /// The received frame could not be decoded
+    Decode(u32),
+    /// The received frame decoded to a different package
+    Mismatch(u32),
+}
+
+/// Runs the loopback self-test, stopping at the first package that fails
+///
+/// Every frame is accepted, and FD frames are allowed, so each package is sent whole under its
+/// own ID whatever the bus filters and bitrates are.
+pub async fn can_self_test(mut can: CanConfigurator<'_>) -> Result<(), SelfTestError> {
+    can.set_config(
+        CAN_TIMINGS
+            .apply(can.config())
+            .set_frame_transmit(FrameTransmissionConfig::AllowFdCan),
+    );
+    let mut can = can.start(OperatingMode::InternalLoopbackMode);
+
+    let result = loopback_all(&mut can).await;
+    match &result {
+        Ok(()) => info!("CAN self-test passed"),
+        Err(err) => {
+            error!("CAN self-test failed: {}", err);
+            log_event("CAN self-test failed");
+        }
+    }
+    result
+}
+
+/// Loops back one of each package in the shared [`package_fixtures!`] table
+async fn loopback_all(can: &mut Can<'_>) -> Result<(), SelfTestError> {
+    macro_rules! loopback_each {
+        ($($package:expr $(=> [$($byte:expr),* $(,)?])?),* $(,)?) => {
+            $(loopback(can, $package).await?;)*
+        };
+    }
+    package_fixtures!(loopback_each);
+    Ok(())
+}
+
+/// Sends a package, and checks it is received back unchanged under its own ID
+async fn loopback<T: FDCANPack + Encode + Decode<()> + PartialEq>(
+    can: &mut Can<'_>,
+    package: T,
+) -> Result<(), SelfTestError> {
+    let id = T::FDCAN_ID;
+    let mut tx_data = [0; 64];
+    let tx_len = encode_package(&package, &mut tx_data).map_err(|_| SelfTestError::Encode(id))?;
+    let frame =
+        FdFrame::new_extended(id, &tx_data[..tx_len]).map_err(|_| SelfTestError::Encode(id))?;
+    can.write_fd(&frame).await;
+
+    let envelope = with_timeout(LOOPBACK_TIMEOUT, can.read_fd())
+        .await
+        .map_err(|_| SelfTestError::Timeout(id))?
+        .map_err(|err| SelfTestError::Bus(id, err))?;
+    let received = match envelope.frame.header().id() {
+        Id::Standard(received) => u32::from(received.as_raw()),
+        Id::Extended(received) => received.as_raw(),
+    };
+    if received != id {
+        return Err(SelfTestError::WrongId { sent: id, received });
+    }
+    let decoded: T =
+        decode_package(envelope.frame.data()).map_err(|_| SelfTestError::Decode(id))?;
+    if decoded != package {
+        return Err(SelfTestError::Mismatch(id));
+    }
+    debug!("CAN self-test: {:#05x} looped back", id);
+    Ok(())
+}
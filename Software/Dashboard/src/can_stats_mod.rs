@@ -0,0 +1,389 @@
+//! Module for CAN bus statistics
+//!
+//! Counts received frames and decode errors, in total and over the last second, and how many
+//! frames were received for each known ID. This shows whether the dashboard is keeping up
+//! with the bus, and which boards are reporting.
+//!
+//! [`FrameRateMeter`] also keeps a rolling average of the time between frames, which reacts
+//! faster than the per second counts.
+//!
+//! Frames in the safety block, such as the H2 alarm, are also timed, to show they are handled
+//! within their deadline:
+//! - Latency, from the peripheral's receive timestamp to the frame having been handled
+//! - Jitter, how much the time between frames of one ID changes from one frame to the next
+//!
+//! Both are kept in a [`DurationHistogram`], so their maximum and percentiles are available
+//! without storing every sample.
+
+use defmt::Format;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
+
+use crate::can_mod::RX_IDS;
+use crate::eco_can::ID_BLOCK_MASK;
+
+/// The IDs counted individually, all other IDs are counted together
+pub const KNOWN_IDS: [u32; RX_IDS.len()] = RX_IDS;
+
+/// The period the per second rates are measured over
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Weight of a new interval in the rolling average, as a power of two (1/8)
+const INTERVAL_FILTER_SHIFT: u32 = 3;
+
+/// Tracks the time between frames, and a rolling average of the frame rate
+#[derive(Clone, Copy, Debug, Format)]
+pub struct FrameRateMeter {
+    /// When the last frame was recorded, `None` until the first frame
+    last: Option<Instant>,
+    /// Smoothed time between frames (in ticks), `None` until the second frame
+    avg_interval_ticks: Option<u64>,
+}
+
+impl FrameRateMeter {
+    pub const fn new() -> Self {
+        Self {
+            last: None,
+            avg_interval_ticks: None,
+        }
+    }
+
+    /// Records a frame, returns the time since the previous frame
+    ///
+    /// Returns `None` for the first frame, since there is nothing to measure from. A timestamp
+    /// earlier than the last one counts as no time passing, rather than underflowing.
+    pub fn record(&mut self, now: Instant) -> Option<Duration> {
+        let interval = self.last.map(|last| now.saturating_duration_since(last));
+        self.last = Some(now.max(self.last.unwrap_or(now)));
+        if let Some(interval) = interval {
+            self.avg_interval_ticks = Some(smooth_interval(
+                self.avg_interval_ticks,
+                interval.as_ticks(),
+            ));
+        }
+        interval
+    }
+
+    /// The average frame rate, 0 until two frames have been recorded
+    ///
+    /// If the bus goes quiet, the time since the last frame is used once it is longer than the
+    /// average, so the rate falls instead of holding its last value.
+    pub fn rate_hz(&self, now: Instant) -> u32 {
+        let (Some(last), Some(avg)) = (self.last, self.avg_interval_ticks) else {
+            return 0;
+        };
+        let idle = now.saturating_duration_since(last).as_ticks();
+        interval_to_hz(avg.max(idle))
+    }
+}
+
+impl Default for FrameRateMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adds an interval to the rolling average
+const fn smooth_interval(avg: Option<u64>, sample: u64) -> u64 {
+    match avg {
+        None => sample,
+        Some(avg) => avg - (avg >> INTERVAL_FILTER_SHIFT) + (sample >> INTERVAL_FILTER_SHIFT),
+    }
+}
+
+/// The rate of frames arriving `interval_ticks` apart, rounded
+const fn interval_to_hz(interval_ticks: u64) -> u32 {
+    if interval_ticks == 0 {
+        return u32::MAX;
+    }
+    let hz = (embassy_time::TICK_HZ + interval_ticks / 2) / interval_ticks;
+    if hz > u32::MAX as u64 {
+        u32::MAX
+    } else {
+        hz as u32
+    }
+}
+
+// The average settles on a steady interval, and converts to the matching rate
+const _: () = {
+    let tick_hz = embassy_time::TICK_HZ;
+    assert!(interval_to_hz(tick_hz / 50) == 50);
+    assert!(interval_to_hz(tick_hz * 3) == 0);
+    assert!(interval_to_hz(0) == u32::MAX);
+    assert!(smooth_interval(None, 800) == 800);
+    assert!(smooth_interval(Some(800), 800) == 800);
+    assert!(smooth_interval(Some(800), 1600) == 900);
+};
+
+/// Number of buckets in a [`DurationHistogram`], the last one starts at 16.4 ms
+const HISTOGRAM_BUCKETS: usize = 16;
+
+/// Counts durations in power of two buckets of µs, to estimate percentiles in constant memory
+///
+/// Bucket `i` counts durations under `2^i` µs and at least `2^(i-1)` µs, the last bucket also
+/// counts anything longer. A percentile is reported as the top of its bucket, capped at the
+/// maximum, so it can be up to twice the true value but never under it. The last bucket has no
+/// top, so a percentile in it is reported as the maximum.
+#[derive(Clone, Copy, Debug, Format)]
+pub struct DurationHistogram {
+    counts: [u32; HISTOGRAM_BUCKETS],
+    max_us: u32,
+}
+
+impl DurationHistogram {
+    pub const fn new() -> Self {
+        Self {
+            counts: [0; HISTOGRAM_BUCKETS],
+            max_us: 0,
+        }
+    }
+
+    /// Records a duration
+    pub fn record(&mut self, duration: Duration) {
+        self.record_us(duration.as_micros().min(u32::MAX as u64) as u32);
+    }
+
+    /// Records a duration in µs
+    pub const fn record_us(&mut self, us: u32) {
+        let bucket = bucket(us);
+        self.counts[bucket] = self.counts[bucket].saturating_add(1);
+        if us > self.max_us {
+            self.max_us = us;
+        }
+    }
+
+    /// Number of durations recorded
+    pub const fn count(&self) -> u32 {
+        let mut total: u32 = 0;
+        let mut i = 0;
+        while i < HISTOGRAM_BUCKETS {
+            total = total.saturating_add(self.counts[i]);
+            i += 1;
+        }
+        total
+    }
+
+    /// The longest duration recorded, in µs
+    pub const fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    /// The duration `permille` thousandths of the recordings are at or under, in µs, estimated
+    /// from the buckets as described for [`DurationHistogram`]
+    ///
+    /// e.g. 990 for the 99th percentile. 0 if nothing has been recorded.
+    pub const fn percentile_us(&self, permille: u32) -> u32 {
+        let total = self.count() as u64;
+        // The rank of the percentile, rounded up so it is never under-reported
+        let rank = (total * permille as u64).div_ceil(1_000);
+        let mut seen: u64 = 0;
+        let mut i = 0;
+        while i < HISTOGRAM_BUCKETS {
+            seen += self.counts[i] as u64;
+            if seen >= rank && seen > 0 {
+                if i == HISTOGRAM_BUCKETS - 1 {
+                    return self.max_us;
+                }
+                let top = (1u32 << i) - 1;
+                return if top < self.max_us { top } else { self.max_us };
+            }
+            i += 1;
+        }
+        self.max_us
+    }
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The bucket of a duration in µs, see [`DurationHistogram`]
+const fn bucket(us: u32) -> usize {
+    let bucket = (u32::BITS - us.leading_zeros()) as usize;
+    if bucket < HISTOGRAM_BUCKETS {
+        bucket
+    } else {
+        HISTOGRAM_BUCKETS - 1
+    }
+}
+
+// Percentiles land on the top of their bucket, never above the maximum, and never under the
+// true value
+const _: () = {
+    assert!(bucket(0) == 0);
+    assert!(bucket(1) == 1);
+    assert!(bucket(3) == 2 && bucket(4) == 3);
+    assert!(bucket(u32::MAX) == HISTOGRAM_BUCKETS - 1);
+    let mut histogram = DurationHistogram::new();
+    assert!(histogram.percentile_us(990) == 0);
+    let mut i = 0;
+    while i < 99 {
+        histogram.record_us(100);
+        i += 1;
+    }
+    histogram.record_us(5_000);
+    assert!(histogram.count() == 100);
+    assert!(histogram.percentile_us(500) == 127);
+    assert!(histogram.percentile_us(990) == 127);
+    assert!(histogram.percentile_us(1_000) == 5_000);
+    assert!(histogram.max_us() == 5_000);
+
+    // Past the top of the last regular bucket, the maximum is reported rather than the top
+    let mut histogram = DurationHistogram::new();
+    histogram.record_us(20_000);
+    histogram.record_us(50_000);
+    assert!(histogram.percentile_us(500) == 50_000);
+    assert!(histogram.percentile_us(990) == 50_000);
+};
+
+/// Number of IDs in a reserved block, and so in the safety block
+const SAFETY_IDS: usize = (!ID_BLOCK_MASK & 0x7FF) as usize + 1;
+
+/// A copy of the statistics at one point in time
+#[derive(Clone, Copy, Debug, Format)]
+pub struct CanStatsSnapshot {
+    pub total_frames: u32,
+    pub decode_errors: u32,
+    /// Frames received in the last full second
+    pub frames_per_second: u32,
+    /// Decode errors in the last full second
+    pub errors_per_second: u32,
+    /// Rolling average frame rate, see [`FrameRateMeter`]
+    pub frame_rate_hz: u32,
+    /// Frames received for each of [`KNOWN_IDS`]
+    pub id_counts: [u32; KNOWN_IDS.len()],
+    /// Frames received with an ID not in [`KNOWN_IDS`]
+    pub other_ids: u32,
+    /// Time from a safety frame being received to it having been handled
+    pub safety_latency: DurationHistogram,
+    /// Change in the time between safety frames of the same ID
+    pub safety_jitter: DurationHistogram,
+}
+
+pub struct CanStats {
+    total_frames: u32,
+    decode_errors: u32,
+    id_counts: [u32; KNOWN_IDS.len()],
+    other_ids: u32,
+    /// Start of the current rate window, `None` until the first frame
+    window_start: Option<Instant>,
+    window_frames: u32,
+    window_errors: u32,
+    frames_per_second: u32,
+    errors_per_second: u32,
+    frame_rate: FrameRateMeter,
+    safety_latency: DurationHistogram,
+    safety_jitter: DurationHistogram,
+    /// When each safety ID last arrived, and the interval before that
+    ///
+    /// Kept as two options rather than a nested one, so [`CAN_STATS`] starts zeroed and takes
+    /// no flash.
+    safety_arrivals: [(Option<Instant>, Option<Duration>); SAFETY_IDS],
+}
+
+impl CanStats {
+    pub const fn new() -> Self {
+        Self {
+            total_frames: 0,
+            decode_errors: 0,
+            id_counts: [0; KNOWN_IDS.len()],
+            other_ids: 0,
+            window_start: None,
+            window_frames: 0,
+            window_errors: 0,
+            frames_per_second: 0,
+            errors_per_second: 0,
+            frame_rate: FrameRateMeter::new(),
+            safety_latency: DurationHistogram::new(),
+            safety_jitter: DurationHistogram::new(),
+            safety_arrivals: [(None, None); SAFETY_IDS],
+        }
+    }
+
+    /// Starts a new rate window if the current one has ended
+    fn roll_window(&mut self, now: Instant) {
+        let Some(window_start) = self.window_start else {
+            self.window_start = Some(now);
+            return;
+        };
+        let elapsed = now.saturating_duration_since(window_start);
+        if elapsed < RATE_WINDOW {
+            return;
+        }
+        // If a whole window passed without frames, the rate is 0
+        let (frames, errors) = if elapsed < RATE_WINDOW * 2 {
+            (self.window_frames, self.window_errors)
+        } else {
+            (0, 0)
+        };
+        self.frames_per_second = frames;
+        self.errors_per_second = errors;
+        self.window_frames = 0;
+        self.window_errors = 0;
+        self.window_start = Some(now);
+    }
+
+    /// Records a received frame, and whether it was decoded
+    pub fn record_frame(&mut self, id: u32, decoded: bool, now: Instant) {
+        self.roll_window(now);
+        self.frame_rate.record(now);
+
+        self.total_frames = self.total_frames.wrapping_add(1);
+        self.window_frames += 1;
+        if !decoded {
+            self.decode_errors = self.decode_errors.wrapping_add(1);
+            self.window_errors += 1;
+        }
+        match KNOWN_IDS.iter().position(|&known| known == id) {
+            Some(i) => self.id_counts[i] = self.id_counts[i].wrapping_add(1),
+            None => self.other_ids = self.other_ids.wrapping_add(1),
+        }
+    }
+
+    /// Records the timing of a frame in the safety block
+    ///
+    /// `received` is the frame's receive timestamp, and `handled` the time it had been handled.
+    pub fn record_safety_frame(&mut self, id: u32, received: Instant, handled: Instant) {
+        self.safety_latency
+            .record(handled.saturating_duration_since(received));
+
+        let arrival = &mut self.safety_arrivals[(id & !ID_BLOCK_MASK) as usize % SAFETY_IDS];
+        let (last, previous) = *arrival;
+        let interval = last.map(|last| received.saturating_duration_since(last));
+        if let (Some(interval), Some(previous)) = (interval, previous) {
+            let jitter = if interval > previous {
+                interval - previous
+            } else {
+                previous - interval
+            };
+            self.safety_jitter.record(jitter);
+        }
+        *arrival = (Some(received), interval);
+    }
+
+    /// The current statistics
+    pub fn snapshot(&mut self, now: Instant) -> CanStatsSnapshot {
+        self.roll_window(now);
+        CanStatsSnapshot {
+            total_frames: self.total_frames,
+            decode_errors: self.decode_errors,
+            frames_per_second: self.frames_per_second,
+            errors_per_second: self.errors_per_second,
+            frame_rate_hz: self.frame_rate.rate_hz(now),
+            id_counts: self.id_counts,
+            other_ids: self.other_ids,
+            safety_latency: self.safety_latency,
+            safety_jitter: self.safety_jitter,
+        }
+    }
+}
+
+impl Default for CanStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub static CAN_STATS: Mutex<ThreadModeRawMutex, CanStats> = Mutex::new(CanStats::new());
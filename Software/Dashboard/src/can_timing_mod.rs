@@ -0,0 +1,279 @@
+//! Module for calculating CAN bit timings
+//!
+//! A CAN bit is divided into time quanta (tq), each lasting `prescaler` cycles of the FDCAN
+//! kernel clock. A bit is made up of three segments:
+//!
+//! ```text
+//! | sync (1 tq) |      seg1      |  seg2  |
+//!                                ^ sample point
+//! ```
+//!
+//! So the bitrate is `kernel_clock / (prescaler * (1 + seg1 + seg2))`, and the sample point is
+//! `(1 + seg1) / (1 + seg1 + seg2)`. The sample point should be around 87.5% for the nominal
+//! bitrate, and around 75% for the FD data bitrate.
+//!
+//! [`CanBitTiming::calculate`] is a `const fn`, so the timings used in `main.rs` are derived
+//! and checked at compile time rather than being magic numbers.
+//!
+//! The bitrates themselves are set in [`CanSettings`]. To run on a slower bench bus, change
+//! `CAN_SETTINGS` in `can_mod`, a bitrate the kernel clock can't reach fails the build.
+
+use core::num::{NonZeroU8, NonZeroU16};
+
+use embassy_stm32::can::config::{
+    DataBitTiming, FdCanConfig, FrameTransmissionConfig, NominalBitTiming,
+};
+
+/// Frequency of the FDCAN kernel clock, the FDCAN peripheral is clocked by the 8 MHz HSE
+///
+/// Not the 80 MHz often used for FDCAN: the PLL runs the core at 170 MHz, and none of its
+/// outputs divide down to 80 MHz. The HSE reaches every bitrate the bus uses exactly, see the
+/// checks at the end of this module, which also cover 80 MHz in case the clock tree changes.
+/// Keep this in step with `config.rcc.mux.fdcansel` in `main.rs`.
+pub const FDCAN_KERNEL_CLOCK: u32 = 8_000_000;
+
+/// Maximum allowed difference between the requested and achieved bitrate, in parts per million
+pub const MAX_BITRATE_ERROR_PPM: u32 = 5_000;
+
+/// The range of values the FDCAN peripheral accepts for a bit timing register
+pub struct TimingLimits {
+    pub max_prescaler: u32,
+    pub max_seg1: u32,
+    pub max_seg2: u32,
+    pub max_sync_jump_width: u32,
+}
+
+/// Limits of the nominal bit timing register (NBTP)
+pub const NOMINAL_LIMITS: TimingLimits = TimingLimits {
+    max_prescaler: 512,
+    max_seg1: 255,
+    max_seg2: 127,
+    max_sync_jump_width: 127,
+};
+
+/// Limits of the data bit timing register (DBTP)
+pub const DATA_LIMITS: TimingLimits = TimingLimits {
+    max_prescaler: 31,
+    max_seg1: 31,
+    max_seg2: 15,
+    max_sync_jump_width: 15,
+};
+
+/// Segment and prescaler values for a CAN bitrate
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct CanBitTiming {
+    pub prescaler: u16,
+    pub seg1: u8,
+    pub seg2: u8,
+    pub sync_jump_width: u8,
+    /// The bitrate these values actually achieve
+    pub bitrate: u32,
+}
+
+impl CanBitTiming {
+    /// Calculates the timing for `bitrate`, with the sample point as close as possible
+    /// to `sample_point_permille`.
+    ///
+    /// The most time quanta per bit are preferred, since this gives the finest control over the
+    /// sample point. Returns `None` if the bitrate cannot be reached within `limits`.
+    pub const fn calculate(
+        kernel_clock: u32,
+        bitrate: u32,
+        sample_point_permille: u32,
+        limits: &TimingLimits,
+    ) -> Option<Self> {
+        if bitrate == 0 {
+            return None;
+        }
+        let max_tq = 1 + limits.max_seg1 + limits.max_seg2;
+        let mut best: Option<Self> = None;
+        let mut best_error = u32::MAX;
+
+        let mut tq = max_tq;
+        while tq >= 4 {
+            // In u64, a fast bitrate times the most quanta doesn't fit in a u32
+            let quanta_rate = bitrate as u64 * tq as u64;
+            let prescaler = (kernel_clock as u64 + quanta_rate / 2) / quanta_rate;
+            if prescaler >= 1 && prescaler <= limits.max_prescaler as u64 {
+                let prescaler = prescaler as u32;
+                // Position of the sample point in time quanta, rounded
+                let sample_tq = (tq * sample_point_permille + 500) / 1000;
+                let mut seg1 = if sample_tq > 1 { sample_tq - 1 } else { 1 };
+                if seg1 > limits.max_seg1 {
+                    seg1 = limits.max_seg1;
+                }
+                if seg1 > tq - 2 {
+                    seg1 = tq - 2;
+                }
+                let seg2 = tq - 1 - seg1;
+                if seg2 <= limits.max_seg2 {
+                    let actual = kernel_clock / (prescaler * tq);
+                    let error = actual.abs_diff(bitrate);
+                    if error < best_error {
+                        let sync_jump_width = if seg2 < limits.max_sync_jump_width {
+                            seg2
+                        } else {
+                            limits.max_sync_jump_width
+                        };
+                        best_error = error;
+                        best = Some(Self {
+                            prescaler: prescaler as u16,
+                            seg1: seg1 as u8,
+                            seg2: seg2 as u8,
+                            sync_jump_width: sync_jump_width as u8,
+                            bitrate: actual,
+                        });
+                    }
+                }
+            }
+            tq -= 1;
+        }
+        best
+    }
+
+    /// Number of time quanta per bit
+    pub const fn quanta_per_bit(&self) -> u32 {
+        1 + self.seg1 as u32 + self.seg2 as u32
+    }
+
+    /// The sample point, in permille of the bit time
+    pub const fn sample_point_permille(&self) -> u32 {
+        (1 + self.seg1 as u32) * 1000 / self.quanta_per_bit()
+    }
+
+    /// Difference between the achieved and `target` bitrate, in parts per million
+    pub const fn error_ppm(&self, target: u32) -> u32 {
+        (self.bitrate.abs_diff(target) as u64 * 1_000_000 / target as u64) as u32
+    }
+
+    /// Converts into the nominal bit timing used by `embassy-stm32`
+    pub fn nominal(&self) -> NominalBitTiming {
+        NominalBitTiming {
+            prescaler: NonZeroU16::new(self.prescaler).unwrap(),
+            seg1: NonZeroU8::new(self.seg1).unwrap(),
+            seg2: NonZeroU8::new(self.seg2).unwrap(),
+            sync_jump_width: NonZeroU8::new(self.sync_jump_width).unwrap(),
+        }
+    }
+
+    /// Converts into the FD data bit timing used by `embassy-stm32`
+    pub fn data(&self, transceiver_delay_compensation: bool) -> DataBitTiming {
+        DataBitTiming {
+            transceiver_delay_compensation,
+            prescaler: NonZeroU16::new(self.prescaler).unwrap(),
+            seg1: NonZeroU8::new(self.seg1).unwrap(),
+            seg2: NonZeroU8::new(self.seg2).unwrap(),
+            sync_jump_width: NonZeroU8::new(self.sync_jump_width).unwrap(),
+        }
+    }
+}
+
+/// Bitrates the CAN bus runs at
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct CanSettings {
+    /// Bitrate of the arbitration phase, and of every classic CAN frame
+    pub nominal_bitrate: u32,
+    /// Bitrate of the data phase of FD frames, only used if `fd_enabled`
+    pub data_bitrate: u32,
+    /// Allow FD frames with bitrate switching
+    pub fd_enabled: bool,
+}
+
+impl CanSettings {
+    /// Sally's bus, classic CAN at 100 kbit/s
+    pub const DEFAULT: Self = Self {
+        nominal_bitrate: 100_000,
+        data_bitrate: 1_000_000,
+        fd_enabled: false,
+    };
+
+    /// Calculates the bit timings for these settings
+    ///
+    /// Panics if a bitrate can't be reached within [`MAX_BITRATE_ERROR_PPM`], so use this in a
+    /// `const` to fail the build instead.
+    pub const fn timings(&self, kernel_clock: u32) -> CanTimings {
+        let nominal =
+            CanBitTiming::calculate(kernel_clock, self.nominal_bitrate, 875, &NOMINAL_LIMITS)
+                .expect("nominal bitrate is unreachable");
+        assert!(nominal.error_ppm(self.nominal_bitrate) <= MAX_BITRATE_ERROR_PPM);
+        let data = if self.fd_enabled {
+            let data = CanBitTiming::calculate(kernel_clock, self.data_bitrate, 750, &DATA_LIMITS)
+                .expect("data bitrate is unreachable");
+            assert!(data.error_ppm(self.data_bitrate) <= MAX_BITRATE_ERROR_PPM);
+            Some(data)
+        } else {
+            None
+        };
+        CanTimings { nominal, data }
+    }
+}
+
+impl Default for CanSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Bit timings calculated from [`CanSettings`]
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct CanTimings {
+    pub nominal: CanBitTiming,
+    /// The FD data phase timing, `None` if FD is disabled
+    pub data: Option<CanBitTiming>,
+}
+
+impl CanTimings {
+    /// Applies the timings to a peripheral config, only allowing FD frames if FD is enabled
+    pub fn apply(&self, config: FdCanConfig) -> FdCanConfig {
+        let config = config.set_nominal_bit_timing(self.nominal.nominal());
+        match self.data {
+            Some(data) => config
+                .set_data_bit_timing(data.data(false))
+                .set_frame_transmit(FrameTransmissionConfig::AllowFdCanAndBRS),
+            None => config.set_frame_transmit(FrameTransmissionConfig::ClassicCanOnly),
+        }
+    }
+}
+
+// Known-good configurations, checked at compile time
+const _: () = {
+    // 80 MHz kernel clock, 1 Mbit/s nominal: 80 tq, 87.5% sample point
+    let nominal = CanBitTiming::calculate(80_000_000, 1_000_000, 875, &NOMINAL_LIMITS).unwrap();
+    assert!(nominal.bitrate == 1_000_000);
+    assert!(nominal.prescaler == 1 && nominal.seg1 == 69 && nominal.seg2 == 10);
+    assert!(nominal.sample_point_permille() == 875);
+
+    // 80 MHz kernel clock, 8 Mbit/s data: 10 tq, 80% sample point
+    let data = CanBitTiming::calculate(80_000_000, 8_000_000, 750, &DATA_LIMITS).unwrap();
+    assert!(data.bitrate == 8_000_000);
+    assert!(data.prescaler == 1 && data.seg1 == 7 && data.seg2 == 2);
+
+    // 8 MHz kernel clock, 100 kbit/s nominal
+    let nominal = CanBitTiming::calculate(8_000_000, 100_000, 875, &NOMINAL_LIMITS).unwrap();
+    assert!(nominal.error_ppm(100_000) == 0);
+    assert!(nominal.sample_point_permille() == 875);
+
+    // The default settings are reachable from the 8 MHz kernel clock, with or without FD
+    let timings = CanSettings::DEFAULT.timings(FDCAN_KERNEL_CLOCK);
+    assert!(timings.nominal.bitrate == 100_000 && timings.data.is_none());
+    let fd = CanSettings {
+        fd_enabled: true,
+        ..CanSettings::DEFAULT
+    };
+    let timings = fd.timings(FDCAN_KERNEL_CLOCK);
+    assert!(matches!(timings.data, Some(data) if data.bitrate == 1_000_000));
+
+    // And from an 80 MHz kernel clock
+    let timings = fd.timings(80_000_000);
+    assert!(timings.nominal.bitrate == 100_000 && timings.nominal.error_ppm(100_000) == 0);
+    assert!(timings.nominal.sample_point_permille() == 875);
+    assert!(matches!(timings.data, Some(data) if data.bitrate == 1_000_000));
+
+    // A bitrate times the most quanta per bit overflows a u32, but still calculates
+    assert!(
+        12_000_000u64 * (1 + NOMINAL_LIMITS.max_seg1 + NOMINAL_LIMITS.max_seg2) as u64
+            > u32::MAX as u64
+    );
+    let fast = CanBitTiming::calculate(192_000_000, 12_000_000, 875, &NOMINAL_LIMITS).unwrap();
+    assert!(fast.bitrate == 12_000_000 && fast.quanta_per_bit() == 16);
+};
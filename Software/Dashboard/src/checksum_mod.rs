@@ -0,0 +1,121 @@
+//! Module for payload checksums
+//!
+//! The CAN CRC catches almost every corrupted frame, but a safety critical package such as the
+//! H2 alarm can opt in to a second check: the sender appends a CRC-8 of the ID and payload as
+//! the last byte of the frame, and the frame is rejected if it doesn't match.
+//!
+//! The checksum is CRC-8/SAE-J1850 (polynomial 0x1D, init and final XOR 0xFF), over the ID as
+//! 4 big-endian bytes followed by the payload. Including the ID means a payload sent under the
+//! wrong ID is rejected too.
+//!
+//! Only the IDs in [`CHECKSUMMED_IDS`] are checked. The sender must be changed at the same time
+//! as an ID is added, or every frame with that ID will be rejected.
+
+use defmt::Format;
+
+/// IDs whose frames end in a checksum byte
+///
+/// Empty until the other boards send checksums, e.g. the H2 board for
+/// [`FDCAN_H2ALARM_ID`](crate::eco_can::FDCAN_H2ALARM_ID).
+pub const CHECKSUMMED_IDS: &[u32] = &[];
+
+const CRC8_POLY: u8 = 0x1D;
+const CRC8_INIT: u8 = 0xFF;
+const CRC8_XOR_OUT: u8 = 0xFF;
+
+/// A frame whose checksum byte doesn't match its ID and payload
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub struct ChecksumError {
+    pub id: u32,
+    /// The checksum of the received payload, `None` if the frame was empty
+    pub expected: Option<u8>,
+    pub received: Option<u8>,
+}
+
+/// Returns true if frames with `id` carry a checksum byte
+pub const fn has_checksum(id: u32) -> bool {
+    let mut i = 0;
+    while i < CHECKSUMMED_IDS.len() {
+        if CHECKSUMMED_IDS[i] == id {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Continues a CRC-8/SAE-J1850 over `bytes`
+const fn crc8_update(mut crc: u8, bytes: &[u8]) -> u8 {
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= bytes[i];
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ CRC8_POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
+
+/// The checksum a sender appends to `payload` for `id`
+pub const fn checksum(id: u32, payload: &[u8]) -> u8 {
+    let crc = crc8_update(CRC8_INIT, &id.to_be_bytes());
+    crc8_update(crc, payload) ^ CRC8_XOR_OUT
+}
+
+/// Checks the trailing checksum byte of a frame, returns the payload without it
+pub const fn strip_checksum(id: u32, rx_data: &[u8]) -> Result<&[u8], ChecksumError> {
+    let Some((&received, payload)) = rx_data.split_last() else {
+        return Err(ChecksumError {
+            id,
+            expected: None,
+            received: None,
+        });
+    };
+    let expected = checksum(id, payload);
+    if expected == received {
+        Ok(payload)
+    } else {
+        Err(ChecksumError {
+            id,
+            expected: Some(expected),
+            received: Some(received),
+        })
+    }
+}
+
+// CRC-8/SAE-J1850 check value
+const _: () = assert!(crc8_update(CRC8_INIT, b"123456789") ^ CRC8_XOR_OUT == 0x4B);
+
+// Good and corrupted payloads
+const _: () = {
+    const ID: u32 = 0x100;
+    const PAYLOAD: [u8; 1] = [1];
+    const FRAME: [u8; 2] = [PAYLOAD[0], checksum(ID, &PAYLOAD)];
+
+    // A good frame gives back its payload
+    match strip_checksum(ID, &FRAME) {
+        Ok(&[1]) => {}
+        _ => panic!("good frame rejected"),
+    }
+
+    // Any single bit flip is caught, in the payload or the checksum
+    let mut bit = 0;
+    while bit < 16 {
+        let mut corrupted = FRAME;
+        corrupted[bit / 8] ^= 1 << (bit % 8);
+        assert!(strip_checksum(ID, &corrupted).is_err());
+        bit += 1;
+    }
+
+    // The same payload under another ID, or with no checksum at all
+    assert!(strip_checksum(ID + 1, &FRAME).is_err());
+    assert!(strip_checksum(ID, &PAYLOAD).is_err());
+    assert!(strip_checksum(ID, &[]).is_err());
+};
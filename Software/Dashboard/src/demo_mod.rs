@@ -0,0 +1,228 @@
+//! Module for the demo mode
+//!
+//! For shows and UI work, the telemetry statics are filled with smoothly varying synthetic
+//! values instead of CAN data, so every widget shows motion without the car. Hold both buttons
+//! at boot to start it, it lasts until the dashboard is power cycled.
+//!
+//! [`demo_task`] writes every package on a timer, each as if it had just been received, so
+//! nothing shows as stale. Frames from the bus are still received and handled while it runs,
+//! so the H2 alarm and the thermal limits still enter the safe state, but their packages aren't
+//! stored, so real and synthetic data never mix on the screen. A `DEMO` badge stays on the
+//! screen. The demo packages are written straight into their statics, so the warnings, peaks
+//! and trip meter are only ever fed real data.
+//!
+//! The speed and RPM on the running screen don't come from CAN yet, so they don't move.
+
+use core::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant, Ticker};
+
+use crate::{
+    can_mod::{
+        BATT_PACK2_DATA, BOOST_PACK1_DATA, BOOST_PACK2_DATA, BOOST_PACK3_DATA, FCC_PACK1_DATA,
+        FCC_PACK2_DATA, FCC_PACK3_DATA, FET_DATA, H2_PACK1_DATA, H2_PACK2_DATA, REL_CAP_PACK,
+        REL_FC_PACK, RELAY_MOTOR_PACK, Timestamped,
+    },
+    eco_can::{
+        ECOCAN_H2Pack1_t, ECOCAN_H2Pack2_t, FDCAN_BATTPack2_t, FDCAN_BOOSTPack1_t,
+        FDCAN_BOOSTPack2_t, FDCAN_BOOSTPack3_t, FDCAN_FccPack1_t, FDCAN_FccPack2_t,
+        FDCAN_FccPack3_t, FDCAN_FetPack_t, FDCAN_RelPackCap_t, FDCAN_RelPackFc_t,
+        FDCAN_RelPackMtr_t, FetState,
+    },
+    log_mod::info,
+};
+
+/// Time between demo updates
+pub const DEMO_INTERVAL: Duration = Duration::from_millis(100);
+
+static DEMO_MODE: AtomicBool = AtomicBool::new(false);
+
+/// True while the demo is running
+pub fn demo_mode() -> bool {
+    DEMO_MODE.load(Relaxed)
+}
+
+/// `sin` of `phase` out of a turn of `period`, in thousandths
+///
+/// Uses Bhaskara's approximation, which is within 0.2 % and needs no floats.
+pub const fn sine_permille(phase: u64, period: u64) -> i32 {
+    let degrees = (phase % period * 360 / period) as i32;
+    let (half, negative) = if degrees < 180 {
+        (degrees, false)
+    } else {
+        (degrees - 180, true)
+    };
+    let product = half * (180 - half);
+    let sine = 4_000 * product / (40_500 - product);
+    if negative { -sine } else { sine }
+}
+
+/// A sine wave between `min` and `max`, starting at the middle
+pub const fn wave(phase: u64, period: u64, min: i32, max: i32) -> i32 {
+    min + (max - min) * (1_000 + sine_permille(phase, period)) / 2_000
+}
+
+/// A triangle wave ramping from `min` up to `max` and back down
+pub const fn ramp(phase: u64, period: u64, min: i32, max: i32) -> i32 {
+    let half = period / 2;
+    let at = phase % period;
+    let rise = if at < half { at } else { period - at };
+    min + ((max - min) as i64 * rise as i64 / half as i64) as i32
+}
+
+/// Stores a package as if it had just been received
+///
+/// Skipped if the package is locked, the next update comes soon enough.
+fn store<T>(data: &Mutex<ThreadModeRawMutex, Timestamped<T>>, value: T, now: Instant) {
+    if let Ok(mut package) = data.try_lock() {
+        package.value = value;
+        package.last_seen = Some(now);
+    }
+}
+
+/// Fills every package shown on the display with synthetic values, `ms` into the demo
+///
+/// Voltages are in mV, currents in mA, temperatures in 0.01 °C, and the efficiency in 0.01 %.
+/// The efficiency crosses the running screen's threshold, so both of its colours are shown.
+fn write_demo_packages(ms: u64, now: Instant) {
+    let fc_volt = ramp(ms, 20_000, 30_000, 42_000) as u32;
+    let fc_curr = wave(ms, 8_000, 5_000, 25_000) as u32;
+    let cap_volt = ramp(ms, 30_000, 30_000, 45_000) as u32;
+    let cap_curr = wave(ms, 12_000, -5_000, 10_000);
+    let mtr_curr = wave(ms, 10_000, 0, 30_000) as u32;
+
+    store(&REL_FC_PACK, FDCAN_RelPackFc_t { fc_volt, fc_curr }, now);
+    store(
+        &REL_CAP_PACK,
+        FDCAN_RelPackCap_t { cap_volt, cap_curr },
+        now,
+    );
+    store(
+        &RELAY_MOTOR_PACK,
+        FDCAN_RelPackMtr_t {
+            mtr_volt: wave(ms, 10_000, 36_000, 40_000) as u32,
+            mtr_curr,
+        },
+        now,
+    );
+    store(
+        &FET_DATA,
+        FDCAN_FetPack_t {
+            fet_config: FetState::FET_RUN as u32,
+            input_volt: fc_volt,
+            cap_volt,
+            cap_curr: cap_curr.unsigned_abs(),
+            res_curr: wave(ms, 6_000, 0, 500) as u32,
+            out_curr: mtr_curr,
+        },
+        now,
+    );
+    store(
+        &FCC_PACK1_DATA,
+        FDCAN_FccPack1_t {
+            fc_temp: wave(ms, 40_000, 40_00, 55_00),
+            fc_press: wave(ms, 15_000, 50, 80) as u32,
+        },
+        now,
+    );
+    store(
+        &FCC_PACK2_DATA,
+        FDCAN_FccPack2_t {
+            fan_rpm1: wave(ms, 9_000, 2_000, 5_000) as u32,
+            fan_rpm2: wave(ms + 3_000, 9_000, 2_000, 5_000) as u32,
+        },
+        now,
+    );
+    store(
+        &FCC_PACK3_DATA,
+        FDCAN_FccPack3_t {
+            bme_temp: wave(ms, 60_000, 25_00, 30_00) as u32,
+            bme_humid: wave(ms, 45_000, 40_00, 60_00) as u32,
+        },
+        now,
+    );
+    store(
+        &H2_PACK1_DATA,
+        ECOCAN_H2Pack1_t {
+            h2_sense_1: wave(ms, 7_000, 10, 20) as u16,
+            h2_sense_2: wave(ms + 1_000, 7_000, 10, 20) as u16,
+            h2_sense_3: wave(ms + 2_000, 7_000, 10, 20) as u16,
+            h2_sense_4: wave(ms + 3_000, 7_000, 10, 20) as u16,
+        },
+        now,
+    );
+    store(
+        &H2_PACK2_DATA,
+        ECOCAN_H2Pack2_t {
+            bme_temp: wave(ms, 50_000, 24_00, 28_00) as u16,
+            bme_humid: wave(ms, 35_000, 35_00, 50_00) as u16,
+            imon_7v: wave(ms, 5_000, 600, 800) as u16,
+            imon_12v: wave(ms, 5_000, 1_000, 1_400) as u16,
+        },
+        now,
+    );
+    store(
+        &BOOST_PACK1_DATA,
+        FDCAN_BOOSTPack1_t {
+            in_curr: fc_curr,
+            in_volt: fc_volt,
+        },
+        now,
+    );
+    store(
+        &BOOST_PACK2_DATA,
+        FDCAN_BOOSTPack2_t {
+            out_curr: mtr_curr,
+            out_volt: cap_volt,
+        },
+        now,
+    );
+    store(
+        &BOOST_PACK3_DATA,
+        FDCAN_BOOSTPack3_t {
+            efficiency: wave(ms, 15_000, 85_00, 96_00) as u32,
+            joules: (ms / 10) as u32,
+        },
+        now,
+    );
+    store(
+        &BATT_PACK2_DATA,
+        FDCAN_BATTPack2_t {
+            out_curr: wave(ms, 11_000, 200, 1_500) as u16,
+            out_volt: ramp(ms, 60_000, 12_000, 12_600) as u16,
+        },
+        now,
+    );
+}
+
+/// Runs the demo, writing synthetic packages every [`DEMO_INTERVAL`]
+#[embassy_executor::task]
+pub async fn demo_task() {
+    DEMO_MODE.store(true, Relaxed);
+    info!("Demo mode, showing synthetic data");
+    let start = Instant::now();
+    let mut ticker = Ticker::every(DEMO_INTERVAL);
+    loop {
+        let now = Instant::now();
+        write_demo_packages(now.duration_since(start).as_millis(), now);
+        ticker.next().await;
+    }
+}
+
+// The waves stay within their bounds, and peak where expected
+const _: () = {
+    assert!(sine_permille(0, 360) == 0);
+    assert!(sine_permille(90, 360) == 1_000);
+    assert!(sine_permille(180, 360) == 0);
+    assert!(sine_permille(270, 360) == -1_000);
+    assert!(sine_permille(360, 360) == 0);
+    assert!(wave(0, 1_000, 100, 200) == 150);
+    assert!(wave(250, 1_000, 100, 200) == 200);
+    assert!(wave(750, 1_000, 100, 200) == 100);
+    assert!(wave(250, 1_000, -50, 50) == 50);
+    assert!(ramp(0, 1_000, 100, 200) == 100);
+    assert!(ramp(500, 1_000, 100, 200) == 200);
+    assert!(ramp(750, 1_000, 100, 200) == 150);
+    assert!(ramp(1_000, 1_000, 100, 200) == 100);
+};
@@ -29,6 +29,13 @@
 //!   and the next word will fill the next pixel (the adjacent on the right, or
 //!   the first of the next row if the row ended)
 //!
+//! # Orientation and layout
+//! The panel's mounting is set by [`DISPLAY_ORIENTATION`], and [`DISPLAY_WIDTH`] and
+//! [`DISPLAY_HEIGHT`] follow from it. Widgets are placed relative to [`SCREEN`] with
+//! [`Anchor`], rather than at fixed pixel offsets, so a different orientation only changes
+//! the constant. A [`Grid`] splits the screen, or part of it, into cells for a widget to
+//! declare which one it occupies.
+//!
 //! # Optimization Strategies
 //! 1. The hardware is optimized for drawing rectangles. So prefer rendering rectangles over other shapes.
 //! 1. If a text/gui element's state does not change between render frames, do not redraw it.
@@ -36,85 +43,1860 @@
 //!  The reason for this is because the seven-segment font is rendered using multiple horizontal/veritcal lines
 //! (rectangles), [source](https://github.com/embedded-graphics/eg-seven-segment/blob/master/src/segment.rs#L39).
 
-use defmt::{info, trace};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering::Relaxed};
+
+use eg_seven_segment::SevenSegmentStyle;
+use embassy_embedded_hal::shared_bus::blocking::spi::SpiDeviceWithConfig;
+use embassy_stm32::peripherals::TIM15;
 use embassy_stm32::spi::Spi;
+use embassy_stm32::time::Hertz;
+use embassy_stm32::timer::simple_pwm::SimplePwm;
 use embassy_stm32::{gpio::Output, mode::Async};
-use embassy_time::{Instant, Timer};
-use embedded_graphics::draw_target::DrawTarget;
+use embassy_sync::{
+    blocking_mutex::{Mutex as BlockingMutex, raw::ThreadModeRawMutex},
+    mutex::Mutex,
+};
+use embassy_time::{Delay, Duration, Instant, Ticker, Timer};
+use embedded_graphics::draw_target::{DrawTarget, DrawTargetExt};
+use embedded_graphics::geometry::Dimensions;
+use embedded_graphics::image::{Image, ImageRaw};
 use embedded_graphics::{
-    pixelcolor::Rgb666,
-    prelude::{Point, RgbColor},
+    Drawable, Pixel,
+    mono_font::{MonoTextStyle, ascii::FONT_6X10},
+    pixelcolor::{BinaryColor, Rgb565, Rgb666},
+    prelude::AngleUnit,
+    prelude::{Point, RgbColor, Size, WebColors},
+    primitives::{
+        Arc, Line, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, StrokeAlignment,
+        StyledDrawable,
+    },
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
 };
-use embedded_hal_bus::spi::{ExclusiveDevice, NoDelay};
+use heapless::Vec;
 use mipidsi::models::ILI9488Rgb666;
-use mipidsi::{Display, interface::SpiInterface};
+use mipidsi::options::{ColorOrder, Orientation, Rotation};
+use mipidsi::{
+    Builder, Display,
+    interface::{Interface, InterfaceKind, SpiInterface},
+};
+use static_cell::StaticCell;
 
 use crate::eco_can::RelayState;
+use crate::log_mod::{error, info, set_verbosity, trace, verbosity, warn};
+use crate::mode::test_pattern::{TestPattern, display_test_pattern};
 use crate::{
+    adc_mod::SUPPLY_LOW,
+    btn_mod::{BUTTON_EVENTS, ButtonEvent, ButtonId},
     can_mod::RELAY_STATE,
+    demo_mod::demo_mode,
+    event_log_mod::log_event,
     mode::{
-        charging::render_charging_gui, init_charging::init_render_charging_gui,
-        init_running::init_render_running_gui, running::render_running_gui,
-        standby::render_standby_gui, startup::render_startup_gui,
+        alarm::{render_demo_badge, render_safe_state_gui, render_warning_indicators},
+        charging::render_charging_gui,
+        init_charging::init_render_charging_gui,
+        init_running::init_render_running_gui,
+        pages::{Page, render_page},
+        running::{invalidate_running_gui, render_running_gui},
+        standby::render_standby_gui,
+        startup::render_startup_gui,
+        touch_calibration::render_touch_target,
+    },
+    peak_mod::PEAKS,
+    safe_state_mod::{clear_safe_state, safe_state},
+    storage_mod::{erase_stored_trip, save_touch_calibration},
+    touch_mod::{
+        CalibrationRun, CalibrationStep, TOUCH_CALIBRATION, TOUCH_EVENTS, TouchEvent, TouchPoint,
     },
+    trip_mod::TRIP,
+    units_mod::{fill_overflow, pow10},
+    warning_mod::{FC_VOLTAGE_LOW, ThermalLevel, thermal_level},
+    watchdog_mod::{CriticalTask, check_in},
 };
 
+/// The SPI bus, shared by the display and the touch controller
+pub type SharedSpiBus = BlockingMutex<ThreadModeRawMutex, RefCell<Spi<'static, Async>>>;
+
+/// A device on [`SharedSpiBus`], the bus is reconfigured for each device's transactions
+/// since the touch controller is much slower than the display
+pub type SharedSpiDevice =
+    SpiDeviceWithConfig<'static, ThreadModeRawMutex, Spi<'static, Async>, Output<'static>>;
+
+/// SPI clock for the display, 40 MHz is the maximum frequency the ILI9488 can handle
+pub const LCD_SPI_FREQ: Hertz = Hertz::mhz(40);
+
+/// Puts SPI1 behind [`SharedSpiBus`], so the display and touch controller can each own a
+/// [`SharedSpiDevice`] on it. Can only be called once.
+pub fn share_spi_bus(spi: Spi<'static, Async>) -> &'static SharedSpiBus {
+    static SPI_BUS: StaticCell<SharedSpiBus> = StaticCell::new();
+    SPI_BUS.init(BlockingMutex::new(RefCell::new(spi)))
+}
+
+/// Type Alias for the SPI interface to the display
+pub type DisplayInterface = SpiInterface<'static, SharedSpiDevice, Output<'static>>;
+
 /// Type Alias for ILI9488 driver, the current display driver
-pub type DisplayDevice = Display<
-    SpiInterface<
-        'static,
-        ExclusiveDevice<Spi<'static, Async>, Output<'static>, NoDelay>,
-        Output<'static>,
-    >,
-    ILI9488Rgb666,
-    Output<'static>,
->;
-
-pub const DISPLAY_WIDTH: u32 = 480;
-pub const DISPLAY_HEIGHT: u32 = 320;
-pub const CENTER_POINT: Point = Point::new(DISPLAY_WIDTH as i32 / 2, DISPLAY_HEIGHT as i32 / 2);
+pub type DisplayDevice = Display<DisplayBus, ILI9488Rgb666, DisplayReset>;
+
+/// A handle to the display's [`DisplayInterface`], see [`share_display`]
+///
+/// The driver takes its interface by value and drops it if init fails, so it is given this
+/// handle instead, which can be handed over again for another attempt.
+#[derive(Clone, Copy)]
+pub struct DisplayBus(&'static BlockingMutex<ThreadModeRawMutex, RefCell<DisplayInterface>>);
+
+impl Interface for DisplayBus {
+    type Word = u8;
+    type Error = <DisplayInterface as Interface>::Error;
+
+    const KIND: InterfaceKind = DisplayInterface::KIND;
+
+    fn send_command(&mut self, command: u8, args: &[u8]) -> Result<(), Self::Error> {
+        self.0
+            .lock(|di| di.borrow_mut().send_command(command, args))
+    }
+
+    fn send_pixels<const N: usize>(
+        &mut self,
+        pixels: impl IntoIterator<Item = [u8; N]>,
+    ) -> Result<(), Self::Error> {
+        self.0.lock(|di| di.borrow_mut().send_pixels(pixels))
+    }
+
+    fn send_repeated_pixel<const N: usize>(
+        &mut self,
+        pixel: [u8; N],
+        count: u32,
+    ) -> Result<(), Self::Error> {
+        self.0
+            .lock(|di| di.borrow_mut().send_repeated_pixel(pixel, count))
+    }
+}
+
+/// A handle to the display's reset pin, see [`DisplayBus`]
+#[derive(Clone, Copy)]
+pub struct DisplayReset(&'static BlockingMutex<ThreadModeRawMutex, RefCell<Output<'static>>>);
+
+impl embedded_hal::digital::ErrorType for DisplayReset {
+    type Error = core::convert::Infallible;
+}
+
+impl embedded_hal::digital::OutputPin for DisplayReset {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.lock(|pin| pin.borrow_mut().set_low());
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.lock(|pin| pin.borrow_mut().set_high());
+        Ok(())
+    }
+}
+
+/// Puts the display's interface and reset pin behind handles, so init can be retried with
+/// them. Can only be called once.
+pub fn share_display(di: DisplayInterface, reset: Output<'static>) -> (DisplayBus, DisplayReset) {
+    static DISPLAY_BUS: StaticCell<BlockingMutex<ThreadModeRawMutex, RefCell<DisplayInterface>>> =
+        StaticCell::new();
+    static DISPLAY_RESET: StaticCell<BlockingMutex<ThreadModeRawMutex, RefCell<Output<'static>>>> =
+        StaticCell::new();
+    (
+        DisplayBus(DISPLAY_BUS.init(BlockingMutex::new(RefCell::new(di)))),
+        DisplayReset(DISPLAY_RESET.init(BlockingMutex::new(RefCell::new(reset)))),
+    )
+}
+
+/// How the panel is mounted in the car, landscape with the connector on the left
+pub const DISPLAY_ORIENTATION: Orientation =
+    Orientation::new().rotate(Rotation::Deg270).flip_vertical();
+/// Rotation of the panel, touch calibrations are kept per rotation
+pub const DISPLAY_ROTATION: Rotation = DISPLAY_ORIENTATION.rotation;
+
+/// Size of the ILI9488 in its native portrait orientation
+const PANEL_SIZE: Size = Size::new(320, 480);
+/// Screen size in the current orientation, the width and height swap when rotated sideways
+const SCREEN_SIZE: Size = if DISPLAY_ROTATION.is_vertical() {
+    Size::new(PANEL_SIZE.height, PANEL_SIZE.width)
+} else {
+    PANEL_SIZE
+};
+pub const DISPLAY_WIDTH: u32 = SCREEN_SIZE.width;
+pub const DISPLAY_HEIGHT: u32 = SCREEN_SIZE.height;
+/// The whole screen, lay widgets out relative to this with [`Anchor`]
+pub const SCREEN: Rectangle = Rectangle::new(Point::zero(), SCREEN_SIZE);
+pub const CENTER_POINT: Point = Anchor::Center.point(SCREEN);
+
+/// A point on the edge or at the center of an area, used to lay out widgets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Horizontal and vertical position, 0 for left/top, 1 for center and 2 for right/bottom
+    const fn position(self) -> (i32, i32) {
+        match self {
+            Self::TopLeft => (0, 0),
+            Self::TopCenter => (1, 0),
+            Self::TopRight => (2, 0),
+            Self::CenterLeft => (0, 1),
+            Self::Center => (1, 1),
+            Self::CenterRight => (2, 1),
+            Self::BottomLeft => (0, 2),
+            Self::BottomCenter => (1, 2),
+            Self::BottomRight => (2, 2),
+        }
+    }
+
+    /// The anchor's point on `area`, right and bottom are just outside the area
+    pub const fn point(self, area: Rectangle) -> Point {
+        let (x, y) = self.position();
+        Point::new(
+            area.top_left.x + area.size.width as i32 * x / 2,
+            area.top_left.y + area.size.height as i32 * y / 2,
+        )
+    }
+
+    /// The anchor's point on `area`, moved by `offset`
+    pub const fn at(self, area: Rectangle, offset: Point) -> Point {
+        let point = self.point(area);
+        Point::new(point.x + offset.x, point.y + offset.y)
+    }
+
+    /// The part of `area` on the anchor's side
+    ///
+    /// The corners are quarters of the area, the edges halves, and the center is all of it.
+    pub const fn region(self, area: Rectangle) -> Rectangle {
+        let (x, y) = self.position();
+        let (width, height) = (area.size.width, area.size.height);
+        let (left, region_width) = match x {
+            0 => (0, width / 2),
+            1 => (0, width),
+            _ => (width / 2, width - width / 2),
+        };
+        let (top, region_height) = match y {
+            0 => (0, height / 2),
+            1 => (0, height),
+            _ => (height / 2, height - height / 2),
+        };
+        Rectangle::new(
+            Point::new(area.top_left.x + left as i32, area.top_left.y + top as i32),
+            Size::new(region_width, region_height),
+        )
+    }
+}
+
+// The layout matches the original fixed offsets in the landscape orientation
+const _: () = {
+    assert!(DISPLAY_WIDTH == 480 && DISPLAY_HEIGHT == 320);
+    assert!(CENTER_POINT.x == 240 && CENTER_POINT.y == 160);
+    let bottom_right = Anchor::BottomRight.point(SCREEN);
+    assert!(bottom_right.x == 480 && bottom_right.y == 320);
+    let quarter = Anchor::TopRight.region(SCREEN);
+    assert!(quarter.top_left.x == 240 && quarter.top_left.y == 0);
+    assert!(quarter.size.width == 240 && quarter.size.height == 160);
+    let center = Anchor::Center.point(Anchor::BottomRight.region(SCREEN));
+    assert!(center.x == 360 && center.y == 240);
+    let offset = Anchor::BottomLeft.at(SCREEN, Point::new(50, -50));
+    assert!(offset.x == 50 && offset.y == 270);
+};
+
+/// An area split into equal columns and rows, so widgets can be placed by cell
+///
+/// Place a widget with an [`Anchor`] on its cell, such as
+/// `Anchor::Center.point(GRID.cell(1, 0))`, rather than a pixel offset. When the area doesn't
+/// divide evenly the spare pixels go to the later cells, so the cells always tile the area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Grid {
+    area: Rectangle,
+    cols: u32,
+    rows: u32,
+}
+
+impl Grid {
+    /// Splits the whole screen into `cols` by `rows` cells
+    pub const fn new(cols: u32, rows: u32) -> Self {
+        Self::within(SCREEN, cols, rows)
+    }
+
+    /// Splits `area` into `cols` by `rows` cells, such as a cell of another grid
+    pub const fn within(area: Rectangle, cols: u32, rows: u32) -> Self {
+        assert!(cols > 0 && rows > 0, "a grid needs at least one cell");
+        Self { area, cols, rows }
+    }
+
+    pub const fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub const fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// The cell in column `col` and row `row`, counted from the top left
+    pub const fn cell(&self, col: u32, row: u32) -> Rectangle {
+        self.span(col, row, 1, 1)
+    }
+
+    /// The `cols` by `rows` cells from the cell in column `col` and row `row`, as one area
+    pub const fn span(&self, col: u32, row: u32, cols: u32, rows: u32) -> Rectangle {
+        assert!(
+            cols > 0 && rows > 0 && col + cols <= self.cols && row + rows <= self.rows,
+            "cells outside the grid"
+        );
+        let (left, right) = (self.x(col), self.x(col + cols));
+        let (top, bottom) = (self.y(row), self.y(row + rows));
+        Rectangle::new(
+            Point::new(left, top),
+            Size::new((right - left) as u32, (bottom - top) as u32),
+        )
+    }
+
+    /// Left edge of column `col`, or the right edge of the area for `col == cols`
+    const fn x(&self, col: u32) -> i32 {
+        let offset = self.area.size.width as u64 * col as u64 / self.cols as u64;
+        self.area.top_left.x + offset as i32
+    }
+
+    /// Top edge of row `row`, or the bottom edge of the area for `row == rows`
+    const fn y(&self, row: u32) -> i32 {
+        let offset = self.area.size.height as u64 * row as u64 / self.rows as u64;
+        self.area.top_left.y + offset as i32
+    }
+}
+
+// Cells tile the area, matching the anchor regions where they overlap
+const _: () = {
+    let grid = Grid::new(2, 2);
+    let quarter = grid.cell(1, 0);
+    let region = Anchor::TopRight.region(SCREEN);
+    assert!(quarter.top_left.x == region.top_left.x && quarter.top_left.y == region.top_left.y);
+    assert!(quarter.size.width == region.size.width && quarter.size.height == region.size.height);
+    let all = grid.span(0, 0, 2, 2);
+    assert!(all.top_left.x == 0 && all.top_left.y == 0);
+    assert!(all.size.width == DISPLAY_WIDTH && all.size.height == DISPLAY_HEIGHT);
+
+    // Uneven splits, the last column ends at the edge of the screen
+    let thirds = Grid::new(7, 3);
+    let last = thirds.cell(6, 2);
+    assert!(last.top_left.x + last.size.width as i32 == DISPLAY_WIDTH as i32);
+    assert!(last.top_left.y + last.size.height as i32 == DISPLAY_HEIGHT as i32);
+    assert!(thirds.cell(0, 0).size.width == 68 && last.size.width == 69);
+
+    // A grid within a cell is offset by the cell
+    let nested = Grid::within(grid.cell(1, 1), 2, 1).cell(1, 0);
+    assert!(nested.top_left.x == 360 && nested.top_left.y == 160);
+    assert!(nested.size.width == 120 && nested.size.height == 160);
+};
+
+/// Backlight PWM frequency, high enough that the dimming does not visibly flicker
+pub const BACKLIGHT_PWM_FREQ: Hertz = Hertz::khz(20);
+
+/// The PWM driving the backlight, `None` until [`init_backlight`] is called
+static BACKLIGHT: Mutex<ThreadModeRawMutex, Option<SimplePwm<'static, TIM15>>> = Mutex::new(None);
+
+/// Takes control of the backlight PWM, and turns the backlight on at full brightness
+pub async fn init_backlight(mut pwm: SimplePwm<'static, TIM15>) {
+    let mut ch1 = pwm.ch1();
+    ch1.set_duty_cycle_fully_on();
+    ch1.enable();
+    *BACKLIGHT.lock().await = Some(pwm);
+}
+
+/// Sets the backlight brightness, from 0 to 100%
+///
+/// Overridden by the next ambient light reading while auto brightness is enabled.
+pub async fn set_brightness(percent: u8) {
+    let percent = percent.min(100);
+    match BACKLIGHT.lock().await.as_mut() {
+        Some(pwm) => pwm.ch1().set_duty_cycle_percent(percent),
+        None => warn!("Backlight is not initialized"),
+    }
+}
+
+/// Full scale of a backlight duty, duties are in per mille
+pub const BACKLIGHT_DUTY_MAX: u16 = 1000;
+/// Dimmest the backlight is set by auto brightness, so the screen never goes dark
+pub const BACKLIGHT_MIN_DUTY: u16 = 100;
+/// Weight of a new reading in the smoothed duty, as a power of two (1/8)
+const BRIGHTNESS_SMOOTHING_SHIFT: u32 = 3;
+
+/// Backlight duty at each ambient light level, `(lux, duty)` in increasing lux
+///
+/// The eye responds to light roughly logarithmically, so the points are spaced by decades.
+/// Duties between points are interpolated, and held at the ends.
+const LUX_TO_DUTY: [(u16, u16); 5] = [
+    (0, BACKLIGHT_MIN_DUTY),
+    (10, 200),
+    (100, 400),
+    (1_000, 700),
+    (10_000, BACKLIGHT_DUTY_MAX),
+];
+
+/// The backlight duty for an ambient light level, in per mille
+pub const fn lux_to_duty(lux: u16) -> u16 {
+    let mut i = 1;
+    while i < LUX_TO_DUTY.len() {
+        let (lux_hi, duty_hi) = LUX_TO_DUTY[i];
+        if lux < lux_hi {
+            let (lux_lo, duty_lo) = LUX_TO_DUTY[i - 1];
+            let span = (duty_hi - duty_lo) as u32 * (lux - lux_lo) as u32;
+            return duty_lo + (span / (lux_hi - lux_lo) as u32) as u16;
+        }
+        i += 1;
+    }
+    LUX_TO_DUTY[LUX_TO_DUTY.len() - 1].1
+}
+
+/// Moves the smoothed duty towards `target`, always by at least 1 so it settles on it
+const fn smooth_duty(smoothed: u16, target: u16) -> u16 {
+    let step = (target as i32 - smoothed as i32) >> BRIGHTNESS_SMOOTHING_SHIFT;
+    let step = if step == 0 {
+        (target as i32 - smoothed as i32).signum()
+    } else {
+        step
+    };
+    (smoothed as i32 + step) as u16
+}
+
+// The curve only brightens, and stays between the minimum and full brightness
+const _: () = {
+    let mut lux: u32 = 0;
+    let mut previous = 0;
+    while lux <= u16::MAX as u32 {
+        let duty = lux_to_duty(lux as u16);
+        assert!(duty >= previous && duty >= BACKLIGHT_MIN_DUTY && duty <= BACKLIGHT_DUTY_MAX);
+        previous = duty;
+        lux += 1;
+    }
+    assert!(lux_to_duty(0) == BACKLIGHT_MIN_DUTY);
+    assert!(lux_to_duty(55) == 300);
+    assert!(lux_to_duty(u16::MAX) == BACKLIGHT_DUTY_MAX);
+};
+
+// Smoothing settles on the target from either side, without overshooting
+const _: () = {
+    let mut smoothed = BACKLIGHT_MIN_DUTY;
+    let mut i = 0;
+    while i < 100 {
+        smoothed = smooth_duty(smoothed, BACKLIGHT_DUTY_MAX);
+        assert!(smoothed <= BACKLIGHT_DUTY_MAX);
+        i += 1;
+    }
+    assert!(smoothed == BACKLIGHT_DUTY_MAX);
+    assert!(smooth_duty(BACKLIGHT_DUTY_MAX, BACKLIGHT_MIN_DUTY) == 887);
+    assert!(smooth_duty(500, 501) == 501 && smooth_duty(500, 499) == 499);
+};
+
+/// True while the backlight follows the ambient light
+static AUTO_BRIGHTNESS: AtomicBool = AtomicBool::new(false);
+/// Smoothed auto brightness duty, 0 until the first ambient light reading
+static AUTO_DUTY: AtomicU16 = AtomicU16::new(0);
+
+/// Turns auto brightness on or off, the backlight keeps its current brightness until the next
+/// reading or [`set_brightness`]
+pub fn set_auto_brightness(enabled: bool) {
+    if AUTO_BRIGHTNESS.swap(enabled, Relaxed) != enabled {
+        info!("Auto brightness {}", if enabled { "on" } else { "off" });
+    }
+}
+
+/// Feeds an ambient light reading, in lux, to auto brightness
+///
+/// Call this at a steady rate from the light source, such as an ADC channel or a CAN package.
+/// The backlight eases towards the new level over a few readings, so a passing shadow doesn't
+/// make it jump.
+pub async fn update_ambient_light(lux: u16) {
+    let target = lux_to_duty(lux);
+    let duty = match AUTO_DUTY.load(Relaxed) {
+        0 => target,
+        smoothed => smooth_duty(smoothed, target),
+    };
+    AUTO_DUTY.store(duty, Relaxed);
+    if !AUTO_BRIGHTNESS.load(Relaxed) {
+        return;
+    }
+    match BACKLIGHT.lock().await.as_mut() {
+        Some(pwm) => pwm.ch1().set_duty_cycle_fraction(duty, BACKLIGHT_DUTY_MAX),
+        None => warn!("Backlight is not initialized"),
+    }
+}
+
+/// Frames the display task renders per second at most
+pub const DISPLAY_TARGET_FPS: u64 = 10;
+/// Time each frame is given to render, a frame that runs over delays the next one
+pub const FRAME_BUDGET: Duration = Duration::from_millis(1000 / DISPLAY_TARGET_FPS);
+
+/// Paces the display task to [`DISPLAY_TARGET_FPS`], and warns about frames over budget
+struct FramePacer {
+    ticker: Ticker,
+    frame_start: Option<Instant>,
+}
+
+impl FramePacer {
+    fn new() -> Self {
+        Self {
+            ticker: Ticker::every(FRAME_BUDGET),
+            frame_start: None,
+        }
+    }
+
+    /// Ends the current frame, and waits for the start of the next one
+    async fn next_frame(&mut self) {
+        if let Some(start) = self.frame_start {
+            let render_time = start.elapsed();
+            if render_time > FRAME_BUDGET {
+                warn!(
+                    "Frame took {} ms, over the {} ms budget",
+                    render_time.as_millis(),
+                    FRAME_BUDGET.as_millis()
+                );
+                // Start counting from now, rather than rendering the missed frames back to back
+                self.ticker.reset();
+            }
+        }
+        self.ticker.next().await;
+        self.frame_start = Some(Instant::now());
+    }
+}
+
+/// Consecutive frames with draw errors before the display is re-initialized
+const DISPLAY_ERROR_LIMIT: u32 = 3;
+
+/// Draw errors since the display task last checked
+///
+/// A glitch on the long SPI cable shouldn't panic the board, so draws record their errors here
+/// with [`DrawResultExt::or_record`], and the display task re-initializes the display if they
+/// keep happening.
+pub static DRAW_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+/// Records a failed draw in [`DRAW_ERRORS`] instead of panicking
+pub trait DrawResultExt {
+    fn or_record(self);
+}
+
+impl<T, E> DrawResultExt for Result<T, E> {
+    fn or_record(self) {
+        if self.is_err() {
+            DRAW_ERRORS.fetch_add(1, Relaxed);
+        }
+    }
+}
+
+/// Attempts at initializing the display at boot, before running without it
+pub const DISPLAY_INIT_ATTEMPTS: u32 = 5;
+/// Time between display init attempts, for the panel's power rail to settle on a cold boot
+pub const DISPLAY_INIT_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Set if the display didn't come up at boot
+static DISPLAY_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// True if the display didn't come up at boot, and the dashboard is running without it
+///
+/// The LEDs signal it, and the display task isn't run, so the watchdog doesn't wait for it.
+pub fn display_failed() -> bool {
+    DISPLAY_FAILED.load(Relaxed)
+}
+
+/// Initializes the display at boot, up to [`DISPLAY_INIT_ATTEMPTS`] times
+///
+/// Waits [`DISPLAY_INIT_RETRY_DELAY`] after each failed attempt. If every attempt fails,
+/// [`display_failed`] is set and `None` is returned.
+pub async fn init_display_with_retry(di: DisplayBus, reset: DisplayReset) -> Option<DisplayDevice> {
+    for attempt in 1..=DISPLAY_INIT_ATTEMPTS {
+        if let Some(display) = init_display(di, reset) {
+            return Some(display);
+        }
+        warn!(
+            "Display init attempt {} of {} failed",
+            attempt, DISPLAY_INIT_ATTEMPTS
+        );
+        if attempt < DISPLAY_INIT_ATTEMPTS {
+            Timer::after(DISPLAY_INIT_RETRY_DELAY).await;
+        }
+    }
+    error!("Display didn't come up, running without it");
+    log_event("Display init failed");
+    DISPLAY_FAILED.store(true, Relaxed);
+    None
+}
+
+/// Resets and initializes the display, `None` if it doesn't respond
+pub fn init_display(di: DisplayBus, reset: DisplayReset) -> Option<DisplayDevice> {
+    Builder::new(ILI9488Rgb666, di)
+        .reset_pin(reset)
+        .color_order(ColorOrder::Bgr)
+        .orientation(DISPLAY_ORIENTATION)
+        .init(&mut Delay)
+        .inspect_err(|_| error!("Display initialization failed"))
+        .ok()
+}
+
+/// Toggles the display's reset pin and runs the init sequence again
+///
+/// The display is blank afterwards, so everything has to be redrawn.
+pub fn reinit_display(display: DisplayDevice) -> Option<DisplayDevice> {
+    let (di, _, reset) = display.release();
+    // The display is always built with a reset pin by `init_display`
+    init_display(di, reset?)
+}
+
+/// Maximum number of separate dirty regions tracked per frame
+const MAX_DIRTY_REGIONS: usize = 8;
+
+/// Areas of the screen that changed during a frame, and need to be redrawn
+///
+/// Overlapping rectangles are merged, so each area is only redrawn once. If more than
+/// [`MAX_DIRTY_REGIONS`] separate areas are marked, they are merged into larger ones.
+pub struct DirtyRegions {
+    regions: Vec<Rectangle, MAX_DIRTY_REGIONS>,
+}
+
+impl DirtyRegions {
+    pub const fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+        }
+    }
+
+    /// The smallest rectangle containing both `a` and `b`
+    fn union(a: &Rectangle, b: &Rectangle) -> Rectangle {
+        let (a_end, b_end) = (a.top_left + a.size, b.top_left + b.size);
+        let top_left = a.top_left.component_min(b.top_left);
+        let bottom_right = a_end.component_max(b_end);
+        Rectangle::with_corners(top_left, bottom_right - Point::new(1, 1))
+    }
+
+    /// Marks an area of the screen as changed
+    pub fn mark_dirty(&mut self, mut area: Rectangle) {
+        if area.is_zero_sized() {
+            return;
+        }
+        // Merge with every region it overlaps, merging may cause new overlaps so repeat until none
+        while let Some(i) = self
+            .regions
+            .iter()
+            .position(|region| !region.intersection(&area).is_zero_sized())
+        {
+            area = Self::union(&area, &self.regions.swap_remove(i));
+        }
+        if let Err(area) = self.regions.push(area) {
+            // Out of space, so grow an existing region to cover the new area
+            let merged = Self::union(&area, &self.regions.swap_remove(0));
+            self.mark_dirty(merged);
+        }
+    }
+
+    /// Returns true if any part of `area` is dirty
+    pub fn is_dirty(&self, area: &Rectangle) -> bool {
+        self.regions
+            .iter()
+            .any(|region| !region.intersection(area).is_zero_sized())
+    }
+
+    /// The dirty regions, which do not overlap
+    pub fn regions(&self) -> &[Rectangle] {
+        &self.regions
+    }
+
+    /// Clears all regions, called once the frame has been drawn
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+}
+
+impl Default for DirtyRegions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Areas of the screen to redraw on the next frame
+pub static DIRTY_REGIONS: Mutex<ThreadModeRawMutex, DirtyRegions> = Mutex::new(DirtyRegions::new());
+
+/// Marks an area of the screen to be redrawn on the next frame
+pub async fn mark_dirty(area: Rectangle) {
+    DIRTY_REGIONS.lock().await.mark_dirty(area);
+}
+
+/// The colors the screens are drawn in
+///
+/// The display task owns the active theme and passes it to every render function, so nothing
+/// it draws uses a literal color for its background or text. Colors that identify a reading,
+/// such as the red speed digits, are the same in every theme. The boot splash, test patterns,
+/// and alarm indicators don't follow the theme either.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Cleared to, and drawn over digits, needles, and bars to erase them
+    pub background: Rgb666,
+    /// Labels, outlines, and values
+    pub foreground: Rgb666,
+    /// Values from stale packages
+    pub muted: Rgb666,
+    /// Problems that need attention soon, such as CAN error-warning
+    pub caution: Rgb666,
+    /// Problems that need attention now, such as offline nodes
+    pub warning: Rgb666,
+    /// Healthy levels, such as the battery and charge meters
+    pub accent: Rgb666,
+}
+
+impl Theme {
+    /// Light on black, the default
+    pub const DARK: Theme = Theme {
+        name: "dark",
+        background: Rgb666::BLACK,
+        foreground: Rgb666::WHITE,
+        muted: Rgb666::CSS_GRAY,
+        caution: Rgb666::YELLOW,
+        warning: Rgb666::RED,
+        accent: Rgb666::GREEN,
+    };
+
+    /// Dark on white, for reading in direct sunlight
+    pub const LIGHT: Theme = Theme {
+        name: "light",
+        background: Rgb666::WHITE,
+        foreground: Rgb666::BLACK,
+        muted: Rgb666::CSS_GRAY,
+        caution: Rgb666::CSS_DARK_ORANGE,
+        warning: Rgb666::RED,
+        accent: Rgb666::CSS_GREEN,
+    };
+
+    /// The other built-in theme
+    pub fn toggled(&self) -> &'static Theme {
+        if *self == Theme::DARK {
+            &Theme::LIGHT
+        } else {
+            &Theme::DARK
+        }
+    }
+}
+
+/// A GUI element that is redrawn by the render loop
+pub trait Widget {
+    /// Redraws the widget with its current data
+    fn draw(&mut self, display: &mut DisplayDevice, theme: &Theme);
+
+    /// The area the widget draws to.
+    ///
+    /// If this is `Some`, the widget is only redrawn when part of its area is marked dirty
+    /// with [`mark_dirty`]. Defaults to `None`, redrawing whenever the update interval elapses.
+    fn bounds(&self) -> Option<Rectangle> {
+        None
+    }
+
+    /// The minimum time between redraws, regardless of how fast the widget's data changes.
+    ///
+    /// Defaults to redrawing every frame.
+    fn update_interval(&self) -> Duration {
+        Duration::from_ticks(0)
+    }
+}
+
+/// Tracks when a widget was last drawn, so the render loop can enforce its update interval
+pub struct WidgetSlot<W: Widget> {
+    pub widget: W,
+    last_draw: Option<Instant>,
+}
+
+impl<W: Widget> WidgetSlot<W> {
+    pub const fn new(widget: W) -> Self {
+        Self {
+            widget,
+            last_draw: None,
+        }
+    }
+
+    /// Returns true if the widget's update interval has elapsed since it was last drawn
+    pub fn is_due(&self, now: Instant) -> bool {
+        should_draw(self.last_draw, now, self.widget.update_interval(), true)
+    }
+
+    /// Draws the widget if it is due, and its area is dirty. Returns true if the widget was drawn.
+    pub fn render(
+        &mut self,
+        display: &mut DisplayDevice,
+        theme: &Theme,
+        now: Instant,
+        dirty: &DirtyRegions,
+    ) -> bool {
+        let dirty = self
+            .widget
+            .bounds()
+            .is_none_or(|bounds| dirty.is_dirty(&bounds));
+        if !should_draw(self.last_draw, now, self.widget.update_interval(), dirty) {
+            return false;
+        }
+        self.widget.draw(display, theme);
+        self.last_draw = Some(now);
+        true
+    }
+
+    /// Forces the widget to be drawn on the next frame
+    pub fn invalidate(&mut self) {
+        self.last_draw = None;
+    }
+}
+
+/// Whether a widget is drawn this frame, see [`WidgetSlot::render`]
+///
+/// A widget that was never drawn is always drawn. Otherwise it is drawn once `interval` has
+/// elapsed since `last_draw`, and only if its area is `dirty`.
+pub const fn should_draw(
+    last_draw: Option<Instant>,
+    now: Instant,
+    interval: Duration,
+    dirty: bool,
+) -> bool {
+    match last_draw {
+        Some(last_draw) => {
+            dirty && now.as_ticks().saturating_sub(last_draw.as_ticks()) >= interval.as_ticks()
+        }
+        None => true,
+    }
+}
+
+// A widget with a 1 s interval whose data changes every 100 ms frame is only drawn once a
+// second, and a widget with no interval is drawn every frame
+const _: () = {
+    const fn draws(interval: Duration, frames: u64) -> u64 {
+        let frame = Duration::from_millis(100).as_ticks();
+        let mut last_draw = None;
+        let mut draws = 0;
+        let mut i = 0;
+        while i < frames {
+            let now = Instant::from_ticks(i * frame);
+            if should_draw(last_draw, now, interval, true) {
+                last_draw = Some(now);
+                draws += 1;
+            }
+            i += 1;
+        }
+        draws
+    }
+    // Frames from 0 to 2.9 s, drawn at 0, 1 and 2 s
+    assert!(draws(Duration::from_secs(1), 30) == 3);
+    assert!(draws(Duration::from_ticks(0), 30) == 30);
+    // A clean area is never redrawn once drawn
+    let now = Instant::from_secs(10);
+    assert!(!should_draw(
+        Some(Instant::from_ticks(0)),
+        now,
+        Duration::from_ticks(0),
+        false
+    ));
+    assert!(should_draw(None, now, Duration::from_secs(1), false));
+};
+
+/// Formats `value`, scaled by `10^decimals`, into `buf` with the decimal point in place
+///
+/// E.g. 123 with 1 decimal is `"12.3"`. There is always a digit before the point, and exactly
+/// `decimals` after it, so 5 with 2 decimals is `"0.05"` and 1200 is `"12.00"`. `decimals` must be
+/// at most 9.
+///
+/// A value longer than `buf` fills all of it with 9s, see
+/// [`fill_overflow`](crate::units_mod::fill_overflow), rather than showing a truncated number.
+/// Written without `core::fmt`, so it is `const` and checked at compile time.
+pub const fn format_fixed(value: u32, decimals: u8, buf: &mut [u8]) -> &str {
+    let unit = pow10(decimals);
+    let mut whole = value / unit;
+    let mut frac = value % unit;
+
+    let mut whole_digits = 1;
+    let mut rest = whole / 10;
+    while rest > 0 {
+        whole_digits += 1;
+        rest /= 10;
+    }
+    let len = whole_digits
+        + if decimals > 0 {
+            1 + decimals as usize
+        } else {
+            0
+        };
+
+    let buf = if len > buf.len() {
+        fill_overflow(buf, decimals);
+        buf
+    } else {
+        let (buf, _) = buf.split_at_mut(len);
+        let mut at = len;
+        let mut i = 0;
+        while i < decimals {
+            at -= 1;
+            buf[at] = b'0' + (frac % 10) as u8;
+            frac /= 10;
+            i += 1;
+        }
+        if decimals > 0 {
+            at -= 1;
+            buf[at] = b'.';
+        }
+        while at > 0 {
+            at -= 1;
+            buf[at] = b'0' + (whole % 10) as u8;
+            whole /= 10;
+        }
+        buf
+    };
+    // Only ASCII digits and points are written
+    match core::str::from_utf8(buf) {
+        Ok(text) => text,
+        Err(_) => "",
+    }
+}
+
+/// Compares a formatted number, `==` on strings isn't const
+const fn formats_as(value: u32, decimals: u8, buf: &mut [u8], expected: &str) -> bool {
+    let text = format_fixed(value, decimals, buf).as_bytes();
+    let expected = expected.as_bytes();
+    if text.len() != expected.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] != expected[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = {
+    let buf = &mut [0; 8];
+    assert!(formats_as(123, 1, buf, "12.3"));
+    assert!(formats_as(123, 0, buf, "123"));
+    // Leading zeros before and after the point
+    assert!(formats_as(5, 2, buf, "0.05"));
+    assert!(formats_as(0, 1, buf, "0.0"));
+    assert!(formats_as(0, 0, buf, "0"));
+    assert!(formats_as(12_345, 3, buf, "12.345"));
+    // Trailing zeros are kept, so the width doesn't jump as the value changes
+    assert!(formats_as(1_200, 2, buf, "12.00"));
+    assert!(formats_as(1_000_000, 3, buf, "1000.000"));
+    assert!(formats_as(u32::MAX, 9, &mut [0; 11], "4.294967295"));
+    // Exactly fills the buffer, then one digit too many
+    assert!(formats_as(9_999_999, 1, buf, "999999.9"));
+    assert!(formats_as(10_000_000, 1, buf, "999999.9"));
+    assert!(formats_as(12_345, 3, &mut [0; 5], "9.999"));
+    assert!(formats_as(123, 1, &mut [0; 2], "99"));
+    assert!(formats_as(1, 0, &mut [], ""));
+};
+
+/// A right aligned seven-segment number that only redraws the digits that changed
+///
+/// `DIGITS` is the maximum number of digits shown, values that don't fit are shown as all 9s.
+/// The style is passed to each update rather than stored, since it follows the theme, and a
+/// changed style redraws every digit.
+pub struct SevenSegField<const DIGITS: usize> {
+    /// Top left corner of the leftmost digit
+    position: Point,
+    /// The style last drawn with, `None` if nothing has been drawn
+    style: Option<SevenSegmentStyle<Rgb666>>,
+    /// The digits last drawn, leading blanks are `b' '`
+    last: [u8; DIGITS],
+}
+
+impl<const DIGITS: usize> SevenSegField<DIGITS> {
+    pub const fn new(position: Point) -> Self {
+        Self {
+            position,
+            style: None,
+            last: [b' '; DIGITS],
+        }
+    }
+
+    /// Splits a value into right aligned digits
+    fn digits(value: u32) -> [u8; DIGITS] {
+        let mut digits = [b' '; DIGITS];
+        let mut str_buffer = itoa::Buffer::new();
+        let value_str = str_buffer.format(value).as_bytes();
+        if value_str.len() > DIGITS {
+            return [b'9'; DIGITS];
+        }
+        digits[DIGITS - value_str.len()..].copy_from_slice(value_str);
+        digits
+    }
+
+    /// The area covered by digit `i`
+    fn digit_area(&self, style: &SevenSegmentStyle<Rgb666>, i: usize) -> Rectangle {
+        let pitch = (style.digit_size.width + style.digit_spacing) as i32;
+        Rectangle::new(
+            self.position + Point::new(i as i32 * pitch, 0),
+            style.digit_size,
+        )
+    }
+
+    /// Draws `value`, only redrawing the digits that changed since the last update.
+    ///
+    /// Digits that are no longer used when the value shrinks (e.g. 100 -> 9) are cleared.
+    pub fn update(
+        &mut self,
+        display: &mut DisplayDevice,
+        theme: &Theme,
+        style: SevenSegmentStyle<Rgb666>,
+        value: u32,
+    ) {
+        let redraw = self.style != Some(style);
+        let digits = Self::digits(value);
+        for (i, &digit) in digits.iter().enumerate() {
+            if !redraw && self.last[i] == digit {
+                continue;
+            }
+            let area = self.digit_area(&style, i);
+            // Drawing a digit overwrites its inactive segments, but the gaps between segments
+            // are only cleared if there is no inactive segment colour
+            if digit == b' ' || style.inactive_segment_color.is_none() {
+                area.draw_styled(&PrimitiveStyle::with_fill(theme.background), display)
+                    .or_record();
+            }
+            if digit != b' ' {
+                let digit = [digit];
+                // Only ASCII digits are stored
+                let digit = core::str::from_utf8(&digit).unwrap();
+                Text::with_baseline(digit, area.top_left, style, Baseline::Top)
+                    .draw(display)
+                    .or_record();
+            }
+        }
+        self.style = Some(style);
+        self.last = digits;
+    }
+
+    /// Forces every digit to be redrawn on the next update, e.g. after the screen is cleared
+    pub fn invalidate(&mut self) {
+        self.style = None;
+    }
+}
+
+/// A horizontal bar filled from the left in proportion to a value
+///
+/// Only filled rectangles are drawn, and an update only redraws the strip between the old and
+/// new fill, so a slowly changing value costs a few columns of pixels per frame.
+pub struct BarGauge {
+    /// Inside of the bar, the frame is drawn around it
+    area: Rectangle,
+    min: u32,
+    max: u32,
+    fill_color: Rgb666,
+    /// Width of the fill last drawn, `None` if nothing has been drawn
+    last_fill: Option<u32>,
+}
+
+impl BarGauge {
+    /// Values at or below `min` show an empty bar, and at or above `max` a full bar
+    pub const fn new(area: Rectangle, min: u32, max: u32, fill_color: Rgb666) -> Self {
+        Self {
+            area,
+            min,
+            max,
+            fill_color,
+            last_fill: None,
+        }
+    }
+
+    /// Inside of the bar, the frame is drawn around it
+    pub const fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    /// Width of the fill for `value`, rounded down
+    pub const fn fill_width(&self, value: u32) -> u32 {
+        if self.max <= self.min || value <= self.min {
+            return 0;
+        }
+        if value >= self.max {
+            return self.area.size.width;
+        }
+        ((value - self.min) as u64 * self.area.size.width as u64 / (self.max - self.min) as u64)
+            as u32
+    }
+
+    /// The columns from `start` to `end` of the bar
+    fn strip(&self, start: u32, end: u32) -> Rectangle {
+        Rectangle::new(
+            self.area.top_left + Point::new(start as i32, 0),
+            Size::new(end - start, self.area.size.height),
+        )
+    }
+
+    /// Draws the frame around the bar, call after the screen is cleared
+    pub fn draw_frame(&self, display: &mut DisplayDevice, theme: &Theme) {
+        self.area
+            .offset(1)
+            .draw_styled(&PrimitiveStyle::with_stroke(theme.foreground, 1), display)
+            .or_record();
+    }
+
+    /// Draws `value`, only redrawing the part of the bar that changed since the last update
+    pub fn update(&mut self, display: &mut DisplayDevice, theme: &Theme, value: u32) {
+        let fill = self.fill_width(value);
+        let width = self.area.size.width;
+        let (filled, cleared) = match self.last_fill {
+            None => ((0, fill), (fill, width)),
+            Some(last) if fill > last => ((last, fill), (fill, fill)),
+            Some(last) => ((fill, fill), (fill, last)),
+        };
+        if filled.1 > filled.0 {
+            self.strip(filled.0, filled.1)
+                .draw_styled(&PrimitiveStyle::with_fill(self.fill_color), display)
+                .or_record();
+        }
+        if cleared.1 > cleared.0 {
+            self.strip(cleared.0, cleared.1)
+                .draw_styled(&PrimitiveStyle::with_fill(theme.background), display)
+                .or_record();
+        }
+        self.last_fill = Some(fill);
+    }
+
+    /// Forces the whole bar to be redrawn on the next update, e.g. after the screen is cleared
+    pub fn invalidate(&mut self) {
+        self.last_fill = None;
+    }
+}
+
+// The fill is proportional to the value, and clamped to the bar
+const _: () = {
+    let area = Rectangle::new(Point::new(0, 0), Size::new(200, 10));
+    let gauge = BarGauge::new(area, 100, 300, Rgb666::GREEN);
+    assert!(gauge.fill_width(0) == 0);
+    assert!(gauge.fill_width(100) == 0);
+    assert!(gauge.fill_width(200) == 100);
+    assert!(gauge.fill_width(299) == 199);
+    assert!(gauge.fill_width(300) == 200);
+    assert!(gauge.fill_width(u32::MAX) == 200);
+    assert!(BarGauge::new(area, 5, 5, Rgb666::GREEN).fill_width(10) == 0);
+};
+
+/// Sine of an angle in whole degrees
+///
+/// There is no `libm`, so this is a Taylor series on the first quadrant, accurate to about 1e-6.
+const fn sin_deg(deg: i32) -> f32 {
+    let deg = deg.rem_euclid(360);
+    let (quadrant_deg, sign) = match deg {
+        0..=90 => (deg, 1.0),
+        91..=180 => (180 - deg, 1.0),
+        181..=270 => (deg - 180, -1.0),
+        _ => (360 - deg, -1.0),
+    };
+    let x = quadrant_deg as f32 * core::f32::consts::PI / 180.0;
+    let x2 = x * x;
+    let series = x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0))));
+    sign * series
+}
+
+/// Cosine of an angle in whole degrees
+const fn cos_deg(deg: i32) -> f32 {
+    sin_deg(deg + 90)
+}
+
+/// Maps a value to a needle angle, `start_deg` at `min` and `start_deg + sweep_deg` at `max`
+///
+/// Angles follow `embedded-graphics`: 0° points right and angles increase clockwise. Values
+/// outside `min..=max` are clamped.
+pub const fn value_to_angle(value: u32, min: u32, max: u32, start_deg: i32, sweep_deg: i32) -> i32 {
+    if max <= min || value <= min {
+        return start_deg;
+    }
+    if value >= max {
+        return start_deg + sweep_deg;
+    }
+    let offset = (value - min) as i64 * sweep_deg as i64 / (max - min) as i64;
+    start_deg + offset as i32
+}
+
+/// A dial with a needle, for values like fan RPM
+///
+/// The dial is drawn once after a clear. Each update only erases the old needle and draws the
+/// new one, which is two line draws, and nothing if the angle didn't change.
+pub struct ArcGauge {
+    center: Point,
+    /// Radius of the dial, the needle stops just inside it
+    radius: u32,
+    start_deg: i32,
+    sweep_deg: i32,
+    min: u32,
+    max: u32,
+    needle_color: Rgb666,
+    /// Angle of the needle last drawn, `None` if nothing has been drawn
+    last_angle: Option<i32>,
+}
+
+impl ArcGauge {
+    /// Width of the needle and the dial
+    const STROKE_WIDTH: u32 = 3;
+    /// Smallest radius with room for a needle inside the dial
+    pub const MIN_RADIUS: u32 = 2 * Self::STROKE_WIDTH + 1;
+
+    /// Panics if `radius` is under [`Self::MIN_RADIUS`], which fails the build for a `static`
+    pub const fn new(
+        center: Point,
+        radius: u32,
+        start_deg: i32,
+        sweep_deg: i32,
+        min: u32,
+        max: u32,
+        needle_color: Rgb666,
+    ) -> Self {
+        assert!(
+            radius >= Self::MIN_RADIUS,
+            "gauge radius is too small for a needle"
+        );
+        Self {
+            center,
+            radius,
+            start_deg,
+            sweep_deg,
+            min,
+            max,
+            needle_color,
+            last_angle: None,
+        }
+    }
+
+    pub const fn center(&self) -> Point {
+        self.center
+    }
+
+    /// The needle angle for `value`
+    pub const fn angle(&self, value: u32) -> i32 {
+        value_to_angle(value, self.min, self.max, self.start_deg, self.sweep_deg)
+    }
+
+    /// The end of the needle at `angle`
+    const fn needle_tip(&self, angle: i32) -> Point {
+        let length = (self.radius - 2 * Self::STROKE_WIDTH) as f32;
+        Point::new(
+            self.center.x + (length * cos_deg(angle)) as i32,
+            self.center.y + (length * sin_deg(angle)) as i32,
+        )
+    }
+
+    fn draw_needle(&self, display: &mut DisplayDevice, angle: i32, color: Rgb666) {
+        Line::new(self.center, self.needle_tip(angle))
+            .draw_styled(
+                &PrimitiveStyle::with_stroke(color, Self::STROKE_WIDTH),
+                display,
+            )
+            .or_record();
+    }
+
+    /// Draws the dial, call after the screen is cleared
+    pub fn draw_dial(&self, display: &mut DisplayDevice, theme: &Theme) {
+        Arc::with_center(
+            self.center,
+            self.radius * 2,
+            (self.start_deg as f32).deg(),
+            (self.sweep_deg as f32).deg(),
+        )
+        .draw_styled(
+            &PrimitiveStyle::with_stroke(theme.foreground, Self::STROKE_WIDTH),
+            display,
+        )
+        .or_record();
+    }
+
+    /// Moves the needle to `value`, erasing the old needle
+    pub fn update(&mut self, display: &mut DisplayDevice, theme: &Theme, value: u32) {
+        let angle = self.angle(value);
+        match self.last_angle {
+            Some(last) if last == angle => return,
+            Some(last) => self.draw_needle(display, last, theme.background),
+            None => (),
+        }
+        self.draw_needle(display, angle, self.needle_color);
+        self.last_angle = Some(angle);
+    }
+
+    /// Forces the needle to be redrawn on the next update, e.g. after the screen is cleared
+    pub fn invalidate(&mut self) {
+        self.last_angle = None;
+    }
+}
+
+// Values map linearly onto the sweep, and the needle points the right way
+const _: () = {
+    assert!(value_to_angle(0, 0, 1000, 150, 240) == 150);
+    assert!(value_to_angle(500, 0, 1000, 150, 240) == 270);
+    assert!(value_to_angle(1000, 0, 1000, 150, 240) == 390);
+    assert!(value_to_angle(5000, 0, 1000, 150, 240) == 390);
+    assert!(value_to_angle(50, 100, 100, 150, 240) == 150);
+
+    let gauge = ArcGauge::new(Point::new(100, 100), 56, 150, 240, 0, 1000, Rgb666::RED);
+    // Straight up at the middle of the sweep, since y grows downwards
+    let tip = gauge.needle_tip(gauge.angle(500));
+    assert!(tip.x == 100 && tip.y == 50);
+    let tip = gauge.needle_tip(0);
+    assert!(tip.x == 150 && tip.y == 100);
+};
+
+/// Width and height of the status icons, in pixels
+pub const ICON_SIZE: u32 = 16;
+
+/// A monochrome icon, one bit per pixel, drawn in any color with [`draw_icon`]
+pub type Icon = ImageRaw<'static, BinaryColor>;
+
+/// Packs an icon's rows into bytes, the most significant bit is the leftmost pixel
+///
+/// Writing each row as a binary literal keeps the picture readable in the source.
+pub const fn icon_bytes<const BYTES: usize>(rows: [u16; ICON_SIZE as usize]) -> [u8; BYTES] {
+    assert!(BYTES == rows.len() * 2);
+    let mut bytes = [0; BYTES];
+    let mut i = 0;
+    while i < rows.len() {
+        let [high, low] = rows[i].to_be_bytes();
+        bytes[i * 2] = high;
+        bytes[i * 2 + 1] = low;
+        i += 1;
+    }
+    bytes
+}
+
+/// Bytes of an [`ICON_SIZE`] square icon
+const ICON_BYTES: usize = (ICON_SIZE * ICON_SIZE / 8) as usize;
+
+#[rustfmt::skip]
+static LIGHTNING_BITS: [u8; ICON_BYTES] = icon_bytes([
+    0b0000_0000_1111_1000,
+    0b0000_0001_1111_0000,
+    0b0000_0011_1110_0000,
+    0b0000_0111_1100_0000,
+    0b0000_1111_1000_0000,
+    0b0001_1111_1111_1000,
+    0b0011_1111_1111_0000,
+    0b0000_0001_1110_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0111_1000_0000,
+    0b0000_0111_0000_0000,
+    0b0000_1110_0000_0000,
+    0b0000_1100_0000_0000,
+    0b0001_1000_0000_0000,
+    0b0001_0000_0000_0000,
+    0b0000_0000_0000_0000,
+]);
+
+#[rustfmt::skip]
+static WARNING_BITS: [u8; ICON_BYTES] = icon_bytes([
+    0b0000_0001_1000_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0111_1110_0000,
+    0b0000_0110_0110_0000,
+    0b0000_1110_0111_0000,
+    0b0000_1110_0111_0000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0011_1111_1111_1100,
+    0b0011_1110_0111_1100,
+    0b0111_1110_0111_1110,
+    0b0111_1111_1111_1110,
+    0b1111_1111_1111_1111,
+    0b1111_1111_1111_1111,
+    0b0000_0000_0000_0000,
+]);
+
+#[rustfmt::skip]
+static THERMOMETER_BITS: [u8; ICON_BYTES] = icon_bytes([
+    0b0000_0011_1100_0000,
+    0b0000_0110_0110_0000,
+    0b0000_0100_0010_0000,
+    0b0000_0101_1010_0000,
+    0b0000_0101_1010_0000,
+    0b0000_0101_1010_0000,
+    0b0000_0101_1010_0000,
+    0b0000_0101_1010_0000,
+    0b0000_0101_1010_0000,
+    0b0000_1101_1011_0000,
+    0b0001_1011_1101_1000,
+    0b0001_0111_1110_1000,
+    0b0001_0111_1110_1000,
+    0b0001_1011_1101_1000,
+    0b0000_1100_0011_0000,
+    0b0000_0111_1110_0000,
+]);
+
+/// Charging, or the fuel cell
+pub static LIGHTNING_ICON: Icon = Icon::new(&LIGHTNING_BITS, ICON_SIZE);
+/// A warning or alarm
+pub static WARNING_ICON: Icon = Icon::new(&WARNING_BITS, ICON_SIZE);
+/// A temperature warning
+pub static THERMOMETER_ICON: Icon = Icon::new(&THERMOMETER_BITS, ICON_SIZE);
+
+// Rows pack left to right, and the icons are square
+const _: () = {
+    let bytes: [u8; ICON_BYTES] = icon_bytes([0b1000_0000_0000_0001; ICON_SIZE as usize]);
+    assert!(bytes[0] == 0x80 && bytes[1] == 0x01 && bytes[ICON_BYTES - 1] == 0x01);
+    assert!(LIGHTNING_BITS[0] == 0x00 && LIGHTNING_BITS[1] == 0xF8);
+    assert!(ICON_BYTES * 8 == (ICON_SIZE * ICON_SIZE) as usize);
+};
+
+/// Draws monochrome images onto the display in two colors
+struct Tinted<'a> {
+    display: &'a mut DisplayDevice,
+    color: Rgb666,
+    background: Rgb666,
+}
+
+impl Dimensions for Tinted<'_> {
+    fn bounding_box(&self) -> Rectangle {
+        self.display.bounding_box()
+    }
+}
+
+impl DrawTarget for Tinted<'_> {
+    type Color = BinaryColor;
+    type Error = <DisplayDevice as DrawTarget>::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        let (color, background) = (self.color, self.background);
+        self.display
+            .draw_iter(pixels.into_iter().map(|Pixel(point, pixel)| {
+                Pixel(point, if pixel.is_on() { color } else { background })
+            }))
+    }
+
+    /// An image is sent as one window, rather than a window per pixel
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = BinaryColor>,
+    {
+        let (color, background) = (self.color, self.background);
+        self.display.fill_contiguous(
+            area,
+            colors
+                .into_iter()
+                .map(|pixel| if pixel.is_on() { color } else { background }),
+        )
+    }
+}
+
+/// Draws a monochrome icon with its top left corner at `top_left`
+///
+/// Unset pixels are drawn in `background`, so the icon also erases whatever was under it.
+pub fn draw_icon(
+    display: &mut DisplayDevice,
+    icon: &Icon,
+    top_left: Point,
+    color: Rgb666,
+    background: Rgb666,
+) {
+    let mut target = Tinted {
+        display,
+        color,
+        background,
+    };
+    Image::new(icon, top_left).draw(&mut target).or_record();
+}
+
+/// Draws an RGB565 bitmap, two big-endian bytes per pixel, with its top left corner at
+/// `top_left`
+pub fn draw_rgb565(display: &mut DisplayDevice, image: &ImageRaw<'_, Rgb565>, top_left: Point) {
+    Image::new(image, top_left)
+        .draw(&mut display.color_converted::<Rgb565>())
+        .or_record();
+}
+
+/// An icon shown while a status is active, such as the H2 alarm
+///
+/// There is no framebuffer, so the icon is only drawn, or erased, when the status changes.
+pub struct StatusIcon {
+    icon: &'static Icon,
+    top_left: Point,
+    color: Rgb666,
+    /// Whether the icon was last drawn or erased, `None` if it needs to be redrawn
+    shown: Option<bool>,
+}
+
+impl StatusIcon {
+    pub const fn new(icon: &'static Icon, top_left: Point, color: Rgb666) -> Self {
+        Self {
+            icon,
+            top_left,
+            color,
+            shown: None,
+        }
+    }
+
+    /// The area the icon covers
+    pub const fn bounds(&self) -> Rectangle {
+        Rectangle::new(self.top_left, Size::new(ICON_SIZE, ICON_SIZE))
+    }
+
+    /// Shows the icon while `active`, erasing it otherwise
+    pub fn update(&mut self, display: &mut DisplayDevice, theme: &Theme, active: bool) {
+        if self.shown == Some(active) {
+            return;
+        }
+        if active {
+            draw_icon(
+                display,
+                self.icon,
+                self.top_left,
+                self.color,
+                theme.background,
+            );
+        } else {
+            self.bounds()
+                .draw_styled(&PrimitiveStyle::with_fill(theme.background), display)
+                .or_record();
+        }
+        self.shown = Some(active);
+    }
+
+    /// Forces the icon to be redrawn on the next update, e.g. after the screen is cleared
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+}
+
+/// A labelled box, filled while something is on and outlined while it is off
+///
+/// Like [`StatusIcon`], it is only drawn when the state changes. A state of `None` is unknown,
+/// such as from a stale package, and is outlined in the muted color.
+pub struct OnOffIndicator {
+    area: Rectangle,
+    label: &'static str,
+    /// The state last drawn, `None` if it needs to be redrawn
+    shown: Option<Option<bool>>,
+}
+
+impl OnOffIndicator {
+    /// Width of the outline
+    const BORDER_WIDTH: u32 = 2;
+
+    /// The label should fit in `area` in the 6x10 font
+    pub const fn new(area: Rectangle, label: &'static str) -> Self {
+        Self {
+            area,
+            label,
+            shown: None,
+        }
+    }
+
+    /// Shows `state`, if it changed since the last update
+    pub fn update(&mut self, display: &mut DisplayDevice, theme: &Theme, state: Option<bool>) {
+        if self.shown == Some(state) {
+            return;
+        }
+        let (fill, border, text) = match state {
+            Some(true) => (theme.accent, theme.accent, theme.background),
+            Some(false) => (theme.background, theme.foreground, theme.foreground),
+            None => (theme.background, theme.muted, theme.muted),
+        };
+        let style = PrimitiveStyleBuilder::new()
+            .fill_color(fill)
+            .stroke_color(border)
+            .stroke_width(Self::BORDER_WIDTH)
+            .stroke_alignment(StrokeAlignment::Inside)
+            .build();
+        self.area.draw_styled(&style, display).or_record();
+        Text::with_text_style(
+            self.label,
+            self.area.center(),
+            MonoTextStyle::new(&FONT_6X10, text),
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Middle)
+                .build(),
+        )
+        .draw(display)
+        .or_record();
+        self.shown = Some(state);
+    }
+
+    /// Forces the indicator to be redrawn on the next update, e.g. after the screen is cleared
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+}
+
+/// Reads every pending touch event, and returns the last touch down or long press
+///
+/// Only those are acted on, the moves and the release are dropped.
+fn take_touch() -> Option<TouchEvent> {
+    let mut touch = None;
+    while let Ok(event) = TOUCH_EVENTS.try_receive() {
+        if let TouchEvent::Down(_) | TouchEvent::LongPress(_) = event {
+            touch = Some(event);
+        }
+    }
+    touch
+}
 
 /// Responsible for rendering data to the display
+///
+/// Starts on the first test pattern if `test_pattern_at_boot`, rather than the startup screen.
+/// Button 2 short presses step through the patterns and then leave them, after which they can't
+/// be shown again until the next boot.
 #[embassy_executor::task]
-pub async fn display_task(mut display: DisplayDevice) {
+pub async fn display_task(mut display: DisplayDevice, test_pattern_at_boot: bool) {
     let start = Instant::now().as_millis();
-    display.clear(Rgb666::GREEN).unwrap();
+    display.clear(Rgb666::GREEN).or_record();
     let end = Instant::now().as_millis();
     info!("Time taken to do a full screen clear: {} ms", end - start);
 
     let mut prev_relay_state = RelayState::RELAY_STRTP;
+    // The active test pattern, if any
+    let mut test_pattern: Option<TestPattern> = None;
+    // The touch calibration in progress, if any
+    let mut calibration: Option<CalibrationRun> = None;
+    let mut buttons = BUTTON_EVENTS.subscriber().unwrap();
+    let mut page = Page::Overview;
+    let mut theme = &Theme::DARK;
+    let mut alarm_shown = false;
+    let mut fc_low_shown = false;
+    let mut supply_low_shown = false;
+    let mut thermal_shown = ThermalLevel::Normal;
+    // Consecutive frames with draw errors
+    let mut failed_frames = 0;
 
-    // Always render default startup screen
-    render_startup_gui(&mut display);
+    if test_pattern_at_boot {
+        info!("Button 2 held at boot, starting test patterns");
+        test_pattern = Some(TestPattern::Red);
+        display_test_pattern(&mut display, TestPattern::Red);
+    } else {
+        render_startup_gui(&mut display);
+    }
 
+    let mut pacer = FramePacer::new();
     loop {
+        pacer.next_frame().await;
+        check_in(CriticalTask::Display);
+        let mut redraw = false;
+
+        // Re-initialize the display if draws keep failing, rather than drawing to a dead screen
+        let errors = DRAW_ERRORS.swap(0, Relaxed);
+        if errors == 0 {
+            failed_frames = 0;
+        } else {
+            failed_frames += 1;
+            warn!("{} draw errors, {} failed frames", errors, failed_frames);
+        }
+        if failed_frames >= DISPLAY_ERROR_LIMIT {
+            warn!("Re-initializing the display");
+            log_event("Display re-initialized");
+            failed_frames = 0;
+            // If the display doesn't come back, this task stops checking in with the watchdog,
+            // which resets the board
+            let Some(reinit) = reinit_display(display) else {
+                return;
+            };
+            display = reinit;
+            alarm_shown = false;
+            test_pattern = None;
+            redraw = true;
+        }
+        let button_event = buttons.try_next_message_pure();
+        let touch = take_touch();
+
+        // The safe state takes over the whole screen until it is cleared
+        if let Some(fault) = safe_state().await {
+            if !alarm_shown {
+                render_safe_state_gui(&mut display, fault);
+                alarm_shown = true;
+                test_pattern = None;
+                calibration = None;
+            }
+            if let Some((ButtonId::Button2, ButtonEvent::DoublePress)) = button_event {
+                let _ = clear_safe_state().await;
+            }
+            continue;
+        } else if alarm_shown {
+            alarm_shown = false;
+            redraw = true;
+        }
+
+        // The screen is redrawn to show or hide the warning indicators
+        let fc_low = FC_VOLTAGE_LOW.load(Relaxed);
+        let supply_low = SUPPLY_LOW.load(Relaxed);
+        let thermal = thermal_level();
+        if fc_low != fc_low_shown || supply_low != supply_low_shown || thermal != thermal_shown {
+            fc_low_shown = fc_low;
+            supply_low_shown = supply_low;
+            thermal_shown = thermal;
+            redraw = true;
+        }
+
+        match button_event {
+            // Leave the touch calibration when button 2 is short pressed
+            Some((ButtonId::Button2, ButtonEvent::ShortPress)) if calibration.is_some() => {
+                info!("Touch calibration cancelled");
+                calibration = None;
+                redraw = true;
+            }
+            // On the packages page, button 2 short presses show the next group of packages
+            Some((ButtonId::Button2, ButtonEvent::ShortPress))
+                if matches!(page, Page::Packages(_)) =>
+            {
+                page = page.next_group();
+                redraw = true;
+            }
+            // Advance the test pattern when button 2 is short pressed, they are only started at
+            // boot, so a press while driving can't blank the screen
+            Some((ButtonId::Button2, ButtonEvent::ShortPress)) if test_pattern.is_some() => {
+                test_pattern = test_pattern.and_then(TestPattern::next);
+                match test_pattern {
+                    Some(pattern) => display_test_pattern(&mut display, pattern),
+                    None => {
+                        info!("Exiting test patterns");
+                        redraw = true;
+                    }
+                }
+            }
+            // On the peaks page, button 2 double presses reset the peaks
+            Some((ButtonId::Button2, ButtonEvent::DoublePress)) if page == Page::Peaks => {
+                PEAKS.lock().await.reset();
+                redraw = true;
+            }
+            // Toggle between the light and dark themes on a button 2 double press on any other page
+            Some((ButtonId::Button2, ButtonEvent::DoublePress)) => {
+                theme = theme.toggled();
+                info!("Switching to the {} theme", theme.name);
+                redraw = true;
+            }
+            // Reset the trip meter and erase the saved trip when button 1 is held
+            Some((ButtonId::Button1, ButtonEvent::LongPress)) => {
+                TRIP.lock().await.reset();
+                erase_stored_trip();
+            }
+            // Step the log verbosity when button 1 is tapped while button 2 is held
+            Some((ButtonId::Button1, ButtonEvent::Chord)) => set_verbosity(verbosity().next()),
+            // Switch pages when button 2 is held, and keep switching while it stays held. Not
+            // behind a test pattern, button 2 may still be held from boot
+            Some((ButtonId::Button2, ButtonEvent::LongPress | ButtonEvent::Repeat))
+                if test_pattern.is_none() =>
+            {
+                page = page.next();
+                info!("Switching to page {}", page);
+                redraw = true;
+            }
+            _ => (),
+        }
+        match (touch, &mut calibration) {
+            // Not behind a test pattern, the screen can't show the targets
+            _ if test_pattern.is_some() => (),
+            (Some(TouchEvent::Down(point)), Some(run)) => {
+                match run.record(point.raw) {
+                    CalibrationStep::Next => (),
+                    CalibrationStep::Done(result) => {
+                        TOUCH_CALIBRATION.lock().await.set(DISPLAY_ROTATION, result);
+                        save_touch_calibration();
+                        info!("Touch calibrated: {}", result);
+                        calibration = None;
+                    }
+                    CalibrationStep::Failed => {
+                        warn!("Touch calibration points in a line, retrying")
+                    }
+                }
+                redraw = true;
+            }
+            // A touch before the screen is calibrated, or one held, starts calibrating it
+            (
+                Some(
+                    TouchEvent::Down(TouchPoint { position: None, .. }) | TouchEvent::LongPress(_),
+                ),
+                None,
+            ) => {
+                info!("Starting touch calibration");
+                calibration = Some(CalibrationRun::new());
+                redraw = true;
+            }
+            // A tap on the screen switches pages, like holding button 2
+            (Some(TouchEvent::Down(_)), None) => {
+                page = page.next();
+                info!("Switching to page {}", page);
+                redraw = true;
+            }
+            _ => (),
+        }
+        if test_pattern.is_some() {
+            continue;
+        }
+        if let Some(run) = &calibration {
+            if redraw {
+                render_touch_target(&mut display, theme, run.target(), run.step());
+            }
+            continue;
+        }
+
+        if page != Page::Overview {
+            // A single clear on page change, then only the values are redrawn
+            if redraw {
+                display.clear(theme.background).or_record();
+                mark_dirty(display.bounding_box()).await;
+            }
+            render_page(&mut display, theme, page, redraw).await;
+            if redraw {
+                render_warning_indicators(
+                    &mut display,
+                    fc_low_shown,
+                    supply_low_shown,
+                    thermal_shown,
+                );
+            }
+            if demo_mode() {
+                render_demo_badge(&mut display);
+            }
+            DIRTY_REGIONS.lock().await.clear();
+            continue;
+        }
+
         let relay_state_lock = RELAY_STATE.lock().await;
         let relay_state = relay_state_lock.clone();
         drop(relay_state_lock);
 
         // Inialized display screen if switching relay state
-        if prev_relay_state != relay_state {
-            display.clear(Rgb666::BLACK).unwrap();
+        let cleared = prev_relay_state != relay_state || redraw;
+        if cleared {
+            display.clear(theme.background).or_record();
 
             match relay_state {
                 RelayState::RELAY_STRTP => render_startup_gui(&mut display),
-                RelayState::RELAY_CHRGE => init_render_charging_gui(&mut display),
-                RelayState::RELAY_STBY => render_standby_gui(&mut display, true).await,
-                RelayState::RELAY_RUN => init_render_running_gui(&mut display),
+                RelayState::RELAY_CHRGE => init_render_charging_gui(&mut display, theme),
+                RelayState::RELAY_STBY => render_standby_gui(&mut display, theme, true).await,
+                RelayState::RELAY_RUN => {
+                    init_render_running_gui(&mut display, theme);
+                    invalidate_running_gui().await;
+                }
             }
             // Update previous relay state
             prev_relay_state = relay_state.clone();
+            mark_dirty(display.bounding_box()).await;
         }
 
         // Update display with current relay state
         match relay_state {
             RelayState::RELAY_STRTP => (),
-            RelayState::RELAY_CHRGE => render_charging_gui(&mut display).await,
-            RelayState::RELAY_STBY => render_standby_gui(&mut display, false).await,
-            RelayState::RELAY_RUN => render_running_gui(&mut display),
+            RelayState::RELAY_CHRGE => render_charging_gui(&mut display, theme).await,
+            RelayState::RELAY_STBY => render_standby_gui(&mut display, theme, false).await,
+            RelayState::RELAY_RUN => render_running_gui(&mut display, theme).await,
+        }
+        if cleared {
+            render_warning_indicators(&mut display, fc_low_shown, supply_low_shown, thermal_shown);
         }
+        if demo_mode() {
+            render_demo_badge(&mut display);
+        }
+
+        // Everything dirty has been redrawn
+        DIRTY_REGIONS.lock().await.clear();
 
         trace!("Display Health check");
-        Timer::after_millis(10).await;
     }
 }
@@ -3,18 +3,24 @@
 //! A CAN package is setup like this:
 //! ```rust
 //! #[allow(non_camel_case_types)]
-//! #[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+//! #[derive(
+//!     bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+//! )]
+//! #[fdcan(id = CAN_ID)] // the ID of the CAN package
 //! #[repr(C)]
 //! pub struct FDCAN_PACKAGE_NAME {
 //!     // Package Data
 //! }
-//! impl FDCANPack for FDCAN_FetPack_t {
-//!    const FDCAN_BYTES: FDCANLength = BYTE_LENGTH; // set this to the size of the package in bytes
-//!    const FDCAN_ID: u32 = CAN_ID;    // the ID of the CAN package
-//! }
 //! ```
 //! `#[allow(non_camel_case_types)]` allows non-camel-case names for FDCAN packages
 //!
+//! `#[derive(FdcanPackage)]` implements [`FDCANPack`] with the ID from `#[fdcan(id = ...)]`.
+//! `FDCAN_BYTES` is computed from the fields, and it fails to compile if that isn't a valid
+//! [`FDCANLength`], see [`assert_len`]. An optional `min_bytes = N` sets
+//! [`FDCANPack::MIN_BYTES`], and `classic` sets [`FDCANPack::PREFER_CLASSIC`]. It also fails to
+//! compile if the ID is above [`MAX_STANDARD_ID`], or another package already uses it, see
+//! [`IdClaim`].
+//!
 //! `#[derive(bincode::Encode, bincode::Decode)]` makes the
 //! package able to be encoded to and decoded from bytes.
 //!
@@ -26,12 +32,59 @@
 //!
 //! `#[repr(C)]` Make Rust use the same memory layout for this struct as C to ensure compatility.
 //! For more information: [https://doc.rust-lang.org/nomicon/other-reprs.html](https://doc.rust-lang.org/nomicon/other-reprs.html)
+//!
+//! ### Engineering Units
+//! Fields are sent as fixed point integers. Packages with physical quantities have getters
+//! that apply the scale, such as [`FDCAN_RelPackFc_t::fc_volt_volts`]:
+//! - Voltages are sent in mV, see [`MILLI`]
+//! - Currents are sent in mA, see [`MILLI`]
+//! - Temperatures are sent in 0.01 °C, see [`CENTI`]
+//! - Efficiencies are sent in 0.01 %, see [`FDCAN_BOOSTPack3_t::efficiency_percent`]
+//!
+//! Use the getters instead of dividing raw fields, so the scale is only defined here.
+//!
+//! For text, each registered package has a [`PackageFields`] table giving every field's label,
+//! unit and scale, such as `Field::volts("FC Volt", ...)`. Fields whose scale the sending board
+//! doesn't document are shown as received.
 
-use bincode::error::DecodeError;
+use bincode::{
+    Decode, Encode,
+    config::{BigEndian, Configuration, Fixint},
+    error::{DecodeError, EncodeError},
+};
+use core::fmt::{self, Write};
 use defmt::Format;
+use fdcan_derive::FdcanPackage;
+
+use crate::units_mod::{
+    CENTI_DECIMALS, DISPLAY_DECIMALS, MILLI_DECIMALS, centi_to_units, round_scaled_signed,
+    write_scaled,
+};
+
+/// The wire format of every package: big-endian, with fixed size integers
+///
+/// Use [`encode_package`] and [`decode_package`] where possible, which both use this.
+pub const fn can_bincode_config() -> Configuration<BigEndian, Fixint> {
+    bincode::config::standard()
+        .with_big_endian()
+        .with_fixed_int_encoding()
+}
+
+/// Encodes a package into `tx_data`, returns the number of bytes written
+pub fn encode_package<T: Encode>(package: &T, tx_data: &mut [u8]) -> Result<usize, EncodeError> {
+    bincode::encode_into_slice(package, tx_data, can_bincode_config())
+}
+
+/// Decodes a package from the start of `rx_data`
+///
+/// Has no side effects, so it can be used to check received data before storing it.
+pub fn decode_package<T: Decode<()>>(rx_data: &[u8]) -> Result<T, DecodeError> {
+    Ok(bincode::decode_from_slice(rx_data, can_bincode_config())?.0)
+}
 
 /// Bit Definitions for FET State
 #[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
 #[repr(u8)]
 pub enum FetBit {
     ALL_FET_OFF = 0x00,
@@ -40,10 +93,38 @@ pub enum FetBit {
     RES_FET = 0x04,
     OUT_FET = 0x08,
 }
+impl FetBit {
+    /// Every FET, in the order of [`FDCAN_FetPack_t::fet_states`]
+    pub const FETS: [FetBit; 4] = [
+        FetBit::FUELCELL_FET,
+        FetBit::CAP_FET,
+        FetBit::RES_FET,
+        FetBit::OUT_FET,
+    ];
+
+    /// Short label for the FET indicators
+    pub const fn label(self) -> &'static str {
+        match self {
+            FetBit::ALL_FET_OFF => "OFF",
+            FetBit::FUELCELL_FET => "FC",
+            FetBit::CAP_FET => "CAP",
+            FetBit::RES_FET => "RES",
+            FetBit::OUT_FET => "OUT",
+        }
+    }
+
+    /// Whether this FET is on in `bits`, `ALL_FET_OFF` is only set when every FET is off
+    pub const fn is_set(self, bits: u8) -> bool {
+        match self {
+            FetBit::ALL_FET_OFF => bits == 0,
+            fet => bits & fet as u8 != 0,
+        }
+    }
+}
 
 /// FET States
 #[allow(non_camel_case_types)]
-#[derive(Clone, Debug, Format)]
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
 #[repr(u8)]
 pub enum FetState {
     FET_STBY = FetBit::ALL_FET_OFF as u8,
@@ -53,9 +134,54 @@ pub enum FetState {
         | FetBit::RES_FET as u8
         | FetBit::OUT_FET as u8,
 }
+impl FetState {
+    /// The state with exactly these FETs on, `None` for any other combination
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        const FET_STBY: u8 = FetState::FET_STBY as u8;
+        const FET_CHRGE: u8 = FetState::FET_CHRGE as u8;
+        const FET_RUN: u8 = FetState::FET_RUN as u8;
+
+        match bits {
+            FET_STBY => Some(FetState::FET_STBY),
+            FET_CHRGE => Some(FetState::FET_CHRGE),
+            FET_RUN => Some(FetState::FET_RUN),
+            _ => None,
+        }
+    }
+
+    /// Whether a FET is on in this state
+    pub const fn is_on(self, fet: FetBit) -> bool {
+        fet.is_set(self as u8)
+    }
+}
+impl TryFrom<u8> for FetState {
+    type Error = DecodeError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        FetState::from_bits(value).ok_or(DecodeError::Other("Invalid FET State"))
+    }
+}
+const _: () = {
+    assert!(matches!(
+        FetState::from_bits(0x00),
+        Some(FetState::FET_STBY)
+    ));
+    assert!(matches!(
+        FetState::from_bits(0x07),
+        Some(FetState::FET_CHRGE)
+    ));
+    assert!(matches!(FetState::from_bits(0x0F), Some(FetState::FET_RUN)));
+    assert!(FetState::from_bits(0x01).is_none());
+    assert!(FetState::from_bits(0x08).is_none());
+    assert!(FetState::from_bits(0xFF).is_none());
+    assert!(FetState::FET_CHRGE.is_on(FetBit::RES_FET));
+    assert!(!FetState::FET_CHRGE.is_on(FetBit::OUT_FET));
+    assert!(FetState::FET_STBY.is_on(FetBit::ALL_FET_OFF));
+    assert!(!FetState::FET_RUN.is_on(FetBit::ALL_FET_OFF));
+};
 
 /// Bit Definitions for REL Board State
 #[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
 #[repr(u8)]
 pub enum RelayBit {
     ALL_RELAY_OFF = 0x00,
@@ -64,9 +190,19 @@ pub enum RelayBit {
     DSCHRGE_RELAY = 0x04,
     MTR_RELAY = 0x08,
 }
+impl RelayBit {
+    /// Whether this relay is closed in `bits`, `ALL_RELAY_OFF` is only set when every relay is open
+    pub const fn is_set(self, bits: u8) -> bool {
+        match self {
+            RelayBit::ALL_RELAY_OFF => bits == 0,
+            relay => bits & relay as u8 != 0,
+        }
+    }
+}
 /// Relay Board State
 #[allow(non_camel_case_types)]
-#[derive(Clone, Debug, Format, PartialEq, Eq)]
+#[derive(Clone, Debug, Format, PartialEq, Eq, FdcanPackage)]
+#[fdcan(id = 0x018)]
 #[repr(u8)]
 pub enum RelayState {
     RELAY_STBY = RelayBit::ALL_RELAY_OFF as u8,
@@ -75,27 +211,97 @@ pub enum RelayState {
     RELAY_RUN =
         RelayBit::CAP_RELAY as u8 | RelayBit::DSCHRGE_RELAY as u8 | RelayBit::MTR_RELAY as u8,
 }
-impl FDCANPack for RelayState {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_1;
-    const FDCAN_ID: u32 = 0x018;
-}
-impl TryFrom<u8> for RelayState {
-    type Error = DecodeError;
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl RelayState {
+    pub const ALL: [RelayState; 4] = [
+        RelayState::RELAY_STBY,
+        RelayState::RELAY_STRTP,
+        RelayState::RELAY_CHRGE,
+        RelayState::RELAY_RUN,
+    ];
+
+    /// The state with exactly these relays closed, `None` for any other combination
+    pub const fn from_bits(bits: u8) -> Option<Self> {
         const RELAY_STBY: u8 = RelayState::RELAY_STBY as u8;
         const RELAY_STRTP: u8 = RelayState::RELAY_STRTP as u8;
         const RELAY_CHRGE: u8 = RelayState::RELAY_CHRGE as u8;
         const RELAY_RUN: u8 = RelayState::RELAY_RUN as u8;
 
-        match value {
-            RELAY_STBY => Ok(RelayState::RELAY_STBY),
-            RELAY_STRTP => Ok(RelayState::RELAY_STRTP),
-            RELAY_CHRGE => Ok(RelayState::RELAY_CHRGE),
-            RELAY_RUN => Ok(RelayState::RELAY_RUN),
-            _ => Err(DecodeError::Other("Invalid Relay State")),
+        match bits {
+            RELAY_STBY => Some(RelayState::RELAY_STBY),
+            RELAY_STRTP => Some(RelayState::RELAY_STRTP),
+            RELAY_CHRGE => Some(RelayState::RELAY_CHRGE),
+            RELAY_RUN => Some(RelayState::RELAY_RUN),
+            _ => None,
+        }
+    }
+
+    /// The relays closed in this state, the byte sent on the bus
+    pub const fn bits(&self) -> u8 {
+        match self {
+            RelayState::RELAY_STBY => RelayState::RELAY_STBY as u8,
+            RelayState::RELAY_STRTP => RelayState::RELAY_STRTP as u8,
+            RelayState::RELAY_CHRGE => RelayState::RELAY_CHRGE as u8,
+            RelayState::RELAY_RUN => RelayState::RELAY_RUN as u8,
         }
     }
+
+    /// Whether a relay is closed in this state
+    pub const fn is_on(&self, relay: RelayBit) -> bool {
+        relay.is_set(self.bits())
+    }
+}
+impl TryFrom<u8> for RelayState {
+    type Error = DecodeError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        RelayState::from_bits(value).ok_or(DecodeError::Other("Invalid Relay State"))
+    }
 }
+const _: () = {
+    assert!(matches!(
+        RelayState::from_bits(0x00),
+        Some(RelayState::RELAY_STBY)
+    ));
+    assert!(matches!(
+        RelayState::from_bits(0x06),
+        Some(RelayState::RELAY_STRTP)
+    ));
+    assert!(matches!(
+        RelayState::from_bits(0x02),
+        Some(RelayState::RELAY_CHRGE)
+    ));
+    assert!(matches!(
+        RelayState::from_bits(0x0D),
+        Some(RelayState::RELAY_RUN)
+    ));
+    assert!(RelayState::from_bits(0x01).is_none());
+    assert!(RelayState::from_bits(0xFF).is_none());
+    assert!(RelayState::RELAY_RUN.is_on(RelayBit::MTR_RELAY));
+    assert!(!RelayState::RELAY_CHRGE.is_on(RelayBit::CAP_RELAY));
+};
+// Every state round-trips through its byte, and no other byte is accepted. `try_from` is
+// `from_bits` with an error, so this is the set of bytes the decoder accepts.
+const _: () = {
+    let mut i = 0;
+    while i < RelayState::ALL.len() {
+        let bits = RelayState::ALL[i].bits();
+        match RelayState::from_bits(bits) {
+            Some(state) => assert!(state.bits() == bits),
+            None => panic!("relay state rejected"),
+        }
+        i += 1;
+    }
+
+    let mut accepted = 0;
+    let mut byte: u16 = 0;
+    while byte <= 0xFF {
+        if let Some(state) = RelayState::from_bits(byte as u8) {
+            assert!(state.bits() == byte as u8);
+            accepted += 1;
+        }
+        byte += 1;
+    }
+    assert!(accepted == RelayState::ALL.len());
+};
 
 /// The length of the package in bytes, can be up to 64 bytes.
 ///
@@ -122,6 +328,31 @@ pub enum FDCANLength {
     BYTES_64 = 64,
 }
 
+impl FDCANLength {
+    /// The variant for a package of `len` bytes, fails to compile if a frame can't be that long
+    pub const fn from_len(len: usize) -> Self {
+        match len {
+            0 => FDCANLength::BYTES_0,
+            1 => FDCANLength::BYTES_1,
+            2 => FDCANLength::BYTES_2,
+            3 => FDCANLength::BYTES_3,
+            4 => FDCANLength::BYTES_4,
+            5 => FDCANLength::BYTES_5,
+            6 => FDCANLength::BYTES_6,
+            7 => FDCANLength::BYTES_7,
+            8 => FDCANLength::BYTES_8,
+            12 => FDCANLength::BYTES_12,
+            16 => FDCANLength::BYTES_16,
+            20 => FDCANLength::BYTES_20,
+            24 => FDCANLength::BYTES_24,
+            32 => FDCANLength::BYTES_32,
+            48 => FDCANLength::BYTES_48,
+            64 => FDCANLength::BYTES_64,
+            _ => panic!("not a valid FDCAN frame length, pad the package to the next length"),
+        }
+    }
+}
+
 /// Prerequisite trait for FDCAN Packages
 ///
 /// Sets the ID and number of bytes for a CAN package.
@@ -150,6 +381,221 @@ pub trait FDCANPack {
     /// bits \[10:4\] in 0x010/0x01F but the last four bits \[3:0\] can be 0 or 1
     /// The same logic will be applied henceforth
     const FDCAN_ID: u32;
+    /// The shortest frame that can be decoded, must fall on a field boundary.
+    ///
+    /// Shorter frames only update the leading fields, the rest keep their previous values.
+    /// Defaults to [`Self::FDCAN_BYTES`], so the whole package must be received.
+    const MIN_BYTES: usize = Self::FDCAN_BYTES as usize;
+    /// Always sent in a classic frame, even when FD is enabled, for nodes that only accept
+    /// classic CAN. The package must fit in [`CLASSIC_MAX_BYTES`].
+    const PREFER_CLASSIC: bool = false;
+}
+
+/// The most data a classic CAN frame holds
+pub const CLASSIC_MAX_BYTES: usize = 8;
+
+/// The highest standard (11 bit) CAN ID
+pub const MAX_STANDARD_ID: u32 = 0x7FF;
+
+/// Implemented once for each CAN ID in use, so two packages given the same ID fail to compile
+///
+/// `#[derive(FdcanPackage)]` claims the package's ID, so a new package is checked without
+/// listing it anywhere. IDs without a package, such as [`FDCAN_H2ALARM_ID`], are claimed by
+/// hand. A duplicate is reported as conflicting implementations of `IdClaim<ID>` for
+/// [`CanIds`], with the ID in decimal.
+pub trait IdClaim<const ID: u32> {}
+
+/// The type every [`IdClaim`] is implemented for
+pub struct CanIds;
+
+/// Mask comparing bits \[10:4\] of an ID, so it matches a whole reserved block of 16 IDs
+pub const ID_BLOCK_MASK: u32 = 0x7F0;
+
+/// The filter ID and mask accepting the reserved block that `base` falls in
+///
+/// See [`FDCANPack::FDCAN_ID`] for the mask math.
+pub const fn id_range_mask(base: u32) -> (u32, u32) {
+    (base & ID_BLOCK_MASK, ID_BLOCK_MASK)
+}
+
+/// Whether `id` is accepted by a bit mask filter
+pub const fn id_matches_mask(id: u32, (filter, mask): (u32, u32)) -> bool {
+    id & mask == filter & mask
+}
+
+/// Checks that the mask for `base` accepts exactly the IDs `first..=last`
+const fn assert_id_range(base: u32, first: u32, last: u32) {
+    let range = id_range_mask(base);
+    let mut id = 0;
+    while id <= MAX_STANDARD_ID {
+        assert!(id_matches_mask(id, range) == (id >= first && id <= last));
+        id += 1;
+    }
+}
+const _: () = assert_id_range(0x010, 0x010, 0x01F);
+const _: () = assert_id_range(0x01F, 0x010, 0x01F);
+const _: () = assert_id_range(0x030, 0x030, 0x03F);
+const _: () = assert_id_range(0x032, 0x030, 0x03F);
+
+/// Divisor for fields sent in thousandths of a unit (mV, mA)
+pub const MILLI: f32 = 1_000.0;
+/// Divisor for fields sent in hundredths of a unit (0.01 °C)
+pub const CENTI: f32 = 100.0;
+
+/// A signed current field in amps, sent in mA
+///
+/// Negative currents flow back into the source, such as the capacitors charging from regen.
+pub const fn signed_current_amps(raw_ma: i32) -> f32 {
+    raw_ma as f32 / MILLI
+}
+/// An unsigned current field in amps, sent in mA
+pub const fn unsigned_current_amps(raw_ma: u32) -> f32 {
+    raw_ma as f32 / MILLI
+}
+/// A signed current field in mA, widened so it can be mixed with unsigned fields
+pub const fn signed_current_ma(raw_ma: i32) -> i64 {
+    raw_ma as i64
+}
+/// An unsigned current field in mA, widened so it can be mixed with signed fields
+///
+/// Casting a `u32` to `i32` instead would turn currents above 2.1 kA negative.
+pub const fn unsigned_current_ma(raw_ma: u32) -> i64 {
+    raw_ma as i64
+}
+/// Sum of currents in mA, saturating instead of overflowing
+pub const fn saturating_sum_ma(currents: &[i64]) -> i64 {
+    let mut sum: i64 = 0;
+    let mut i = 0;
+    while i < currents.len() {
+        sum = sum.saturating_add(currents[i]);
+        i += 1;
+    }
+    sum
+}
+
+/// How a package field is shown as text, such as on the packages page
+///
+/// The raw value is read through `raw`, widened to an `i64` so signed and unsigned fields share
+/// one type, and rounded to `decimals` with [`round_scaled_signed`].
+pub struct Field<T> {
+    /// Short name, such as `"FC Volt"`
+    pub label: &'static str,
+    /// Shown after the value, such as `"V"`, empty if it has none
+    pub unit: &'static str,
+    /// Decimals in the raw value, e.g. [`MILLI_DECIMALS`] for a field sent in mV
+    pub raw_decimals: u8,
+    /// Decimals shown
+    pub decimals: u8,
+    pub raw: fn(&T) -> i64,
+}
+
+impl<T> Field<T> {
+    pub const fn new(
+        label: &'static str,
+        unit: &'static str,
+        raw_decimals: u8,
+        decimals: u8,
+        raw: fn(&T) -> i64,
+    ) -> Self {
+        Self {
+            label,
+            unit,
+            raw_decimals,
+            decimals,
+            raw,
+        }
+    }
+
+    /// A voltage sent in mV, shown in volts
+    pub const fn volts(label: &'static str, raw: fn(&T) -> i64) -> Self {
+        Self::new(label, "V", MILLI_DECIMALS, DISPLAY_DECIMALS, raw)
+    }
+
+    /// A current sent in mA, shown in amps
+    pub const fn amps(label: &'static str, raw: fn(&T) -> i64) -> Self {
+        Self::new(label, "A", MILLI_DECIMALS, DISPLAY_DECIMALS, raw)
+    }
+
+    /// A temperature sent in 0.01 °C, the font has no degree sign
+    pub const fn celsius(label: &'static str, raw: fn(&T) -> i64) -> Self {
+        Self::new(label, "degC", CENTI_DECIMALS, DISPLAY_DECIMALS, raw)
+    }
+
+    /// A percentage sent in 0.01 %
+    pub const fn percent(label: &'static str, raw: fn(&T) -> i64) -> Self {
+        Self::new(label, "%", CENTI_DECIMALS, DISPLAY_DECIMALS, raw)
+    }
+
+    /// A field sent in whole units, such as `rpm`
+    pub const fn whole(label: &'static str, unit: &'static str, raw: fn(&T) -> i64) -> Self {
+        Self::new(label, unit, 0, 0, raw)
+    }
+
+    /// A field whose scale isn't documented, shown as received
+    pub const fn unscaled(label: &'static str, raw: fn(&T) -> i64) -> Self {
+        Self::whole(label, "", raw)
+    }
+
+    /// Writes the field's label and value from `package`, e.g. `FC Volt 12.3 V`
+    pub fn write(&self, package: &T, out: &mut dyn Write) -> fmt::Result {
+        write_field(
+            out,
+            self.label,
+            self.unit,
+            (self.raw)(package),
+            self.raw_decimals,
+            self.decimals,
+        )
+    }
+}
+
+/// The body of [`Field::write`], kept out of the generic impl so it is only compiled once
+fn write_field(
+    out: &mut dyn Write,
+    label: &str,
+    unit: &str,
+    raw: i64,
+    raw_decimals: u8,
+    decimals: u8,
+) -> fmt::Result {
+    let (negative, whole, frac) = round_scaled_signed(raw, raw_decimals, decimals);
+    out.write_str(label)?;
+    out.write_char(' ')?;
+    write_scaled(out, negative, whole, frac, decimals)?;
+    if !unit.is_empty() {
+        out.write_char(' ')?;
+        out.write_str(unit)?;
+    }
+    Ok(())
+}
+
+/// The label, unit and scale of each of a package's fields
+///
+/// Shown with [`Field::write`], so every package is presented the same way and showing a new
+/// package only needs its table. Registered packages must implement it, see `can_mod`.
+pub trait PackageFields: Sized + 'static {
+    /// Every field, in declaration order
+    const FIELDS: &'static [Field<Self>];
+}
+
+/// Fails to compile if a package's size does not match its declared `FDCAN_BYTES`,
+/// so a new field can't be added without updating the frame length.
+///
+/// Packages are `#[repr(C)]` and must not contain padding, so that their size matches the
+/// encoded length. `#[derive(FdcanPackage)]` invokes it for each package, a hand written
+/// [`FDCANPack`] impl should too:
+/// ```rust,ignore
+/// const _: () = assert_len::<FDCAN_PACKAGE_NAME>();
+/// ```
+pub const fn assert_len<T: FDCANPack>() {
+    core::assert!(
+        size_of::<T>() == T::FDCAN_BYTES as usize,
+        "FDCAN_BYTES does not match the size of the package"
+    );
+    core::assert!(
+        !T::PREFER_CLASSIC || T::FDCAN_BYTES as usize <= CLASSIC_MAX_BYTES,
+        "a package sent in classic frames must fit in 8 bytes"
+    );
 }
 
 // Highest priority CAN messages
@@ -161,8 +607,20 @@ pub const FDCAN_H2ALARM_ID: u16 = 0x001;
 /// 1 indicates led on
 pub const FDCAN_SYNCLED_ID: u16 = 0x00F;
 
+// The packageless IDs are checked like the derived ones
+impl IdClaim<{ FDCAN_H2ALARM_ID as u32 }> for CanIds {}
+impl IdClaim<{ FDCAN_SYNCLED_ID as u32 }> for CanIds {}
+const _: () = {
+    assert!(FDCAN_H2ALARM_ID as u32 <= MAX_STANDARD_ID);
+    assert!(FDCAN_SYNCLED_ID as u32 <= MAX_STANDARD_ID);
+};
+
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+// A classic CAN frame holds 8 bytes, which is `fet_config` and `input_volt`
+#[fdcan(id = 0x010, min_bytes = 8)]
 #[repr(C)]
 pub struct FDCAN_FetPack_t {
     pub fet_config: u32,
@@ -172,105 +630,285 @@ pub struct FDCAN_FetPack_t {
     pub res_curr: u32,
     pub out_curr: u32,
 }
-impl FDCANPack for FDCAN_FetPack_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_24;
-    const FDCAN_ID: u32 = 0x010;
+impl PackageFields for FDCAN_FetPack_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::unscaled("FET Config", |p| p.fet_config.into()),
+        Field::volts("In Volt", |p| p.input_volt.into()),
+        Field::volts("Cap Volt", |p| p.cap_volt.into()),
+        Field::amps("Cap Curr", |p| p.cap_curr.into()),
+        Field::amps("Res Curr", |p| p.res_curr.into()),
+        Field::amps("Out Curr", |p| p.out_curr.into()),
+    ];
 }
+impl FDCAN_FetPack_t {
+    /// `fet_config` as a FET state, an error if it isn't one of the known states
+    pub fn fet_state(&self) -> Result<FetState, DecodeError> {
+        u8::try_from(self.fet_config)
+            .ok()
+            .and_then(FetState::from_bits)
+            .ok_or(DecodeError::Other("Invalid FET State"))
+    }
+    /// Whether a FET is on in `fet_config`, even if it isn't one of the known states
+    pub const fn fet_config_has(&self, fet: FetBit) -> bool {
+        self.fet_config <= u8::MAX as u32 && fet.is_set(self.fet_config as u8)
+    }
+    /// Whether each FET is on, in the order of [`FetBit::FETS`]: fuel cell, cap, res, out
+    pub const fn fet_states(&self) -> [bool; 4] {
+        let mut states = [false; 4];
+        let mut i = 0;
+        while i < FetBit::FETS.len() {
+            states[i] = self.fet_config_has(FetBit::FETS[i]);
+            i += 1;
+        }
+        states
+    }
+    /// `input_volt` in volts, sent in mV
+    pub const fn input_volt_volts(&self) -> f32 {
+        self.input_volt as f32 / MILLI
+    }
+    /// `cap_volt` in volts, sent in mV
+    pub const fn cap_volt_volts(&self) -> f32 {
+        self.cap_volt as f32 / MILLI
+    }
+    /// `cap_curr` in amps, sent in mA
+    pub const fn cap_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.cap_curr)
+    }
+    /// `res_curr` in amps, sent in mA
+    pub const fn res_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.res_curr)
+    }
+    /// `out_curr` in amps, sent in mA
+    pub const fn out_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.out_curr)
+    }
+}
+// Every FET is on while running, and a config outside of a byte has no FETs on
+const _: () = {
+    const fn fet(fet_config: u32) -> FDCAN_FetPack_t {
+        FDCAN_FetPack_t {
+            fet_config,
+            input_volt: 0,
+            cap_volt: 0,
+            cap_curr: 0,
+            res_curr: 0,
+            out_curr: 0,
+        }
+    }
+    let run = fet(FetState::FET_RUN as u32);
+    assert!(run.fet_config_has(FetBit::FUELCELL_FET));
+    assert!(run.fet_config_has(FetBit::CAP_FET));
+    assert!(run.fet_config_has(FetBit::RES_FET));
+    assert!(run.fet_config_has(FetBit::OUT_FET));
+    assert!(!run.fet_config_has(FetBit::ALL_FET_OFF));
+    let charge = fet(FetState::FET_CHRGE as u32);
+    assert!(charge.fet_config_has(FetBit::RES_FET));
+    assert!(!charge.fet_config_has(FetBit::OUT_FET));
+    assert!(fet(0).fet_config_has(FetBit::ALL_FET_OFF));
+    assert!(!fet(0x101).fet_config_has(FetBit::FUELCELL_FET));
+
+    // Each state's FETs, fuel cell, cap, res, out
+    const fn states_are(fet_config: u32, expected: [bool; 4]) -> bool {
+        let states = fet(fet_config).fet_states();
+        let mut i = 0;
+        while i < states.len() {
+            if states[i] != expected[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+    assert!(states_are(FetState::FET_STBY as u32, [false; 4]));
+    assert!(states_are(
+        FetState::FET_CHRGE as u32,
+        [true, true, true, false]
+    ));
+    assert!(states_are(FetState::FET_RUN as u32, [true; 4]));
+    assert!(states_are(
+        FetBit::OUT_FET as u32,
+        [false, false, false, true]
+    ));
+    // Out of range configs have every FET off, like fet_config_has
+    assert!(states_are(0x10F, [false; 4]));
+};
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x013)]
 #[repr(C)]
 pub struct ECOCAN_RelPackChrg_t {
     pub fc_coloumbs: i32,
     pub cap_coloumbs: i32,
 }
-impl FDCANPack for ECOCAN_RelPackChrg_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x013;
+impl PackageFields for ECOCAN_RelPackChrg_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::whole("FC Charge", "C", |p| p.fc_coloumbs.into()),
+        Field::whole("Cap Charge", "C", |p| p.cap_coloumbs.into()),
+    ];
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x014)]
 #[repr(C)]
 pub struct FDCAN_RelPackNrg_t {
     pub fc_joules: i32,
     pub cap_joules: i32,
 }
-impl FDCANPack for FDCAN_RelPackNrg_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x014;
+impl PackageFields for FDCAN_RelPackNrg_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::whole("FC Energy", "J", |p| p.fc_joules.into()),
+        Field::whole("Cap Energy", "J", |p| p.cap_joules.into()),
+    ];
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x015)]
 #[repr(C)]
 pub struct FDCAN_RelPackMtr_t {
     pub mtr_volt: u32,
     pub mtr_curr: u32,
 }
-impl FDCANPack for FDCAN_RelPackMtr_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x015;
+impl PackageFields for FDCAN_RelPackMtr_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::volts("Mtr Volt", |p| p.mtr_volt.into()),
+        Field::amps("Mtr Curr", |p| p.mtr_curr.into()),
+    ];
+}
+impl FDCAN_RelPackMtr_t {
+    /// `mtr_volt` in volts, sent in mV
+    pub const fn mtr_volt_volts(&self) -> f32 {
+        self.mtr_volt as f32 / MILLI
+    }
+    /// `mtr_curr` in amps, sent in mA
+    pub const fn mtr_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.mtr_curr)
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x016)]
 #[repr(C)]
 pub struct FDCAN_RelPackCap_t {
     pub cap_volt: u32,
     pub cap_curr: i32,
 }
-impl FDCANPack for FDCAN_RelPackCap_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x016;
+impl PackageFields for FDCAN_RelPackCap_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::volts("Cap Volt", |p| p.cap_volt.into()),
+        Field::amps("Cap Curr", |p| p.cap_curr.into()),
+    ];
+}
+impl FDCAN_RelPackCap_t {
+    /// `cap_volt` in volts, sent in mV
+    pub const fn cap_volt_volts(&self) -> f32 {
+        self.cap_volt as f32 / MILLI
+    }
+    /// `cap_curr` in amps, sent in mA, negative while the capacitors are charging
+    pub const fn cap_curr_amps(&self) -> f32 {
+        signed_current_amps(self.cap_curr)
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x017)]
 #[repr(C)]
 pub struct FDCAN_RelPackFc_t {
     pub fc_volt: u32,
     pub fc_curr: u32,
 }
-impl FDCANPack for FDCAN_RelPackFc_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x017;
+impl PackageFields for FDCAN_RelPackFc_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::volts("FC Volt", |p| p.fc_volt.into()),
+        Field::amps("FC Curr", |p| p.fc_curr.into()),
+    ];
+}
+impl FDCAN_RelPackFc_t {
+    /// `fc_volt` in volts, sent in mV
+    pub const fn fc_volt_volts(&self) -> f32 {
+        self.fc_volt as f32 / MILLI
+    }
+    /// `fc_curr` in amps, sent in mA
+    pub const fn fc_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.fc_curr)
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x020)]
 #[repr(C)]
 pub struct FDCAN_FccPack1_t {
     pub fc_temp: i32,
     pub fc_press: u32,
 }
-impl FDCANPack for FDCAN_FccPack1_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x020;
+impl PackageFields for FDCAN_FccPack1_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::celsius("FC Temp", |p| p.fc_temp.into()),
+        Field::unscaled("FC Press", |p| p.fc_press.into()),
+    ];
+}
+impl FDCAN_FccPack1_t {
+    /// `fc_temp` in degrees Celsius, sent in hundredths of a degree
+    pub const fn fc_temp_celsius(&self) -> f32 {
+        self.fc_temp as f32 / CENTI
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x021)]
 #[repr(C)]
 pub struct FDCAN_FccPack2_t {
     pub fan_rpm1: u32,
     pub fan_rpm2: u32,
 }
-impl FDCANPack for FDCAN_FccPack2_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x021;
+impl PackageFields for FDCAN_FccPack2_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::whole("Fan 1", "rpm", |p| p.fan_rpm1.into()),
+        Field::whole("Fan 2", "rpm", |p| p.fan_rpm2.into()),
+    ];
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x022)]
 #[repr(C)]
 pub struct FDCAN_FccPack3_t {
     pub bme_temp: u32,
     pub bme_humid: u32,
 }
-impl FDCANPack for FDCAN_FccPack3_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x022;
+impl PackageFields for FDCAN_FccPack3_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::celsius("FCC Temp", |p| p.bme_temp.into()),
+        Field::unscaled("FCC Humid", |p| p.bme_humid.into()),
+    ];
+}
+impl FDCAN_FccPack3_t {
+    /// `bme_temp` in degrees Celsius, sent in hundredths of a degree
+    pub const fn bme_temp_celsius(&self) -> f32 {
+        self.bme_temp as f32 / CENTI
+    }
 }
 
 // Reserved IDs up to 0x03F
@@ -279,7 +917,10 @@ impl FDCANPack for FDCAN_FccPack3_t {
 // Mask: 0x7F0
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x030)]
 #[repr(C)]
 pub struct ECOCAN_H2Pack1_t {
     pub h2_sense_1: u16,
@@ -287,13 +928,20 @@ pub struct ECOCAN_H2Pack1_t {
     pub h2_sense_3: u16,
     pub h2_sense_4: u16,
 }
-impl FDCANPack for ECOCAN_H2Pack1_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x030;
+impl PackageFields for ECOCAN_H2Pack1_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::unscaled("H2 1", |p| p.h2_sense_1.into()),
+        Field::unscaled("H2 2", |p| p.h2_sense_2.into()),
+        Field::unscaled("H2 3", |p| p.h2_sense_3.into()),
+        Field::unscaled("H2 4", |p| p.h2_sense_4.into()),
+    ];
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x031)]
 #[repr(C)]
 pub struct ECOCAN_H2Pack2_t {
     pub bme_temp: u16,
@@ -301,66 +949,273 @@ pub struct ECOCAN_H2Pack2_t {
     pub imon_7v: u16,
     pub imon_12v: u16,
 }
-impl FDCANPack for ECOCAN_H2Pack2_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x031;
+impl PackageFields for ECOCAN_H2Pack2_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::celsius("H2 Temp", |p| p.bme_temp.into()),
+        Field::unscaled("H2 Humid", |p| p.bme_humid.into()),
+        Field::unscaled("7V Mon", |p| p.imon_7v.into()),
+        Field::unscaled("12V Mon", |p| p.imon_12v.into()),
+    ];
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x032)]
 #[repr(C)]
 pub struct ECOCAN_H2_ARM_ALARM_t {
     pub h2_alarm_armed: u8,
 }
-impl FDCANPack for ECOCAN_H2_ARM_ALARM_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_1;
-    const FDCAN_ID: u32 = 0x032;
-}
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x040)]
 #[repr(C)]
 pub struct FDCAN_BOOSTPack1_t {
     pub in_curr: u32,
     pub in_volt: u32,
 }
-impl FDCANPack for FDCAN_BOOSTPack1_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x040;
+impl PackageFields for FDCAN_BOOSTPack1_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::amps("In Curr", |p| p.in_curr.into()),
+        Field::volts("In Volt", |p| p.in_volt.into()),
+    ];
+}
+impl FDCAN_BOOSTPack1_t {
+    /// `in_curr` in amps, sent in mA
+    pub const fn in_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.in_curr)
+    }
+    /// `in_volt` in volts, sent in mV
+    pub const fn in_volt_volts(&self) -> f32 {
+        self.in_volt as f32 / MILLI
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x041)]
 #[repr(C)]
 pub struct FDCAN_BOOSTPack2_t {
     pub out_curr: u32,
     pub out_volt: u32,
 }
-impl FDCANPack for FDCAN_BOOSTPack2_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x041;
+impl PackageFields for FDCAN_BOOSTPack2_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::amps("Out Curr", |p| p.out_curr.into()),
+        Field::volts("Out Volt", |p| p.out_volt.into()),
+    ];
+}
+impl FDCAN_BOOSTPack2_t {
+    /// `out_curr` in amps, sent in mA
+    pub const fn out_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.out_curr)
+    }
+    /// `out_volt` in volts, sent in mV
+    pub const fn out_volt_volts(&self) -> f32 {
+        self.out_volt as f32 / MILLI
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x042)]
 #[repr(C)]
 pub struct FDCAN_BOOSTPack3_t {
     pub efficiency: u32,
     pub joules: u32,
 }
-impl FDCANPack for FDCAN_BOOSTPack3_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_8;
-    const FDCAN_ID: u32 = 0x042;
+impl PackageFields for FDCAN_BOOSTPack3_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::percent("Efficiency", |p| p.efficiency.into()),
+        Field::whole("Energy", "J", |p| p.joules.into()),
+    ];
+}
+impl FDCAN_BOOSTPack3_t {
+    /// `efficiency` in percent, sent in 0.01 %
+    ///
+    /// The boost board doesn't document the scale. Hundredths are assumed, like temperatures,
+    /// since whole percents would be too coarse to compare runs and a converter reads from
+    /// about 80 % to 98 %, so a raw value of 9 512 is 95.12 %.
+    ///
+    /// Returned as whole percent and tenths, rounded like the other display values, see
+    /// [`centi_to_units`].
+    pub const fn efficiency_percent(&self) -> (u16, u16) {
+        centi_to_units(self.efficiency)
+    }
 }
 
 #[allow(non_camel_case_types)]
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x050)]
 #[repr(C)]
 pub struct FDCAN_BATTPack2_t {
     pub out_curr: u16,
     pub out_volt: u16,
 }
-impl FDCANPack for FDCAN_BATTPack2_t {
-    const FDCAN_BYTES: FDCANLength = FDCANLength::BYTES_4;
-    const FDCAN_ID: u32 = 0x050;
+impl PackageFields for FDCAN_BATTPack2_t {
+    const FIELDS: &'static [Field<Self>] = &[
+        Field::amps("Batt Curr", |p| p.out_curr.into()),
+        Field::volts("Batt Volt", |p| p.out_volt.into()),
+    ];
 }
+impl FDCAN_BATTPack2_t {
+    /// `out_curr` in amps, sent in mA
+    pub const fn out_curr_amps(&self) -> f32 {
+        unsigned_current_amps(self.out_curr as u32)
+    }
+    /// `out_volt` in volts, sent in mV
+    pub const fn out_volt_volts(&self) -> f32 {
+        self.out_volt as f32 / MILLI
+    }
+}
+
+// Dashboard commands
+// Reserved IDs up to 0x06F
+// 0x060 = 0b00001100000
+// 0x06F = 0b00001101111
+
+/// Asks the relay board to switch to `target_state`, a [`RelayState`]
+#[allow(non_camel_case_types)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x060)]
+#[repr(C)]
+pub struct DASH_RelayCmd_t {
+    pub target_state: u8,
+}
+impl DASH_RelayCmd_t {
+    pub const fn new(target: RelayState) -> Self {
+        Self {
+            target_state: target as u8,
+        }
+    }
+
+    /// The requested state, an error if `target_state` isn't a valid [`RelayState`]
+    pub fn target(&self) -> Result<RelayState, DecodeError> {
+        RelayState::try_from(self.target_state)
+    }
+}
+
+/// Turn signal state, which LEDs blink amber
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum IndicatorState {
+    #[default]
+    Off = 0,
+    Left = 1,
+    Right = 2,
+    /// Both sides
+    Hazard = 3,
+}
+impl TryFrom<u8> for IndicatorState {
+    type Error = DecodeError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(IndicatorState::Off),
+            1 => Ok(IndicatorState::Left),
+            2 => Ok(IndicatorState::Right),
+            3 => Ok(IndicatorState::Hazard),
+            _ => Err(DecodeError::Other("Invalid Indicator State")),
+        }
+    }
+}
+
+/// Sets the dashboard's turn signals to `state`, an [`IndicatorState`]
+#[allow(non_camel_case_types)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x061)]
+#[repr(C)]
+pub struct DASH_IndicatorCmd_t {
+    pub state: u8,
+}
+impl PackageFields for DASH_IndicatorCmd_t {
+    const FIELDS: &'static [Field<Self>] = &[Field::unscaled("Indicator", |p| p.state.into())];
+}
+impl DASH_IndicatorCmd_t {
+    pub const fn new(state: IndicatorState) -> Self {
+        Self { state: state as u8 }
+    }
+
+    /// The requested state, an error if `state` isn't a valid [`IndicatorState`]
+    pub fn indicator(&self) -> Result<IndicatorState, DecodeError> {
+        IndicatorState::try_from(self.state)
+    }
+}
+
+/// Sent by the dashboard every second, so other boards can tell it is still on the bus
+#[allow(non_camel_case_types)]
+#[derive(
+    bincode::Encode, bincode::Decode, PartialEq, Clone, Debug, Format, Default, FdcanPackage,
+)]
+#[fdcan(id = 0x062)]
+#[repr(C)]
+pub struct DASH_Heartbeat_t {
+    /// Seconds since the dashboard booted
+    pub uptime_s: u32,
+    /// Incremented with every heartbeat, wrapping from 65535 back to 0
+    pub counter: u16,
+    /// Firmware version, the major version in the high nibble and the minor in the low
+    pub fw_version: u8,
+    /// Reserved, sent as 0
+    pub reserved: u8,
+}
+
+// Check a few known conversions
+const _: () = {
+    let fc = FDCAN_RelPackFc_t {
+        fc_volt: 12_000,
+        fc_curr: 1_500,
+    };
+    core::assert!(fc.fc_volt_volts() == 12.0);
+    core::assert!(fc.fc_curr_amps() == 1.5);
+
+    let cap = FDCAN_RelPackCap_t {
+        cap_volt: 0,
+        cap_curr: -2_250,
+    };
+    core::assert!(cap.cap_curr_amps() == -2.25);
+
+    // Signed and unsigned currents mix without wrapping, and sums saturate
+    core::assert!(unsigned_current_ma(u32::MAX) == 4_294_967_295);
+    core::assert!(
+        saturating_sum_ma(&[unsigned_current_ma(1_500), signed_current_ma(-2_250)]) == -750
+    );
+    core::assert!(saturating_sum_ma(&[i64::MAX, 1]) == i64::MAX);
+    core::assert!(saturating_sum_ma(&[i64::MIN, -1]) == i64::MIN);
+    core::assert!(saturating_sum_ma(&[]) == 0);
+
+    let fcc = FDCAN_FccPack1_t {
+        fc_temp: -525,
+        fc_press: 0,
+    };
+    core::assert!(fcc.fc_temp_celsius() == -5.25);
+
+    // Efficiency in hundredths of a percent
+    const fn efficiency(raw: u32) -> (u16, u16) {
+        FDCAN_BOOSTPack3_t {
+            efficiency: raw,
+            joules: 0,
+        }
+        .efficiency_percent()
+    }
+    core::assert!(matches!(efficiency(9_550), (95, 5)));
+    core::assert!(matches!(efficiency(9_512), (95, 1)));
+    core::assert!(matches!(efficiency(10_000), (100, 0)));
+    // Rounded to the nearest tenth
+    core::assert!(matches!(efficiency(25), (0, 3)));
+    core::assert!(matches!(efficiency(8_996), (90, 0)));
+    core::assert!(matches!(efficiency(0), (0, 0)));
+};
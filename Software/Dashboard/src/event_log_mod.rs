@@ -0,0 +1,95 @@
+//! Module for the on-screen event log
+//!
+//! The `defmt` log is lost without a debugger attached, so errors worth seeing in the car are
+//! also pushed into a ring of the last [`EVENT_LOG_LEN`] events with [`log_event`]. The
+//! diagnostics page shows them, newest first, with the uptime they happened at.
+//!
+//! Events are plain text, since `defmt` format strings can't be rendered on the device. Keep
+//! them short, text past [`EVENT_CHARS`] is cut off.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex as BlockingMutex, raw::ThreadModeRawMutex};
+use embassy_time::Instant;
+use heapless::{Deque, String};
+
+/// Events kept, the oldest is dropped to make room
+pub const EVENT_LOG_LEN: usize = 8;
+/// Longest event text
+pub const EVENT_CHARS: usize = 32;
+
+/// A logged event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// Uptime when the event was logged, in seconds
+    pub secs: u32,
+    pub text: String<EVENT_CHARS>,
+}
+
+/// The most recent events
+#[derive(Clone)]
+pub struct EventLog {
+    events: Deque<Event, EVENT_LOG_LEN>,
+    /// Counts every event logged, so a reader can tell the log has changed
+    generation: u32,
+}
+
+impl EventLog {
+    pub const fn new() -> Self {
+        Self {
+            events: Deque::new(),
+            generation: 0,
+        }
+    }
+
+    /// Adds an event, dropping the oldest if the log is full
+    pub fn push(&mut self, secs: u32, text: &str) {
+        let mut event = Event {
+            secs,
+            text: String::new(),
+        };
+        for c in text.chars() {
+            if event.text.push(c).is_err() {
+                break;
+            }
+        }
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+        let _ = self.events.push_back(event);
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// The events, newest first
+    pub fn recent(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter().rev()
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The on-screen event log
+///
+/// A blocking mutex, so events can be logged from code that isn't async. Don't hold it across
+/// an await.
+pub static EVENT_LOG: BlockingMutex<ThreadModeRawMutex, RefCell<EventLog>> =
+    BlockingMutex::new(RefCell::new(EventLog::new()));
+
+/// Adds an event to the on-screen log
+pub fn log_event(text: &str) {
+    let secs = Instant::now().as_secs() as u32;
+    EVENT_LOG.lock(|log| log.borrow_mut().push(secs, text));
+}
+
+/// A copy of the log, so it can be drawn without holding the lock
+pub fn event_log() -> EventLog {
+    EVENT_LOG.lock(|log| log.borrow().clone())
+}
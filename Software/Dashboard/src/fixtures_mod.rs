@@ -0,0 +1,130 @@
+//! Module for the sample packages shared by the package checks
+//!
+//! One of each package, used by the encoding checks in debug builds, see
+//! [`check_package_encoding`](crate::can_mod::check_package_encoding), and by the CAN self-test.
+//! A new package only has to be added here to be covered by all of them.
+//!
+//! The packages are all different types, so [`package_fixtures!`] hands the table to a macro
+//! rather than returning it:
+//!
+//! ```rust,ignore
+//! macro_rules! round_trip_all {
+//!     ($($package:expr $(=> [$($byte:expr),* $(,)?])?),* $(,)?) => {
+//!         $(check_round_trip($package);)*
+//!     };
+//! }
+//! package_fixtures!(round_trip_all);
+//! ```
+//!
+//! Every field is non-zero and differs from its neighbours, so a swapped or dropped field
+//! doesn't decode to the same package. A package followed by `=> [bytes]` is also checked
+//! against the exact bytes the other boards send it as.
+
+/// Calls `$callback!` with one sample of each package, see [`fixtures_mod`](crate::fixtures_mod)
+macro_rules! package_fixtures {
+    ($callback:ident) => {
+        $callback! {
+            // Running with every FET on, 24 V in, 20.5 V and 1.5 A on the caps, 0.25 A through
+            // the resistor and 3 A out
+            $crate::eco_can::FDCAN_FetPack_t {
+                fet_config: $crate::eco_can::FetState::FET_RUN as u32,
+                input_volt: 24_000,
+                cap_volt: 20_500,
+                cap_curr: 1_500,
+                res_curr: 250,
+                out_curr: 3_000,
+            } => [
+                0x00, 0x00, 0x00, 0x0F, // fet_config
+                0x00, 0x00, 0x5D, 0xC0, // input_volt
+                0x00, 0x00, 0x50, 0x14, // cap_volt
+                0x00, 0x00, 0x05, 0xDC, // cap_curr
+                0x00, 0x00, 0x00, 0xFA, // res_curr
+                0x00, 0x00, 0x0B, 0xB8, // out_curr
+            ],
+            // 1000 C from the fuel cell, and the caps 2 C down, signed fields are two's
+            // complement
+            $crate::eco_can::ECOCAN_RelPackChrg_t {
+                fc_coloumbs: 1_000,
+                cap_coloumbs: -2,
+            } => [
+                0x00, 0x00, 0x03, 0xE8, // fc_coloumbs
+                0xFF, 0xFF, 0xFF, 0xFE, // cap_coloumbs
+            ],
+            // 36 kJ from the fuel cell, and 1.5 kJ back into the caps
+            $crate::eco_can::FDCAN_RelPackNrg_t {
+                fc_joules: 36_000,
+                cap_joules: -1_500,
+            } => [
+                0x00, 0x00, 0x8C, 0xA0, // fc_joules
+                0xFF, 0xFF, 0xFA, 0x24, // cap_joules
+            ],
+            $crate::eco_can::FDCAN_RelPackMtr_t {
+                mtr_volt: 38_000,
+                mtr_curr: 20_000,
+            },
+            $crate::eco_can::FDCAN_RelPackCap_t {
+                cap_volt: 40_000,
+                cap_curr: -3_000,
+            },
+            // Big-endian, so the first field's most significant byte comes first
+            $crate::eco_can::FDCAN_RelPackFc_t {
+                fc_volt: 0x0102_0304,
+                fc_curr: 0x0506_0708,
+            } => [1, 2, 3, 4, 5, 6, 7, 8],
+            // -5.25 °C, and a pressure reading whose bytes are all distinct
+            $crate::eco_can::FDCAN_FccPack1_t {
+                fc_temp: -525,
+                fc_press: 101_325,
+            } => [
+                0xFF, 0xFF, 0xFD, 0xF3, // fc_temp
+                0x00, 0x01, 0x8B, 0xCD, // fc_press
+            ],
+            $crate::eco_can::FDCAN_FccPack2_t {
+                fan_rpm1: 4_200,
+                fan_rpm2: 3_900,
+            },
+            $crate::eco_can::FDCAN_FccPack3_t {
+                bme_temp: 2_345,
+                bme_humid: 4_567,
+            },
+            $crate::eco_can::ECOCAN_H2Pack1_t {
+                h2_sense_1: 1,
+                h2_sense_2: 2,
+                h2_sense_3: 3,
+                h2_sense_4: 4,
+            },
+            $crate::eco_can::ECOCAN_H2Pack2_t {
+                bme_temp: 2_100,
+                bme_humid: 3_300,
+                imon_7v: 700,
+                imon_12v: 1_200,
+            },
+            $crate::eco_can::ECOCAN_H2_ARM_ALARM_t { h2_alarm_armed: 1 },
+            $crate::eco_can::DASH_RelayCmd_t::new($crate::eco_can::RelayState::RELAY_RUN),
+            $crate::eco_can::DASH_IndicatorCmd_t::new($crate::eco_can::IndicatorState::Hazard),
+            $crate::eco_can::DASH_Heartbeat_t {
+                uptime_s: 0x0102_0304,
+                counter: u16::MAX,
+                fw_version: $crate::can_mod::FIRMWARE_VERSION,
+                reserved: 0,
+            },
+            $crate::eco_can::FDCAN_BOOSTPack1_t {
+                in_curr: 5_000,
+                in_volt: 36_000,
+            },
+            $crate::eco_can::FDCAN_BOOSTPack2_t {
+                out_curr: 4_000,
+                out_volt: 42_000,
+            },
+            $crate::eco_can::FDCAN_BOOSTPack3_t {
+                efficiency: 9_150,
+                joules: 12_345,
+            },
+            $crate::eco_can::FDCAN_BATTPack2_t {
+                out_curr: 800,
+                out_volt: 12_600,
+            },
+        }
+    };
+}
+pub(crate) use package_fixtures;
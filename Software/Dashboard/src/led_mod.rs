@@ -3,105 +3,505 @@
 //! Responsible for handling the WS2812B LED lights on the dashboard.
 //!
 //! WS2812B Datasheet: [https://cdn-shop.adafruit.com/datasheets/WS2812B.pdf](https://cdn-shop.adafruit.com/datasheets/WS2812B.pdf)
+//!
+//! The bit timing is derived from [`LED_KIND`] and [`LED_TIMER_HZ`], so fitting an SK6812 strip
+//! or changing the clock only needs those constants changing.
 
 // use defmt::info;
-use defmt::trace;
+use core::sync::atomic::{AtomicU8, Ordering::Relaxed};
+
+use defmt::Format;
 use embassy_stm32::Peri;
 use embassy_stm32::peripherals::{DMA2_CH1, TIM2};
 use embassy_stm32::timer::simple_pwm::SimplePwm;
-use embassy_time::Timer;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant, Timer};
 use rgb_led_pwm_dma_maker::{LedDataComposition, LedDmaBuffer, RGB, calc_dma_buffer_length};
 
-use crate::can_mod::RELAY_STATE;
-use crate::eco_can::RelayState;
+use crate::can_mod::{RELAY_STATE, SYNC_LED};
+use crate::display_mod::display_failed;
+use crate::eco_can::{IndicatorState, RelayState};
+use crate::log_mod::{info, trace, warn};
+use crate::safe_state_mod::safe_state;
+use crate::warning_mod::{FC_VOLTAGE_LOW, ThermalLevel, thermal_level};
+use crate::watchdog_mod::{CriticalTask, check_in};
+
+/// Number of LEDs on the PCB, change this for a board revision with a different count
+///
+/// Everything else, including the DMA buffer size, follows from it at compile time.
+pub const LED_COUNT: usize = 5;
+
+/// Length of the DMA buffer for `led_count` RGB888 LEDs and the reset time
+pub const fn led_dma_buffer_len(led_count: usize) -> usize {
+    calc_dma_buffer_length(8 * 3, led_count, LED_TIMING.reset_length)
+}
+
+/// Brightness of all LEDs except the safe state flash, 0 (off) to 255 (full)
+///
+/// The default dims full colors to the same level as the LEDs have always been run at.
+pub static LED_BRIGHTNESS: AtomicU8 = AtomicU8::new(DEFAULT_LED_BRIGHTNESS);
+pub const DEFAULT_LED_BRIGHTNESS: u8 = 51;
+
+/// The turn signals, set with [`set_indicator`]
+pub static INDICATOR: Mutex<ThreadModeRawMutex, IndicatorState> = Mutex::new(IndicatorState::Off);
+/// Period of the turn signal blink, about 1.5 Hz
+pub const INDICATOR_BLINK_PERIOD: Duration = Duration::from_millis(667);
+/// LEDs at each end of the strip that blink for that side's turn signal
+pub const INDICATOR_LEDS: usize = 2;
+
+/// Switches the turn signals, takes effect on the next LED frame
+///
+/// The blink restarts on, and turning the signals off stops them straight away, mid blink.
+pub async fn set_indicator(state: IndicatorState) {
+    let mut indicator = INDICATOR.lock().await;
+    if *indicator != state {
+        info!("Indicator {}", state);
+        *indicator = state;
+    }
+}
+
+/// The LED part fitted to the dashboard
+pub const LED_KIND: LedKind = LedKind::Ws2812b;
+/// Frequency TIM2 is clocked at, the 170 MHz system clock with no APB1 prescaler
+pub const LED_TIMER_HZ: u32 = 170_000_000;
+/// The PWM frequency and duty cycles for [`LED_KIND`] at [`LED_TIMER_HZ`]
+pub const LED_TIMING: LedTiming = LedTiming::from_timer_hz(LED_TIMER_HZ, LED_KIND);
 
-// There are 5 LED's on the PCB
-const LED_COUNT: usize = 5;
+/// A supported addressable LED part, and its datasheet bit timing
+///
+/// Each bit is one PWM period, high for T1H to send a 1 or T0H to send a 0. The line is then
+/// held low for the reset time to latch the colors.
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum LedKind {
+    /// [WS2812B datasheet](https://cdn-shop.adafruit.com/datasheets/WS2812B.pdf)
+    Ws2812b,
+    /// SK6812, the RGB version, RGBW parts need 32 bits per LED
+    Sk6812,
+}
+
+impl LedKind {
+    /// Length of one bit, in ns
+    pub const fn bit_ns(self) -> u32 {
+        match self {
+            LedKind::Ws2812b => 1_250,
+            LedKind::Sk6812 => 1_200,
+        }
+    }
+
+    /// High time of a 1 bit, in ns
+    pub const fn t1h_ns(self) -> u32 {
+        match self {
+            LedKind::Ws2812b => 800,
+            LedKind::Sk6812 => 600,
+        }
+    }
+
+    /// High time of a 0 bit, in ns
+    pub const fn t0h_ns(self) -> u32 {
+        match self {
+            LedKind::Ws2812b => 400,
+            LedKind::Sk6812 => 300,
+        }
+    }
+
+    /// Minimum low time that latches the colors, in ns
+    pub const fn reset_ns(self) -> u32 {
+        match self {
+            LedKind::Ws2812b => 50_000,
+            LedKind::Sk6812 => 80_000,
+        }
+    }
+}
+
+/// PWM settings that produce an LED part's bit timing
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub struct LedTiming {
+    /// PWM frequency, one period per bit
+    pub pwm_hz: u32,
+    /// Timer ticks in one period, the duty cycle of a permanently high output
+    pub max_duty: u16,
+    /// Duty cycle of a 1 bit
+    pub t1h: u16,
+    /// Duty cycle of a 0 bit
+    pub t0h: u16,
+    /// Number of low periods sent after the colors to latch them
+    pub reset_length: usize,
+}
+
+impl LedTiming {
+    /// Derives the PWM settings for `kind` from the timer's clock
+    ///
+    /// - `pwm_hz = 1 / bit`, e.g. 1 / 1.25 us = 800 kHz for the WS2812B
+    /// - `max_duty = timer_hz / pwm_hz`, the timer counts `0..max_duty` with no prescaler,
+    ///   which is what `SimplePwm` sets up on the 32 bit TIM2
+    /// - `t1h = T1H / bit * max_duty`, e.g. 0.8 us / 1.25 us * 212 = 136 at 170 MHz
+    /// - `t0h = T0H / bit * max_duty`, e.g. 0.4 us / 1.25 us * 212 = 68 at 170 MHz
+    /// - `reset_length = reset / bit`, rounded up, e.g. 50 us / 1.25 us = 40
+    ///
+    /// Duty cycles are rounded to the nearest tick. Fails the build if used in a const and
+    /// the timer is too slow to tell a 0 from a 1.
+    pub const fn from_timer_hz(timer_hz: u32, kind: LedKind) -> Self {
+        let bit_ns = kind.bit_ns() as u64;
+        let pwm_hz = (1_000_000_000 / bit_ns) as u32;
+        let max_duty = timer_hz / pwm_hz;
+        assert!(max_duty <= u16::MAX as u32, "LED timer is too fast");
+        let t1h = duty_cycle(kind.t1h_ns(), max_duty, kind.bit_ns());
+        let t0h = duty_cycle(kind.t0h_ns(), max_duty, kind.bit_ns());
+        assert!(0 < t0h && t0h < t1h, "LED timer is too slow");
+        Self {
+            pwm_hz,
+            max_duty: max_duty as u16,
+            t1h,
+            t0h,
+            reset_length: kind.reset_ns().div_ceil(kind.bit_ns()) as usize,
+        }
+    }
+}
+
+/// Duty cycle that holds the output high for `high_ns` of a `bit_ns` period, to the nearest tick
+const fn duty_cycle(high_ns: u32, max_duty: u32, bit_ns: u32) -> u16 {
+    ((high_ns as u64 * max_duty as u64 + bit_ns as u64 / 2) / bit_ns as u64) as u16
+}
+
+// The timing that was previously hand calculated for the WS2812B at 170 MHz
+const _: () = {
+    let timing = LedTiming::from_timer_hz(170_000_000, LedKind::Ws2812b);
+    assert!(timing.pwm_hz == 800_000);
+    assert!(timing.max_duty == 212);
+    assert!(timing.t1h == 136 && timing.t0h == 68);
+    assert!(timing.reset_length == 40);
+};
 
 /// Updates the LED lights on the dashboard
 #[embassy_executor::task]
-pub async fn led_task(mut led_in: SimplePwm<'static, TIM2>, mut led_dma: Peri<'static, DMA2_CH1>) {
-    // RESET_LENGTH = reset_period / data_transfer_time = 50us / 1.25us = 40
-    const RESET_LENGTH: usize = 40;
-    // Calculate the dma buffer's length at compile time
-    // Uses RGB888 formatting
-    const DMA_BUFFER_LEN: usize = calc_dma_buffer_length(8 * 3, LED_COUNT, RESET_LENGTH);
-    // t1h = T1H / data_transfer_time * max_duty_cycle = 0.8us / 1.25us * 200 =
-    let t1h: u16 = 128;
-    // t1h = T0H / data_transfer_time * max_duty_cycle = 0.4us / 1.25us * 200 =
-    let t0h: u16 = 64;
-
-    let mut dma_buffer = LedDmaBuffer::<DMA_BUFFER_LEN>::new(t1h, t0h, LedDataComposition::GRB);
-    let mut led_array: [RGB; LED_COUNT];
-    let mut index = 0;
+pub async fn led_task(led_in: SimplePwm<'static, TIM2>, led_dma: Peri<'static, DMA2_CH1>) {
+    run_leds::<LED_COUNT, { led_dma_buffer_len(LED_COUNT) }>(led_in, led_dma).await
+}
+
+/// Drives `N` LEDs, `BUFFER_LEN` must be [`led_dma_buffer_len`] of `N`
+///
+/// Embassy tasks can't be generic, so [`led_task`] runs this with [`LED_COUNT`].
+async fn run_leds<const N: usize, const BUFFER_LEN: usize>(
+    mut led_in: SimplePwm<'static, TIM2>,
+    mut led_dma: Peri<'static, DMA2_CH1>,
+) -> ! {
+    const { assert!(BUFFER_LEN == led_dma_buffer_len(N)) };
+    if led_in.max_duty_cycle() != LED_TIMING.max_duty {
+        warn!(
+            "LED timer max duty is {}, expected {}, is LED_TIMER_HZ right?",
+            led_in.max_duty_cycle(),
+            LED_TIMING.max_duty
+        );
+    }
+
+    let mut dma_buffer =
+        LedDmaBuffer::<BUFFER_LEN>::new(LED_TIMING.t1h, LED_TIMING.t0h, LedDataComposition::GRB);
+    let mut led_array: [RGB; N];
+    let mut alarm_flash = false;
+    // The animation restarts whenever it changes
+    let mut prev_animation = None;
+    let mut animation_start = Instant::now();
+    let mut prev_indicator = IndicatorState::Off;
+    let mut indicator_start = Instant::now();
 
     loop {
+        check_in(CriticalTask::Led);
         let relay_state_lock = RELAY_STATE.lock().await;
         let relay_state = relay_state_lock.clone();
         drop(relay_state_lock);
 
-        // Inialized display screen if switching relay state
-        match relay_state {
-            RelayState::RELAY_STRTP => {
-                led_array = led_startup();
-                let _ = dma_buffer.set_dma_buffer(&led_array, None);
+        // Flash all LEDs red while in the safe state
+        if safe_state().await.is_some() {
+            alarm_flash = !alarm_flash;
+            // The safe state is always at full brightness
+            let color = if alarm_flash { RED } else { OFF };
+            led_array = [apply_gamma(color, u8::MAX); N];
+            let _ = dma_buffer.set_dma_buffer(&led_array, None);
+            led_in
+                .waveform::<embassy_stm32::timer::Ch1>(
+                    led_dma.reborrow(),
+                    dma_buffer.get_dma_buffer(),
+                )
+                .await;
+            Timer::after_millis(ALARM_FLASH_MS).await;
+            continue;
+        }
+
+        // Warnings take over from the relay state, the most severe first
+        let thermal = thermal_level();
+        let animation = if thermal == ThermalLevel::Critical {
+            LedAnimation::Blink {
+                colors: [RED; N],
+                period: OVERTEMP_BLINK_PERIOD,
+            }
+        } else if FC_VOLTAGE_LOW.load(Relaxed) {
+            LedAnimation::Breathe {
+                color: RED,
+                period: FC_LOW_PULSE_PERIOD,
             }
-            RelayState::RELAY_CHRGE => {
-                led_array = led_charging();
-                let _ = dma_buffer.set_dma_buffer(&led_array, Some(index % LED_COUNT as i32));
+        } else if thermal == ThermalLevel::Warning {
+            LedAnimation::Breathe {
+                color: ORANGE,
+                period: HOT_PULSE_PERIOD,
             }
-            RelayState::RELAY_STBY => {
-                led_array = led_standby();
-                let _ = dma_buffer.set_dma_buffer(&led_array, None);
+        } else if display_failed() {
+            // Without the display, the LEDs still show the relay state between the fades
+            LedAnimation::Fade {
+                from: state_to_colors(&relay_state),
+                to: [PURPLE; N],
+                period: DISPLAY_FAILED_FADE_PERIOD,
             }
-            RelayState::RELAY_RUN => {
-                led_array = led_running();
-                let _ = dma_buffer.set_dma_buffer(&led_array, Some(index % LED_COUNT as i32));
+        } else if *SYNC_LED.lock().await {
+            // The blink starts when the broadcast arrives, so every board blinks in unison
+            LedAnimation::Blink {
+                colors: state_to_colors(&relay_state),
+                period: SYNC_BLINK_PERIOD,
             }
+        } else {
+            state_to_animation(&relay_state)
+        };
+        if prev_animation != Some(animation) {
+            animation_start = Instant::now();
+            prev_animation = Some(animation);
         }
-        index = index.wrapping_add_unsigned(1);
+
+        // Set the colors for the current frame of the animation
+        let brightness = LED_BRIGHTNESS.load(Relaxed);
+        let elapsed = animation_start.elapsed();
+        let colors: [Color; N] = animation.frame(elapsed);
+
+        // Turn signals override the animation on their LEDs, at full brightness
+        let indicator = *INDICATOR.lock().await;
+        if indicator != prev_indicator {
+            indicator_start = Instant::now();
+            prev_indicator = indicator;
+        }
+        let indicator_mask = indicator_leds::<N>(indicator);
+        let blink = LedAnimation::Blink {
+            colors: [AMBER; 1],
+            period: INDICATOR_BLINK_PERIOD,
+        }
+        .frame(indicator_start.elapsed())[0];
+        led_array = core::array::from_fn(|i| {
+            if indicator_mask[i] {
+                apply_gamma(blink, u8::MAX)
+            } else {
+                apply_gamma(colors[i], brightness)
+            }
+        });
+        let _ = dma_buffer.set_dma_buffer(&led_array, None);
         // Output pwm waveform to set LED colors
         led_in
             .waveform::<embassy_stm32::timer::Ch1>(led_dma.reborrow(), dma_buffer.get_dma_buffer())
             .await;
         trace!("LED Health check");
-        Timer::after_millis(500).await;
+        Timer::after(ANIMATION_TICK).await;
+    }
+}
+
+/// An LED color before gamma correction, see [`apply_gamma`]
+///
+/// [`RGB`] doesn't expose its channels, so colors are kept as this until they are output.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// Amber, for standby
+const AMBER: Color = Color::new(255, 170, 0);
+/// Blue, for charging
+const BLUE: Color = Color::new(0, 0, 255);
+/// Green, for running
+const GREEN: Color = Color::new(0, 255, 0);
+/// Red, for the safe state and critical warnings
+const RED: Color = Color::new(255, 0, 0);
+/// Orange, for the over temperature warning
+const ORANGE: Color = Color::new(255, 60, 0);
+/// Purple, for a display that didn't come up
+const PURPLE: Color = Color::new(160, 0, 255);
+const OFF: Color = Color::new(0, 0, 0);
+/// Time each LED flash is on or off in the safe state
+const ALARM_FLASH_MS: u64 = 250;
+/// Period of the red pulse while the fuel cell voltage is low
+const FC_LOW_PULSE_PERIOD: Duration = Duration::from_secs(1);
+/// Period of the orange pulse while a temperature is over its warning limit
+const HOT_PULSE_PERIOD: Duration = Duration::from_secs(2);
+/// Period of the red blink while a temperature is over its critical limit
+const OVERTEMP_BLINK_PERIOD: Duration = Duration::from_millis(500);
+
+/// Period of the fade to purple while running without the display
+const DISPLAY_FAILED_FADE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Period of the blink while the sync LED broadcast is on
+const SYNC_BLINK_PERIOD: Duration = Duration::from_secs(1);
+
+/// Gamma 2.8 correction table, maps a perceived brightness to the WS2812B's PWM level
+const GAMMA: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
+    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
+    14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25, 25, 26, 27,
+    27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46,
+    47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72,
+    73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104,
+    105, 107, 109, 110, 112, 114, 115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137,
+    138, 140, 142, 144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
+    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
+    223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
+];
+
+/// Time between animation frames
+const ANIMATION_TICK: Duration = Duration::from_millis(20);
+/// Time each LED is held for in a [`LedAnimation::Chase`]
+const CHASE_STEP: Duration = Duration::from_millis(500);
+
+/// An LED animation, which gives the colors for any time since it started
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LedAnimation<const N: usize = LED_COUNT> {
+    /// Fixed colors
+    Solid([Color; N]),
+    /// One color fading out and back in again, once each `period`
+    Breathe { color: Color, period: Duration },
+    /// Crossfade from one palette to another and back again, once each `period`
+    Fade {
+        from: [Color; N],
+        to: [Color; N],
+        period: Duration,
+    },
+    /// The colors moving along the LEDs one step every [`CHASE_STEP`]
+    Chase([Color; N]),
+    /// The colors on for the first half of each `period`, and off for the second half
+    Blink {
+        colors: [Color; N],
+        period: Duration,
+    },
+}
+
+impl<const N: usize> LedAnimation<N> {
+    /// The colors at `elapsed` since the animation started
+    pub fn frame(&self, elapsed: Duration) -> [Color; N] {
+        match *self {
+            LedAnimation::Solid(colors) => colors,
+            LedAnimation::Breathe { color, period } => {
+                [lerp_color(OFF, color, triangle_wave(elapsed, period)); N]
+            }
+            LedAnimation::Fade { from, to, period } => {
+                let t = triangle_wave(elapsed, period);
+                core::array::from_fn(|i| lerp_color(from[i], to[i], t))
+            }
+            LedAnimation::Chase(mut colors) => {
+                let step = elapsed.as_ticks() / CHASE_STEP.as_ticks();
+                colors.rotate_right((step % N.max(1) as u64) as usize);
+                colors
+            }
+            LedAnimation::Blink { colors, period } => {
+                let period = period.as_ticks().max(1);
+                if elapsed.as_ticks() % period < period / 2 {
+                    colors
+                } else {
+                    [OFF; N]
+                }
+            }
+        }
+    }
+}
+
+/// Rises from 0 to 255 over the first half of `period` and falls back over the second half
+fn triangle_wave(elapsed: Duration, period: Duration) -> u8 {
+    let period = period.as_ticks().max(1);
+    // Position in the period, from 0 to 510
+    let phase = (elapsed.as_ticks() % period * 510 / period) as u16;
+    if phase <= 255 {
+        phase as u8
+    } else {
+        (510 - phase) as u8
     }
 }
 
-fn led_startup() -> [RGB; LED_COUNT] {
-    [
-        RGB::new(3, 0, 0),
-        RGB::new(0, 3, 0),
-        RGB::new(0, 0, 3),
-        RGB::new(0, 3, 3),
-        RGB::new(3, 3, 0),
-    ]
-}
-fn led_charging() -> [RGB; LED_COUNT] {
-    [
-        RGB::new(0, 0, 0),
-        RGB::new(0, 3, 0),
-        RGB::new(0, 3, 0),
-        RGB::new(0, 3, 0),
-        RGB::new(0, 3, 0),
-    ]
-}
-fn led_standby() -> [RGB; LED_COUNT] {
-    [
-        RGB::new(3, 0, 0),
-        RGB::new(0, 3, 0),
-        RGB::new(0, 0, 3),
-        RGB::new(0, 3, 3),
-        RGB::new(3, 3, 0),
-    ]
-}
-fn led_running() -> [RGB; LED_COUNT] {
-    [
-        RGB::new(3, 0, 0),
-        RGB::new(0, 3, 0),
-        RGB::new(0, 0, 3),
-        RGB::new(0, 3, 3),
-        RGB::new(3, 3, 0),
-    ]
+/// Linear interpolation from `a` (t = 0) to `b` (t = 255)
+fn lerp(a: u8, b: u8, t: u8) -> u8 {
+    let (a, b, t) = (i32::from(a), i32::from(b), i32::from(t));
+    (a + (b - a) * t / 255) as u8
+}
+
+fn lerp_color(a: Color, b: Color, t: u8) -> Color {
+    Color::new(lerp(a.r, b.r, t), lerp(a.g, b.g, t), lerp(a.b, b.b, t))
+}
+
+/// Scales a color by `brightness` (0 blanks the LED), then gamma corrects it
+///
+/// Scaling first means dimming is perceptually even across the colors.
+pub fn apply_gamma(color: Color, brightness: u8) -> RGB {
+    let channel = |c: u8| GAMMA[(u16::from(c) * u16::from(brightness) / 255) as usize];
+    RGB::new(channel(color.r), channel(color.g), channel(color.b))
+}
+
+/// The LEDs that blink for a turn signal state, left is the start of the strip
+pub fn indicator_leds<const N: usize>(state: IndicatorState) -> [bool; N] {
+    let (left, right) = match state {
+        IndicatorState::Off => (false, false),
+        IndicatorState::Left => (true, false),
+        IndicatorState::Right => (false, true),
+        IndicatorState::Hazard => (true, true),
+    };
+    // With too few LEDs for both sides, the sides share the middle ones
+    core::array::from_fn(|i| (left && i < INDICATOR_LEDS) || (right && i + INDICATOR_LEDS >= N))
+}
+
+/// The LED animation for a relay state
+///
+/// - Startup: one of each color fading into the next, to check every LED works
+/// - Standby: breathing amber
+/// - Charging: blue, with one LED off that moves along
+/// - Running: green, with one LED off that moves along
+pub fn state_to_animation<const N: usize>(state: &RelayState) -> LedAnimation<N> {
+    let colors = state_to_colors(state);
+    match state {
+        RelayState::RELAY_STRTP => {
+            let mut to = colors;
+            to.rotate_right(1);
+            LedAnimation::Fade {
+                from: colors,
+                to,
+                period: Duration::from_secs(2),
+            }
+        }
+        RelayState::RELAY_STBY => LedAnimation::Breathe {
+            color: AMBER,
+            period: Duration::from_secs(4),
+        },
+        RelayState::RELAY_CHRGE | RelayState::RELAY_RUN => LedAnimation::Chase(colors),
+    }
+}
+
+/// Colors shown at startup, repeated along the LEDs if there are more than five
+const STARTUP_COLORS: [Color; 5] = [
+    Color::new(255, 0, 0),
+    Color::new(0, 255, 0),
+    Color::new(0, 0, 255),
+    Color::new(0, 255, 255),
+    Color::new(255, 255, 0),
+];
+
+/// The LED colors for a relay state, before any animation
+///
+/// Charging and running light every LED but the first, so the chase has a gap to move.
+pub fn state_to_colors<const N: usize>(state: &RelayState) -> [Color; N] {
+    let gap_then = |color| core::array::from_fn(|i| if i == 0 { OFF } else { color });
+    match state {
+        RelayState::RELAY_STRTP => {
+            core::array::from_fn(|i| STARTUP_COLORS[i % STARTUP_COLORS.len()])
+        }
+        RelayState::RELAY_STBY => [AMBER; N],
+        RelayState::RELAY_CHRGE => gap_then(BLUE),
+        RelayState::RELAY_RUN => gap_then(GREEN),
+    }
 }
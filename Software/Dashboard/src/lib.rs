@@ -2,9 +2,39 @@
 //! # Sally-Dashboard Documentation
 //! This is the documentation for the dashboard's code. The firmware is composed of three modules.
 
+pub mod adc_mod;
 pub mod btn_mod;
 pub mod can_mod;
+#[cfg(feature = "can-self-test")]
+pub mod can_self_test_mod;
+pub mod can_stats_mod;
+pub mod can_timing_mod;
+pub mod checksum_mod;
+pub mod demo_mod;
 pub mod display_mod;
 pub mod eco_can;
+pub mod event_log_mod;
+#[cfg(any(debug_assertions, feature = "can-self-test"))]
+pub mod fixtures_mod;
 pub mod led_mod;
+pub mod log_mod;
 pub mod mode;
+pub mod node_mod;
+pub mod peak_mod;
+pub mod power_mod;
+pub mod rate_limit_mod;
+pub mod safe_state_mod;
+#[cfg(feature = "sim")]
+pub mod sim_mod;
+pub mod snapshot_mod;
+pub mod source_mod;
+pub mod storage_mod;
+#[cfg(feature = "csv-telemetry")]
+pub mod telemetry_mod;
+pub mod timed_state_mod;
+pub mod timestamp_mod;
+pub mod touch_mod;
+pub mod trip_mod;
+pub mod units_mod;
+pub mod warning_mod;
+pub mod watchdog_mod;
@@ -0,0 +1,131 @@
+//! Module for logging
+//!
+//! Library modules log through these macros instead of importing `defmt` directly. On target
+//! (`target_os = "none"`) they forward to the `defmt` macro of the same name, so the format
+//! strings and output are unchanged.
+//!
+//! On any other target they compile to nothing, since there is no `defmt` logger to link
+//! against. The arguments are still borrowed, so a value that is only logged doesn't raise an
+//! unused variable warning, and logic such as [`classify_frame`](crate::can_mod::classify_frame)
+//! can be built for the host.
+//!
+//! ```ignore
+//! use crate::log_mod::{info, warn};
+//!
+//! info!("Decoded {} packages", count);
+//! ```
+//!
+//! `info` and `verbose` are also gated at runtime by the [`Verbosity`], so the log can be kept
+//! quiet in the field and verbose while debugging. `verbose` is for per-frame logs that would
+//! flood the RTT channel on a loaded bus. Warnings and errors are always logged, and `trace` and
+//! `debug` are only filtered at compile time by `DEFMT_LOG`.
+//!
+//! Tap button 1 while holding button 2 to step through the levels.
+
+use core::sync::atomic::{AtomicU8, Ordering::Relaxed};
+
+use defmt::Format;
+
+/// How much is logged at runtime
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Verbosity {
+    /// Only warnings and errors
+    Quiet = 0,
+    /// Also `info`, the default
+    Normal = 1,
+    /// Also the per-frame `verbose` logs
+    Verbose = 2,
+}
+
+impl Verbosity {
+    /// The next level, wrapping from verbose back to quiet
+    pub const fn next(self) -> Self {
+        match self {
+            Verbosity::Quiet => Verbosity::Normal,
+            Verbosity::Normal => Verbosity::Verbose,
+            Verbosity::Verbose => Verbosity::Quiet,
+        }
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// The current runtime log level
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Sets the runtime log level
+pub fn set_verbosity(level: Verbosity) {
+    VERBOSITY.store(level as u8, Relaxed);
+    // Logged whatever the level, so it's clear why the log went quiet
+    forward!(info, "Log verbosity {}", level);
+}
+
+/// Forwards a log statement to `defmt` at `$level`
+#[cfg(target_os = "none")]
+macro_rules! forward {
+    ($level:ident, $($arg:tt)*) => {
+        ::defmt::$level!($($arg)*)
+    };
+}
+
+/// Discards a log statement, there is no `defmt` logger off target
+#[cfg(not(target_os = "none"))]
+macro_rules! forward {
+    ($level:ident, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        let _ = ($(&$arg,)*);
+    }};
+}
+
+macro_rules! log_trace {
+    ($($arg:tt)*) => {
+        $crate::log_mod::forward!(trace, $($arg)*)
+    };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        $crate::log_mod::forward!(debug, $($arg)*)
+    };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        if $crate::log_mod::verbosity() >= $crate::log_mod::Verbosity::Normal {
+            $crate::log_mod::forward!(info, $($arg)*)
+        }
+    };
+}
+
+/// Logs at info level, only while the [`Verbosity`] is verbose
+macro_rules! log_verbose {
+    ($($arg:tt)*) => {
+        if $crate::log_mod::verbosity() >= $crate::log_mod::Verbosity::Verbose {
+            $crate::log_mod::forward!(info, $($arg)*)
+        }
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::log_mod::forward!(warn, $($arg)*)
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::log_mod::forward!(error, $($arg)*)
+    };
+}
+
+// Defined under another name, `warn` would be ambiguous with the builtin lint attribute
+pub(crate) use {
+    forward, log_debug as debug, log_error as error, log_info as info, log_trace as trace,
+    log_verbose as verbose, log_warn as warn,
+};
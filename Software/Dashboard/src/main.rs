@@ -1,24 +1,37 @@
 #![no_std]
 #![no_main]
-use dashboard::btn_mod::{btn1_task, btn2_task};
-use dashboard::can_mod::{can_receive_task, can_transmit_task};
-use dashboard::display_mod::display_task;
-use dashboard::led_mod::led_task;
+use dashboard::adc_mod::adc_task;
+use dashboard::btn_mod::{BUTTON1_BOUNCE_MS, BUTTON2_BOUNCE_MS, btn1_task, btn2_task};
+use dashboard::can_mod::{can_receive_task, can_transmit_task, start_can};
+#[cfg(feature = "can-self-test")]
+use dashboard::can_self_test_mod::can_self_test;
+use dashboard::demo_mod::demo_task;
+use dashboard::display_mod::{
+    BACKLIGHT_PWM_FREQ, LCD_SPI_FREQ, display_task, init_backlight, init_display_with_retry,
+    share_display, share_spi_bus,
+};
+use dashboard::led_mod::{LED_TIMING, led_task};
+#[cfg(feature = "can-self-test")]
+use dashboard::mode::boot::draw_boot_failure;
+use dashboard::mode::boot::{BootStep, draw_boot_line, draw_boot_splash};
+use dashboard::node_mod::node_supervisor_task;
+use dashboard::storage_mod::storage_task;
+use dashboard::touch_mod::{TOUCH_SPI_FREQ, touch_task};
+use dashboard::watchdog_mod::{WATCHDOG_TIMEOUT_US, watchdog_task};
 use defmt::*;
+use embassy_embedded_hal::shared_bus::blocking::spi::SpiDeviceWithConfig;
 use embassy_executor::Spawner;
+use embassy_stm32::adc::Adc;
 use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::flash::Flash;
 use embassy_stm32::gpio::{Level, Output, OutputType, Pull, Speed};
 use embassy_stm32::spi::{self, Spi};
 use embassy_stm32::time::Hertz;
 use embassy_stm32::timer::low_level::CountingMode;
 use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm};
+use embassy_stm32::wdg::IndependentWatchdog;
 use embassy_stm32::{Config, bind_interrupts, can, peripherals::*};
-use embassy_time::Delay;
-use embedded_hal_bus::spi::ExclusiveDevice;
-use mipidsi::Builder;
 use mipidsi::interface::SpiInterface;
-use mipidsi::models::ILI9488Rgb666;
-use mipidsi::options::Orientation;
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
@@ -27,8 +40,6 @@ bind_interrupts!(struct Irqs {
     FDCAN2_IT1 => can::IT1InterruptHandler<FDCAN2>;
 });
 
-// Default baud rate is 1 MHz
-const CAN_BAUD_RATE: u32 = 100_000;
 // Size of the spi buffer, longer buffers have diminishing returns
 const SPI_BUFFER_SIZE: usize = 512;
 
@@ -42,8 +53,8 @@ async fn main(spawner: Spawner) {
     // Initialize Clock
     ////////////////////////////////
     let mut config = Config::default();
-    // NOTE: Changing clock speed to anything not 170 MHz will make the LED colors wrong.
-    // Will effect the PWM frequency and duty cycle.
+    // NOTE: Changing clock speed to anything not 170 MHz needs `LED_TIMER_HZ` updating in
+    // led_mod, or the LED colors will be wrong.
     {
         // 170 MHz
         use embassy_stm32::rcc::*;
@@ -60,7 +71,10 @@ async fn main(spawner: Spawner) {
             divq: Some(PllQDiv::DIV2), // 170 MHz PLLQ
             divr: Some(PllRDiv::DIV2), // Main system clock at 170 MHz
         });
+        // The CAN bit timings are calculated for this, see `FDCAN_KERNEL_CLOCK`
         config.rcc.mux.fdcansel = mux::Fdcansel::HSE;
+        // The ADC prescaler divides the system clock down to the maximum ADC frequency
+        config.rcc.mux.adc12sel = mux::Adcsel::SYS;
         config.rcc.sys = Sysclk::PLL1_R;
     }
 
@@ -85,52 +99,147 @@ async fn main(spawner: Spawner) {
     let spi_rx_dma = peripherals.DMA1_CH2;
 
     let touch_cs = peripherals.PA9;
-    let _touch_irq = peripherals.PA8;
+    let touch_irq = peripherals.PA8;
     let lcd_cs = peripherals.PA4;
     let lcd_reset = peripherals.PB0;
     let lcd_bright = peripherals.PA2;
+    let lcd_bright_timer = peripherals.TIM15;
     let lcd_dc = peripherals.PA3;
 
+    ////////////////////////////////
+    // Initialize SPI
+    ////////////////////////////////
+    let mut spi_config = spi::Config::default();
+    // The bus starts at the display's clock, the touch controller's device slows it down
+    spi_config.frequency = LCD_SPI_FREQ;
+    spi_config.miso_pull = embassy_stm32::gpio::Pull::Up;
+    spi_config.gpio_speed = Speed::VeryHigh;
+
+    let spi = Spi::new(
+        spi_peripheral,
+        spi_sck,
+        spi_mosi,
+        spi_miso,
+        spi_tx_dma,
+        spi_rx_dma,
+        spi_config,
+    );
+
+    // The display and touch controller share the bus, each transaction locks it and
+    // applies the device's config
+    let spi_bus = share_spi_bus(spi);
+
+    info!("Configured SPI Peripherals");
+
+    ////////////////////////////////
+    // Initialize Touch Screen Peripherals
+    ////////////////////////////////
+
+    // CS is Active Low
+    let touch_cs = Output::new(touch_cs, Level::High, Speed::VeryHigh);
+    let mut touch_spi_config = spi_config;
+    touch_spi_config.frequency = TOUCH_SPI_FREQ;
+    let touch = SpiDeviceWithConfig::new(spi_bus, touch_cs, touch_spi_config);
+    // The IRQ pin is open drain, pulled low while the panel is touched
+    let touch_irq = ExtiInput::new(touch_irq, peripherals.EXTI8, Pull::Up);
+
+    ////////////////////////////////
+    // Initialize Screen Peripherals
+    ////////////////////////////////
+
+    let lcd_cs = Output::new(lcd_cs, Level::High, Speed::VeryHigh);
+    let lcd_reset = Output::new(lcd_reset, Level::Low, Speed::VeryHigh);
+    // Drive the LCD's backlight with PWM so it can be dimmed, it starts at full brightness
+    let lcd_bright = PwmPin::new(lcd_bright, OutputType::PushPull);
+    let lcd_bright = SimplePwm::new(
+        lcd_bright_timer,
+        Some(lcd_bright),
+        None,
+        None,
+        None,
+        BACKLIGHT_PWM_FREQ,
+        CountingMode::EdgeAlignedUp,
+    );
+    init_backlight(lcd_bright).await;
+    let lcd_dc = Output::new(lcd_dc, Level::Low, Speed::VeryHigh);
+
+    // Turn on LCD Display
+    static DISPLAY_BUFFER: StaticCell<[u8; SPI_BUFFER_SIZE]> = StaticCell::new();
+    let spi_buffer = DISPLAY_BUFFER.init([0u8; SPI_BUFFER_SIZE]);
+    let spi_device = SpiDeviceWithConfig::new(spi_bus, lcd_cs, spi_config);
+    let spi_interface = SpiInterface::new(spi_device, lcd_dc, spi_buffer);
+
+    // Retried while the panel's rail settles, and the dashboard carries on without it if it
+    // never comes up
+    let (display_bus, display_reset) = share_display(spi_interface, lcd_reset);
+    let mut display = init_display_with_retry(display_bus, display_reset).await;
+    if display.is_some() {
+        info!("Configured ILI9488 Display");
+    }
+
+    // Show each remaining init step on the screen, so it's visible where boot gets stuck
+    draw_boot_splash(display.as_mut());
+    for step in [BootStep::Clock, BootStep::Spi, BootStep::Display] {
+        draw_boot_line(display.as_mut(), step, true);
+    }
+
     ////////////////////////////////
     // Initialize CAN
     ////////////////////////////////
-    let mut can = can::CanConfigurator::new(can_peripheral, can_rx, can_tx, Irqs);
+    // Loop each package back through the peripheral before joining the bus, see can_self_test_mod
+    #[cfg(feature = "can-self-test")]
+    let (can_peripheral, can_rx, can_tx) = {
+        let (mut can_peripheral, mut can_rx, mut can_tx) = (can_peripheral, can_rx, can_tx);
+        draw_boot_line(display.as_mut(), BootStep::CanSelfTest, false);
+        let loopback = can::CanConfigurator::new(
+            can_peripheral.reborrow(),
+            can_rx.reborrow(),
+            can_tx.reborrow(),
+            Irqs,
+        );
+        match can_self_test(loopback).await {
+            Ok(()) => draw_boot_line(display.as_mut(), BootStep::CanSelfTest, true),
+            Err(_) => draw_boot_failure(display.as_mut(), BootStep::CanSelfTest),
+        }
+        (can_peripheral, can_rx, can_tx)
+    };
+
+    draw_boot_line(display.as_mut(), BootStep::Can, false);
+    let can = can::CanConfigurator::new(can_peripheral, can_rx, can_tx, Irqs);
     let can_stby = Output::new(can_stby, Level::Low, Speed::Low);
     // Because the destructor resets the gpio pin's state, use mem::forget to drop the variable
     core::mem::forget(can_stby);
 
-    can.properties().set_extended_filter(
-        can::filter::ExtendedFilterSlot::_0,
-        can::filter::ExtendedFilter::accept_all_into_fifo1(),
-    );
-    // Nominal Baud Rate: 1M bits/s
-    can.set_bitrate(CAN_BAUD_RATE);
-    // Uncomment if CANFD is used
-    // can.set_fd_data_bitrate(1_000_000, false);
-
-    let can = can.start(can::OperatingMode::NormalOperationMode);
-    let (can_tx, can_rx, _) = can.split();
+    // Filters each block into its FIFO, see can_mod's acceptance filters
+    let (can_tx, can_rx, can_properties) = start_can(can);
 
     info!("Configured CAN");
+    draw_boot_line(display.as_mut(), BootStep::Can, true);
 
     ////////////////////////////////
     // Initialize External Interrupt Buttons
     ////////////////////////////////
+    draw_boot_line(display.as_mut(), BootStep::Buttons, false);
     let btn1 = ExtiInput::new(btn1_pin, peripherals.EXTI3, Pull::Up);
     let btn2 = ExtiInput::new(btn2_pin, peripherals.EXTI4, Pull::Up);
+    // Both held at boot to run the demo, button 2 alone to bring up the display with test
+    // patterns
+    let demo_at_boot = btn1.is_low() && btn2.is_low();
+    let test_pattern_at_boot = btn2.is_low() && !demo_at_boot;
+    draw_boot_line(display.as_mut(), BootStep::Buttons, true);
 
     ////////////////////////////////
     // Initialize LED Lights
     ////////////////////////////////
+    draw_boot_line(display.as_mut(), BootStep::Leds, false);
     let led_in = PwmPin::new(led_pwm, OutputType::PushPull);
     let led_dma = peripherals.DMA2_CH1;
 
-    // PWM_FREQ = 1 / data_transfer_time = 1 / 1.25us = 800kHz
-    const PWM_FREQ: Hertz = Hertz::khz(800);
+    // One PWM period per bit, see LedTiming for the derivation
+    const PWM_FREQ: Hertz = Hertz(LED_TIMING.pwm_hz);
 
     // Obtain a PWM handler, configure the Timer and Frequency
     // The prescaler and ARR are automatically set
-    // Given this system frequency and pwm frequency the max duty cycle will be 50
     let mut led_in = SimplePwm::new(
         led_timer,
         Some(led_in),
@@ -143,75 +252,47 @@ async fn main(spawner: Spawner) {
     // Enable channel 1
     led_in.ch1().enable();
     info!("Configured LED Peripherals");
+    draw_boot_line(display.as_mut(), BootStep::Leds, true);
 
     ////////////////////////////////
-    // Initialize SPI
-    ////////////////////////////////
-    let mut spi_config = spi::Config::default();
-    // 40 MHz is the maximum frequency the ILI9488 can handle
-    spi_config.frequency = Hertz::mhz(40);
-    spi_config.miso_pull = embassy_stm32::gpio::Pull::Up;
-    spi_config.gpio_speed = Speed::VeryHigh;
-
-    let spi = Spi::new(
-        spi_peripheral,
-        spi_sck,
-        spi_mosi,
-        spi_miso,
-        spi_tx_dma,
-        spi_rx_dma,
-        spi_config,
-    );
-
-    info!("Configured SPI Peripherals");
-
-    ////////////////////////////////
-    // Initialize Touch Screen Peripherals
+    // Initialize ADC
     ////////////////////////////////
-
-    // CS is Active Low
-    let _touch_cs = Output::new(touch_cs, Level::High, Speed::VeryHigh);
-
-    ////////////////////////////////
-    // Initialize Screen Peripherals
-    ////////////////////////////////
-
-    let lcd_cs = Output::new(lcd_cs, Level::High, Speed::VeryHigh);
-    let lcd_reset = Output::new(lcd_reset, Level::Low, Speed::VeryHigh);
-    // Turn the LCD's backlight on indefinetly
-    // Because the destructor resets the gpio pin's state, use mem::forget to drop the variable
-    let _lcd_bright = Output::new(lcd_bright, Level::High, Speed::Low);
-    core::mem::forget(_lcd_bright);
-    let lcd_dc = Output::new(lcd_dc, Level::Low, Speed::VeryHigh);
-    let mut delay = Delay;
-
-    // Turn on LCD Display
-    static DISPLAY_BUFFER: StaticCell<[u8; SPI_BUFFER_SIZE]> = StaticCell::new();
-    let spi_buffer = DISPLAY_BUFFER.init([0u8; SPI_BUFFER_SIZE]);
-    let spi_device = ExclusiveDevice::new_no_delay(spi, lcd_cs).unwrap();
-    let spi_interface = SpiInterface::new(spi_device, lcd_dc, spi_buffer);
-
-    let display = Builder::new(ILI9488Rgb666, spi_interface)
-        .reset_pin(lcd_reset)
-        .color_order(mipidsi::options::ColorOrder::Bgr)
-        .orientation(
-            Orientation::new()
-                .rotate(mipidsi::options::Rotation::Deg270)
-                .flip_vertical(),
-        )
-        .init(&mut delay)
-        .unwrap();
-
-    info!("Configured ILI9488 Display");
+    draw_boot_line(display.as_mut(), BootStep::Adc, false);
+    let adc = Adc::new(peripherals.ADC1);
+    info!("Configured ADC");
+    draw_boot_line(display.as_mut(), BootStep::Adc, true);
 
     ////////////////////////////////3
     // Spawn Tasks
     ////////////////////////////////
     info!("Spawning Tasks");
-    spawner.spawn(can_receive_task(can_rx)).unwrap();
+    // Restores the saved trip before any packages are recorded
+    spawner
+        .spawn(storage_task(Flash::new_blocking(peripherals.FLASH)))
+        .unwrap();
+    spawner
+        .spawn(can_receive_task(can_rx, can_properties))
+        .unwrap();
     spawner.spawn(can_transmit_task(can_tx)).unwrap();
+    if demo_at_boot {
+        spawner.spawn(demo_task()).unwrap();
+    }
     spawner.spawn(led_task(led_in, led_dma)).unwrap();
-    spawner.spawn(display_task(display)).unwrap();
-    spawner.spawn(btn1_task(btn1)).unwrap();
-    spawner.spawn(btn2_task(btn2)).unwrap();
+    if let Some(display) = display {
+        spawner
+            .spawn(display_task(display, test_pattern_at_boot))
+            .unwrap();
+    }
+    spawner.spawn(btn1_task(btn1, BUTTON1_BOUNCE_MS)).unwrap();
+    spawner.spawn(btn2_task(btn2, BUTTON2_BOUNCE_MS)).unwrap();
+    spawner.spawn(adc_task(adc)).unwrap();
+    spawner.spawn(touch_task(touch, touch_irq)).unwrap();
+    spawner.spawn(node_supervisor_task()).unwrap();
+    // Started last, so the critical tasks are already running
+    let watchdog = IndependentWatchdog::new(peripherals.IWDG, WATCHDOG_TIMEOUT_US);
+    spawner.spawn(watchdog_task(watchdog)).unwrap();
+    #[cfg(feature = "csv-telemetry")]
+    spawner
+        .spawn(dashboard::telemetry_mod::csv_telemetry_task())
+        .unwrap();
 }
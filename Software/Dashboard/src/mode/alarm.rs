@@ -0,0 +1,160 @@
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    mono_font::{MonoTextStyle, MonoTextStyleBuilder, iso_8859_1::FONT_10X20},
+    pixelcolor::Rgb666,
+    prelude::{Point, Primitive, RgbColor, Size, WebColors},
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+use crate::display_mod::{
+    Anchor, CENTER_POINT, DisplayDevice, DrawResultExt, ICON_SIZE, Icon, LIGHTNING_ICON, SCREEN,
+    THERMOMETER_ICON, draw_icon,
+};
+use crate::safe_state_mod::Fault;
+use crate::warning_mod::ThermalLevel;
+
+/// Area of the fuel cell low voltage indicator, in the top right corner
+const FC_LOW_BOUNDS: Rectangle = Rectangle::new(
+    Anchor::TopRight.at(SCREEN, Point::new(-100, 0)),
+    Size::new(100, 24),
+);
+
+/// Area of the over temperature indicator, below the fuel cell low voltage indicator
+const THERMAL_BOUNDS: Rectangle = Rectangle::new(
+    Anchor::TopRight.at(SCREEN, Point::new(-100, 24)),
+    Size::new(100, 24),
+);
+
+/// Area of the dashboard's own low supply indicator, below the over temperature indicator
+const SUPPLY_LOW_BOUNDS: Rectangle = Rectangle::new(
+    Anchor::TopRight.at(SCREEN, Point::new(-100, 48)),
+    Size::new(100, 24),
+);
+
+/// Renders the full screen safe state banner, naming the fault
+pub fn render_safe_state_gui(display: &mut DisplayDevice, fault: Fault) {
+    display.clear(Rgb666::RED).or_record();
+
+    let title_style = MonoTextStyle::new(&FONT_10X20, Rgb666::WHITE);
+    Text::with_alignment(
+        fault.name(),
+        CENTER_POINT - Point::new(0, 20),
+        title_style,
+        Alignment::Center,
+    )
+    .draw(display)
+    .or_record();
+    Text::with_alignment(
+        "Double press button 2 to clear",
+        CENTER_POINT + Point::new(0, 20),
+        title_style,
+        Alignment::Center,
+    )
+    .draw(display)
+    .or_record();
+}
+
+/// Renders the active warning indicators over the current screen
+///
+/// `supply_low` is the dashboard's own supply, see [`adc_mod`](crate::adc_mod).
+pub fn render_warning_indicators(
+    display: &mut DisplayDevice,
+    fc_low: bool,
+    supply_low: bool,
+    thermal: ThermalLevel,
+) {
+    if fc_low {
+        render_indicator(
+            display,
+            FC_LOW_BOUNDS,
+            Rgb666::RED,
+            &LIGHTNING_ICON,
+            "LOW FC V",
+        );
+    }
+    if supply_low {
+        render_indicator(
+            display,
+            SUPPLY_LOW_BOUNDS,
+            Rgb666::CSS_ORANGE,
+            &LIGHTNING_ICON,
+            "LOW VDD",
+        );
+    }
+    match thermal {
+        ThermalLevel::Normal => (),
+        ThermalLevel::Warning => render_indicator(
+            display,
+            THERMAL_BOUNDS,
+            Rgb666::CSS_ORANGE,
+            &THERMOMETER_ICON,
+            "HOT",
+        ),
+        ThermalLevel::Critical => render_indicator(
+            display,
+            THERMAL_BOUNDS,
+            Rgb666::RED,
+            &THERMOMETER_ICON,
+            "OVERTEMP",
+        ),
+    }
+}
+
+/// Renders the badge marking the data on screen as synthetic, in the top left corner, see
+/// [`demo_mod`](crate::demo_mod)
+///
+/// Drawn every frame, since widgets redrawing beneath it would otherwise cover it. The text
+/// carries its own background, so each pixel is only written once and it doesn't flicker.
+pub fn render_demo_badge(display: &mut DisplayDevice) {
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(Rgb666::WHITE)
+        .background_color(Rgb666::CSS_DARK_VIOLET)
+        .build();
+    Text::with_baseline(" DEMO ", SCREEN.top_left, style, Baseline::Top)
+        .draw(display)
+        .or_record();
+}
+
+/// Gap between an indicator's edge, its icon and its label
+const INDICATOR_ICON_PADDING: u32 = 2;
+
+/// Renders a warning box in `bounds`, with an icon on the left and a label beside it
+fn render_indicator(
+    display: &mut DisplayDevice,
+    bounds: Rectangle,
+    color: Rgb666,
+    icon: &Icon,
+    label: &str,
+) {
+    bounds
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+        .or_record();
+    let icon_top = (bounds.size.height - ICON_SIZE) as i32 / 2;
+    draw_icon(
+        display,
+        icon,
+        Anchor::TopLeft.at(bounds, Point::new(INDICATOR_ICON_PADDING as i32, icon_top)),
+        Rgb666::WHITE,
+        color,
+    );
+    let icon_width = ICON_SIZE + 2 * INDICATOR_ICON_PADDING;
+    let label_area = Rectangle::new(
+        Anchor::TopLeft.at(bounds, Point::new(icon_width as i32, 0)),
+        Size::new(bounds.size.width - icon_width, bounds.size.height),
+    );
+    Text::with_text_style(
+        label,
+        Anchor::Center.point(label_area),
+        MonoTextStyle::new(&FONT_10X20, Rgb666::WHITE),
+        TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Middle)
+            .build(),
+    )
+    .draw(display)
+    .or_record();
+}
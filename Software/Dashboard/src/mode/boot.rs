@@ -0,0 +1,107 @@
+//! Boot splash, showing each init step as it runs
+//!
+//! There is no debugger attached on the car, so if boot gets stuck the last line on the
+//! screen shows which step it was.
+//!
+//! Each function takes the display as an `Option`, and draws nothing if it didn't come up.
+
+use defmt::Format;
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    mono_font::{MonoTextStyle, MonoTextStyleBuilder, iso_8859_1::FONT_10X20},
+    pixelcolor::Rgb666,
+    prelude::{Point, RgbColor},
+    text::{Baseline, Text},
+};
+
+use crate::display_mod::{DisplayDevice, DrawResultExt};
+
+/// Height of each status line
+const LINE_HEIGHT: i32 = 24;
+/// Top left of the first status line
+const FIRST_LINE: Point = Point::new(10, 40);
+
+/// The init steps, in the order they run
+///
+/// The display needs the clock and SPI, so those are shown once the display is up.
+#[derive(Clone, Copy, Debug, Format)]
+pub enum BootStep {
+    Clock,
+    Spi,
+    Display,
+    /// Only run with the `can-self-test` feature, see
+    /// [`can_self_test_mod`](crate::can_self_test_mod)
+    #[cfg(feature = "can-self-test")]
+    CanSelfTest,
+    Can,
+    Buttons,
+    Leds,
+    Adc,
+}
+
+impl BootStep {
+    const fn name(self) -> &'static str {
+        match self {
+            BootStep::Clock => "Clock",
+            BootStep::Spi => "SPI",
+            BootStep::Display => "Display",
+            #[cfg(feature = "can-self-test")]
+            BootStep::CanSelfTest => "CAN self-test",
+            BootStep::Can => "CAN",
+            BootStep::Buttons => "Buttons",
+            BootStep::Leds => "LEDs",
+            BootStep::Adc => "ADC",
+        }
+    }
+}
+
+/// Clears the screen and draws the splash title
+pub fn draw_boot_splash(display: Option<&mut DisplayDevice>) {
+    let Some(display) = display else {
+        return;
+    };
+    display.clear(Rgb666::BLACK).or_record();
+    Text::with_baseline(
+        "Sally Dashboard",
+        Point::new(FIRST_LINE.x, 10),
+        MonoTextStyle::new(&FONT_10X20, Rgb666::WHITE),
+        Baseline::Top,
+    )
+    .draw(display)
+    .or_record();
+}
+
+/// Draws the status line for a step, `...` while it runs and `OK` once it is done
+pub fn draw_boot_line(display: Option<&mut DisplayDevice>, step: BootStep, ok: bool) {
+    if ok {
+        draw_status(display, step, "OK ", Rgb666::GREEN);
+    } else {
+        draw_status(display, step, "...", Rgb666::YELLOW);
+    }
+}
+
+/// Draws the status line for a step that failed, and the boot carried on without
+pub fn draw_boot_failure(display: Option<&mut DisplayDevice>, step: BootStep) {
+    draw_status(display, step, "FAIL", Rgb666::RED);
+}
+
+fn draw_status(display: Option<&mut DisplayDevice>, step: BootStep, status: &str, color: Rgb666) {
+    let Some(display) = display else {
+        return;
+    };
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(color)
+        .background_color(Rgb666::BLACK)
+        .build();
+    let position = FIRST_LINE + Point::new(0, step as i32 * LINE_HEIGHT);
+    Text::with_baseline(step.name(), position, style, Baseline::Top)
+        .draw(display)
+        .or_record();
+    let name_width = step.name().len() as u32 * FONT_10X20.character_size.width;
+    let next = position + Point::new(name_width as i32, 0);
+    Text::with_baseline(status, next + Point::new(10, 0), style, Baseline::Top)
+        .draw(display)
+        .or_record();
+}
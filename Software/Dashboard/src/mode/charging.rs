@@ -1,66 +1,70 @@
-use core::sync::atomic::AtomicU32;
+use core::sync::atomic::AtomicU8;
 
 use super::init_charging::*;
-use crate::can_mod::REL_FC_PACK;
-use crate::display_mod::{CENTER_POINT, DisplayDevice};
+use crate::can_mod::{BATT_PACK2_DATA, REL_FC_PACK};
+use crate::display_mod::{CENTER_POINT, DisplayDevice, DrawResultExt, SevenSegField, Theme};
+use crate::source_mod::{PowerSource, update_power_source};
+use crate::units_mod::millivolts_to_volts;
 use core::sync::atomic::Ordering::Relaxed;
-use eg_seven_segment::SevenSegmentStyleBuilder;
+use eg_seven_segment::{SevenSegmentStyle, SevenSegmentStyleBuilder};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::iso_8859_13::FONT_10X20;
+use embedded_graphics::pixelcolor::Rgb666;
+use embedded_graphics::primitives::Rectangle;
 use embedded_graphics::primitives::StyledDrawable;
-use embedded_graphics::text::renderer::CharacterStyle;
 use embedded_graphics::{
     Drawable,
-    pixelcolor::Rgb666,
     prelude::*,
     primitives::{Arc, PrimitiveStyle},
     text::{Alignment, Text},
 };
 
-static PREV_BATT_VOLTAGE: AtomicU32 = AtomicU32::new(0);
+/// The power source shown on the last frame, [`NO_SOURCE`] forces the label to be redrawn
+pub static PREV_SOURCE: AtomicU8 = AtomicU8::new(NO_SOURCE);
+pub const NO_SOURCE: u8 = u8::MAX;
 
-fn render_battery_voltage_gui(
-    display: &mut DisplayDevice,
-    batt_voltage: u32,
-    prev_batt_voltage: u32,
-) {
-    const DIGIT_SPACING: u32 = 3;
-    const VOLTAGE_POS: Point = Point::new(
-        CENTER_POINT.x + BATT_FONT_WIDTH as i32,
-        CENTER_POINT.y + BATT_FONT_HEIGHT as i32 / 2,
-    );
-    const CLEAR_TEXT_POS: Point = Point::new(
-        CENTER_POINT.x - DIGIT_SPACING as i32,
-        CENTER_POINT.y + BATT_FONT_HEIGHT as i32 / 2,
-    );
+/// Renders the name of the power source being displayed
+fn render_source_gui(display: &mut DisplayDevice, theme: &Theme, source: PowerSource) {
+    let label = match source {
+        PowerSource::FuelCell => "FUEL CELL",
+        PowerSource::Battery => "BATTERY",
+        PowerSource::Unknown => "NO SOURCE",
+    };
+    let clear_style = PrimitiveStyle::with_fill(theme.background);
+    let label_style = MonoTextStyle::new(&FONT_10X20, theme.foreground);
+    let label_pos = CENTER_POINT - Point::new(0, ARC_DIAMTER as i32 / 2 + 20);
 
-    // Define Styles
-    let batt_style = SevenSegmentStyleBuilder::new()
-        .digit_size(Size::new(BATT_FONT_WIDTH, BATT_FONT_HEIGHT))
-        .digit_spacing(DIGIT_SPACING)
-        .segment_width(4)
-        .segment_color(Rgb666::WHITE)
-        .inactive_segment_color(Rgb666::BLACK)
-        .build();
-    let mut clear_style = batt_style.clone();
-    clear_style.set_text_color(Some(Rgb666::BLACK));
+    Rectangle::with_center(label_pos - Point::new(0, 6), Size::new(100, 20))
+        .draw_styled(&clear_style, display)
+        .or_record();
+    Text::with_alignment(label, label_pos, label_style, Alignment::Center)
+        .draw(display)
+        .or_record();
+}
 
-    let mut str_buffer = itoa::Buffer::new();
-    let batt_voltage_str = str_buffer.format(batt_voltage);
+const VOLTAGE_DIGIT_SPACING: u32 = 3;
 
-    // Clear Dead Text
-    if prev_batt_voltage >= 10 && batt_voltage < 10 {
-        Text::with_alignment("8", CLEAR_TEXT_POS, clear_style, Alignment::Right)
-            .draw(display)
-            .unwrap();
-    }
-    // Render Battery Voltage
-    Text::with_alignment(batt_voltage_str, VOLTAGE_POS, batt_style, Alignment::Right)
-        .draw(display)
-        .unwrap();
+/// Source voltage readout in whole volts, both digits centered on the screen
+static VOLTAGE_FIELD: Mutex<ThreadModeRawMutex, SevenSegField<2>> =
+    Mutex::new(SevenSegField::new(Point::new(
+        CENTER_POINT.x - VOLTAGE_DIGIT_SPACING as i32 - BATT_FONT_WIDTH as i32,
+        CENTER_POINT.y - BATT_FONT_HEIGHT as i32 / 2,
+    )));
+
+fn voltage_style(theme: &Theme) -> SevenSegmentStyle<Rgb666> {
+    SevenSegmentStyleBuilder::new()
+        .digit_size(Size::new(BATT_FONT_WIDTH, BATT_FONT_HEIGHT))
+        .digit_spacing(VOLTAGE_DIGIT_SPACING)
+        .segment_width(4)
+        .segment_color(theme.foreground)
+        .inactive_segment_color(theme.background)
+        .build()
 }
 
-fn render_battery_meter_gui(display: &mut DisplayDevice, battery_percent: f32) {
-    let empty_style = PrimitiveStyle::with_stroke(Rgb666::BLACK, 12);
-    let fill_style = PrimitiveStyle::with_stroke(Rgb666::GREEN, 12);
+fn render_battery_meter_gui(display: &mut DisplayDevice, theme: &Theme, battery_percent: f32) {
+    let empty_style = PrimitiveStyle::with_stroke(theme.background, 12);
+    let fill_style = PrimitiveStyle::with_stroke(theme.accent, 12);
 
     const ANGLE_END: f32 = ANGLE_START + (360.0 - (ANGLE_START - 90.0) * 2.0);
     const MAX_METER_LENGTH: f32 = 360.0 - (ANGLE_START - 90.0) * 2.0;
@@ -75,7 +79,7 @@ fn render_battery_meter_gui(display: &mut DisplayDevice, battery_percent: f32) {
         empty_length.deg(),
     )
     .draw_styled(&empty_style, display)
-    .unwrap();
+    .or_record();
 
     Arc::with_center(
         CENTER_POINT,
@@ -84,18 +88,25 @@ fn render_battery_meter_gui(display: &mut DisplayDevice, battery_percent: f32) {
         charge_length.deg(),
     )
     .draw_styled(&fill_style, display)
-    .unwrap();
+    .or_record();
 }
 
-pub async fn render_charging_gui(display: &mut DisplayDevice) {
-    let prev_batt_voltage = PREV_BATT_VOLTAGE.load(Relaxed);
-    let relay_fc_pack = REL_FC_PACK.lock().await;
-    let batt_voltage = relay_fc_pack.fc_volt;
-    drop(relay_fc_pack);
-    let batt_voltage_percent = batt_voltage as f32 / 48.0;
-
-    render_battery_voltage_gui(display, batt_voltage, prev_batt_voltage);
-    render_battery_meter_gui(display, batt_voltage_percent);
+pub async fn render_charging_gui(display: &mut DisplayDevice, theme: &Theme) {
+    // Show the voltage of whichever source is powering the car
+    let source = update_power_source().await;
+    let batt_voltage_mv = match source {
+        PowerSource::Battery => BATT_PACK2_DATA.lock().await.out_volt as u32,
+        PowerSource::FuelCell | PowerSource::Unknown => REL_FC_PACK.lock().await.fc_volt,
+    };
+    let mut voltage_field = VOLTAGE_FIELD.lock().await;
+    if PREV_SOURCE.swap(source as u8, Relaxed) != source as u8 {
+        render_source_gui(display, theme, source);
+        // Also set after a clear, which redraws the readout
+        voltage_field.invalidate();
+    }
+    let batt_voltage_percent = batt_voltage_mv as f32 / 48_000.0;
 
-    PREV_BATT_VOLTAGE.store(batt_voltage, Relaxed);
+    let (batt_volts, _) = millivolts_to_volts(batt_voltage_mv);
+    voltage_field.update(display, theme, voltage_style(theme), batt_volts as u32);
+    render_battery_meter_gui(display, theme, batt_voltage_percent);
 }
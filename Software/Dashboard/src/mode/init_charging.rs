@@ -1,4 +1,6 @@
-use crate::display_mod::{CENTER_POINT, DisplayDevice};
+use super::charging::{NO_SOURCE, PREV_SOURCE};
+use crate::display_mod::{CENTER_POINT, DisplayDevice, DrawResultExt, Theme};
+use core::sync::atomic::Ordering::Relaxed;
 use embedded_graphics::mono_font::MonoTextStyle;
 use embedded_graphics::mono_font::iso_8859_13::FONT_10X20;
 use embedded_graphics::prelude::WebColors;
@@ -17,7 +19,10 @@ pub const BORDER_WIDTH: u32 = 2;
 pub const BATT_FONT_WIDTH: u32 = 20;
 pub const BATT_FONT_HEIGHT: u32 = 35;
 
-pub fn init_render_charging_gui(display: &mut DisplayDevice) {
+pub fn init_render_charging_gui(display: &mut DisplayDevice, theme: &Theme) {
+    // The screen was cleared, so the power source label must be redrawn
+    PREV_SOURCE.store(NO_SOURCE, Relaxed);
+
     // Render loading bar border
     let border_style = PrimitiveStyle::with_stroke(Rgb666::CSS_DARK_GRAY, 12 + BORDER_WIDTH * 2);
     Arc::with_center(
@@ -27,10 +32,10 @@ pub fn init_render_charging_gui(display: &mut DisplayDevice) {
         (360.0 - (ANGLE_START - 90.0 - BORDER_WIDTH as f32) * 2.0).deg(),
     )
     .draw_styled(&border_style, display)
-    .unwrap();
+    .or_record();
 
     // Render Speed Unit
-    let batt_unit_style = MonoTextStyle::new(&FONT_10X20, Rgb666::WHITE);
+    let batt_unit_style = MonoTextStyle::new(&FONT_10X20, theme.foreground);
 
     Text::with_alignment(
         "V",
@@ -43,5 +48,5 @@ pub fn init_render_charging_gui(display: &mut DisplayDevice) {
         Alignment::Right,
     )
     .draw(display)
-    .unwrap();
+    .or_record();
 }
@@ -12,7 +12,7 @@ use embedded_graphics::{
     text::{Alignment, Text},
 };
 
-use crate::display_mod::{CENTER_POINT, DISPLAY_HEIGHT, DISPLAY_WIDTH, DisplayDevice};
+use crate::display_mod::{Anchor, CENTER_POINT, DisplayDevice, DrawResultExt, SCREEN, Theme};
 use embedded_graphics::mono_font::MonoTextStyle;
 
 pub const SPEED_FONT_WIDTH: u32 = 27;
@@ -20,11 +20,11 @@ pub const SPEED_FONT_HEIGHT: u32 = 63;
 
 pub const EFF_FONT_WIDTH: u32 = 15;
 pub const EFF_FONT_HEIGHT: u32 = 25;
-pub const EFF_POS: Point = Point::new(50, DISPLAY_HEIGHT as i32 - 50);
+pub const EFF_POS: Point = Anchor::BottomLeft.at(SCREEN, Point::new(50, -50));
 
 pub const BATT_WIDTH: u32 = 16;
 pub const BATT_HEIGHT: u32 = 40;
-pub const BATT_POS: Point = Point::new(DISPLAY_WIDTH as i32 - 40, DISPLAY_HEIGHT as i32 - 60);
+pub const BATT_POS: Point = Anchor::BottomRight.at(SCREEN, Point::new(-40, -60));
 
 fn init_render_speed_gui(display: &mut DisplayDevice) {
     let speed_unit_style = MonoTextStyle::new(&FONT_10X20, Rgb666::RED);
@@ -37,7 +37,7 @@ fn init_render_speed_gui(display: &mut DisplayDevice) {
     // Render Speed Circle
     Circle::with_center(CENTER_POINT, 120)
         .draw_styled(&speed_circle_style, display)
-        .unwrap();
+        .or_record();
     // Render Speed Unit
     Text::with_alignment(
         "km/h",
@@ -46,20 +46,20 @@ fn init_render_speed_gui(display: &mut DisplayDevice) {
         Alignment::Center,
     )
     .draw(display)
-    .unwrap();
+    .or_record();
 }
 
-fn init_render_efficiency_gui(display: &mut DisplayDevice) {
-    let eff_unit_style = MonoTextStyle::new(&FONT_10X20, Rgb666::GREEN);
+fn init_render_efficiency_gui(display: &mut DisplayDevice, theme: &Theme) {
+    let eff_unit_style = MonoTextStyle::new(&FONT_10X20, theme.accent);
     let eff_circle_style = PrimitiveStyleBuilder::new()
-        .stroke_color(Rgb666::GREEN)
+        .stroke_color(theme.accent)
         .stroke_width(4)
         .stroke_alignment(StrokeAlignment::Outside)
         .build();
     // Render Efficiency Circle
     Circle::with_center(EFF_POS, 70)
         .draw_styled(&eff_circle_style, display)
-        .unwrap();
+        .or_record();
     // Render Efficiency %
     Text::with_alignment(
         "%",
@@ -68,10 +68,10 @@ fn init_render_efficiency_gui(display: &mut DisplayDevice) {
         Alignment::Left,
     )
     .draw(display)
-    .unwrap();
+    .or_record();
 }
 
-fn init_render_battery_gui(display: &mut DisplayDevice) {
+fn init_render_battery_gui(display: &mut DisplayDevice, theme: &Theme) {
     let bat_tip_width = 12;
     let bat_tip_height = 8;
 
@@ -83,16 +83,18 @@ fn init_render_battery_gui(display: &mut DisplayDevice) {
 
     let outline_style = PrimitiveStyleBuilder::new()
         .stroke_alignment(StrokeAlignment::Outside)
-        .stroke_color(Rgb666::WHITE)
+        .stroke_color(theme.foreground)
         .stroke_width(4)
         .build();
-    let tip_style = PrimitiveStyle::with_fill(Rgb666::WHITE);
-    let batt_unit_style = MonoTextStyle::new(&FONT_10X20, Rgb666::WHITE);
+    let tip_style = PrimitiveStyle::with_fill(theme.foreground);
+    let batt_unit_style = MonoTextStyle::new(&FONT_10X20, theme.foreground);
 
     // Render Battery Tip
-    bat_tip.draw_styled(&tip_style, display).unwrap();
+    bat_tip.draw_styled(&tip_style, display).or_record();
     // Render Battery Border
-    batt_outline.draw_styled(&outline_style, display).unwrap();
+    batt_outline
+        .draw_styled(&outline_style, display)
+        .or_record();
     // Render Battey %
     Text::with_alignment(
         "%",
@@ -101,10 +103,10 @@ fn init_render_battery_gui(display: &mut DisplayDevice) {
         Alignment::Right,
     )
     .draw(display)
-    .unwrap();
+    .or_record();
 }
-pub fn init_render_running_gui(display: &mut DisplayDevice) {
+pub fn init_render_running_gui(display: &mut DisplayDevice, theme: &Theme) {
     init_render_speed_gui(display);
-    init_render_efficiency_gui(display);
-    init_render_battery_gui(display);
+    init_render_efficiency_gui(display, theme);
+    init_render_battery_gui(display, theme);
 }
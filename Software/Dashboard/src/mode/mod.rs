@@ -1,7 +1,13 @@
+pub mod alarm;
+pub mod boot;
 pub mod charging;
+pub mod packages;
+pub mod pages;
 pub mod running;
 pub mod standby;
 pub mod startup;
+pub mod test_pattern;
+pub mod touch_calibration;
 
 pub mod init_charging;
 pub mod init_running;
@@ -0,0 +1,126 @@
+//! Page listing every registered CAN package's ID and current value
+//!
+//! Each field is shown with its label, value and unit from the package's
+//! [`PackageFields`](crate::eco_can::PackageFields) table, e.g. `0x017  FC Volt 12.3 V`.
+//!
+//! Packages are shown a group at a time, short press button 2 to show the next group.
+//! Stale packages are drawn in grey, so dead nodes stand out.
+//!
+//! Only lines whose text or staleness changed are redrawn, since the SPI bus is the bottleneck.
+
+use core::fmt::Write;
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::Instant;
+use embedded_graphics::{
+    Drawable,
+    mono_font::{MonoTextStyleBuilder, ascii::FONT_6X10},
+    prelude::Point,
+    text::{Baseline, Text},
+};
+use heapless::String;
+
+use crate::can_mod::{PACKAGE_IDS, write_package};
+use crate::display_mod::{DisplayDevice, DrawResultExt, Theme};
+
+/// Packages shown in each group
+const PACKAGES_PER_GROUP: usize = 6;
+/// Number of groups needed to show every package
+pub const PACKAGE_GROUPS: usize = PACKAGE_IDS.len().div_ceil(PACKAGES_PER_GROUP);
+/// Characters in a line of the 6x10 font across the screen
+const LINE_CHARS: usize = 80;
+/// Lines a package can wrap onto, anything longer is cut off
+const LINES_PER_PACKAGE: usize = 3;
+const LINE_HEIGHT: i32 = 12;
+/// Lines below the header
+const LINES: usize = PACKAGES_PER_GROUP * LINES_PER_PACKAGE;
+
+/// Hash of each line as it was last drawn, 0 if it needs to be redrawn
+static DRAWN_LINES: Mutex<ThreadModeRawMutex, [u32; LINES + 1]> = Mutex::new([0; LINES + 1]);
+
+/// FNV-1a hash of a line and its color, never 0
+fn line_hash(text: &str, stale: bool) -> u32 {
+    let hash = text
+        .bytes()
+        .chain([stale as u8])
+        .fold(0x811C_9DC5_u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        });
+    hash.max(1)
+}
+
+/// Draws a line if it changed since it was last drawn, padding it to erase the old text
+fn draw_line(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    drawn: &mut u32,
+    row: usize,
+    text: &str,
+    stale: bool,
+) {
+    let hash = line_hash(text, stale);
+    if *drawn == hash {
+        return;
+    }
+    *drawn = hash;
+
+    let mut padded: String<LINE_CHARS> = String::new();
+    for c in text.chars().chain(core::iter::repeat(' ')).take(LINE_CHARS) {
+        let _ = padded.push(c);
+    }
+    let color = if stale { theme.muted } else { theme.foreground };
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(color)
+        .background_color(theme.background)
+        .build();
+    let position = Point::new(0, row as i32 * LINE_HEIGHT);
+    Text::with_baseline(&padded, position, style, Baseline::Top)
+        .draw(display)
+        .or_record();
+}
+
+/// Renders a group of packages
+///
+/// `redraw` - If true then every line is drawn, set after a clear
+pub async fn render_packages_page(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    group: usize,
+    redraw: bool,
+) {
+    let now = Instant::now();
+    let mut drawn = DRAWN_LINES.lock().await;
+    if redraw {
+        *drawn = [0; LINES + 1];
+    }
+
+    let mut header: String<LINE_CHARS> = String::new();
+    let _ = core::write!(
+        header,
+        "CAN packages {}/{}, short press button 2 for more",
+        group + 1,
+        PACKAGE_GROUPS
+    );
+    draw_line(display, theme, &mut drawn[0], 0, &header, false);
+
+    let mut ids = PACKAGE_IDS
+        .iter()
+        .copied()
+        .skip(group * PACKAGES_PER_GROUP)
+        .take(PACKAGES_PER_GROUP);
+    for package in 0..PACKAGES_PER_GROUP {
+        let mut text: String<{ LINE_CHARS * LINES_PER_PACKAGE }> = String::new();
+        let stale = match ids.next() {
+            Some(id) => write_package(id, now, &mut text).await.unwrap_or(true),
+            None => false,
+        };
+        for line in 0..LINES_PER_PACKAGE {
+            let start = (line * LINE_CHARS).min(text.len());
+            let end = ((line + 1) * LINE_CHARS).min(text.len());
+            let row = 1 + package * LINES_PER_PACKAGE + line;
+            let text = text.get(start..end).unwrap_or("");
+            draw_line(display, theme, &mut drawn[row], row, text, stale);
+        }
+    }
+}
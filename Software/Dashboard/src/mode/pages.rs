@@ -0,0 +1,913 @@
+//! Pages selected with the buttons, showing detailed readings for one part of the car
+//!
+//! The overview page is the relay state dependent screen (startup, standby, charging, running).
+//! The other pages use the same row layout as the standby screen.
+
+use core::fmt::Write;
+use core::sync::atomic::Ordering::Relaxed;
+
+use defmt::Format;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::Instant;
+use embedded_graphics::{
+    Drawable,
+    mono_font::{MonoTextStyle, MonoTextStyleBuilder, ascii::FONT_6X10, iso_8859_1::FONT_9X15},
+    pixelcolor::Rgb666,
+    prelude::{Point, RgbColor, Size},
+    primitives::Rectangle,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+use heapless::String;
+
+use super::packages::{PACKAGE_GROUPS, render_packages_page};
+use super::standby::{CURRENT_ROW, render_can_value};
+use crate::adc_mod::SUPPLY_MV;
+use crate::can_mod::{
+    CAN_BUS_STATUS, CAN_ERROR_COUNT, CAN_RESTART_COUNT, CAN_TX_ERROR_COUNT, CanBusState,
+};
+use crate::can_stats_mod::CAN_STATS;
+use crate::display_mod::{
+    Anchor, ArcGauge, BarGauge, DisplayDevice, DrawResultExt, Grid, OnOffIndicator, SCREEN, Theme,
+};
+use crate::eco_can::FetBit;
+use crate::event_log_mod::{EVENT_CHARS, EVENT_LOG_LEN, event_log};
+use crate::node_mod::{CanNode, is_offline};
+use crate::peak_mod::{PEAKS, PeakChannel};
+use crate::power_mod::{
+    batt_power_mw, cap_power_mw, fc_power_mw, mtr_power_mw, mw_to_w, net_power_mw,
+};
+use crate::snapshot_mod::{TelemetrySnapshot, snapshot};
+use crate::source_mod::POWER_SOURCE;
+use crate::timestamp_mod::CAN_TIMEBASE;
+use crate::trip_mod::TRIP;
+
+/// Full scale of the fan RPM gauges
+const FAN_GAUGE_MAX_RPM: u32 = 10_000;
+/// The fan gauges fill the right half of the screen, which the fuel cell page's rows leave empty
+const FAN_GAUGE_RADIUS: u32 = 60;
+/// Offset of each fan gauge's label from its center, in the gap at the bottom of the dial
+const FAN_LABEL_OFFSET: Point = Point::new(0, FAN_GAUGE_RADIUS as i32 / 2);
+
+/// The fan gauges are centered in the right hand cells
+const FAN_GRID: Grid = Grid::new(2, 2);
+
+/// Dials for the two fuel cell fans, drawn over a 240° sweep from the bottom left
+static FAN_GAUGES: Mutex<ThreadModeRawMutex, [ArcGauge; 2]> = Mutex::new([
+    fan_gauge(Anchor::Center.point(FAN_GRID.cell(1, 0))),
+    fan_gauge(Anchor::Center.point(FAN_GRID.cell(1, 1))),
+]);
+
+const fn fan_gauge(center: Point) -> ArcGauge {
+    ArcGauge::new(
+        center,
+        FAN_GAUGE_RADIUS,
+        150,
+        240,
+        0,
+        FAN_GAUGE_MAX_RPM,
+        Rgb666::RED,
+    )
+}
+
+/// The FET indicators are in a row across the bottom right of the power page
+const FET_GRID: Grid = Grid::within(Grid::new(2, 2).cell(1, 1), FetBit::FETS.len() as u32, 1);
+/// Size of each FET indicator
+const FET_INDICATOR_SIZE: Size = Size::new(48, 24);
+
+/// Which FETs are on, in the order of [`FetBit::FETS`]
+static FET_INDICATORS: Mutex<ThreadModeRawMutex, [OnOffIndicator; FetBit::FETS.len()]> =
+    Mutex::new([
+        fet_indicator(0),
+        fet_indicator(1),
+        fet_indicator(2),
+        fet_indicator(3),
+    ]);
+
+/// The indicator for the `i`th FET, centered in its cell
+const fn fet_indicator(i: usize) -> OnOffIndicator {
+    let center = Anchor::Center.point(FET_GRID.cell(i as u32, 0));
+    let top_left = Point::new(
+        center.x - FET_INDICATOR_SIZE.width as i32 / 2,
+        center.y - FET_INDICATOR_SIZE.height as i32 / 2,
+    );
+    OnOffIndicator::new(
+        Rectangle::new(top_left, FET_INDICATOR_SIZE),
+        FetBit::FETS[i].label(),
+    )
+}
+
+// Every indicator fits in its cell
+const _: () = {
+    let mut i = 0;
+    while i < FetBit::FETS.len() {
+        assert!(FET_GRID.cell(i as u32, 0).size.width >= FET_INDICATOR_SIZE.width);
+        i += 1;
+    }
+};
+
+/// The bars are stacked on the right of the power page, below the warning indicators and above
+/// the FET indicators, with a label above each bar
+const BAR_GRID: Grid = Grid::within(Grid::new(2, 4).cell(1, 1), 1, 2);
+/// Size of each bar, inside its frame
+const BAR_SIZE: Size = Size::new(200, 12);
+/// Offset of each bar from the top of its cell, leaving room for the label
+const BAR_OFFSET: i32 = 22;
+/// Full scale of the capacitor voltage bar
+const CAP_BAR_MAX_MV: u32 = 50_000;
+/// Full scale of the efficiency bar, in 0.01 %
+const EFFICIENCY_BAR_MAX: u32 = 10_000;
+
+/// Capacitor voltage and boost converter efficiency, with their labels
+static POWER_BARS: Mutex<ThreadModeRawMutex, [(BarGauge, &str); 2]> = Mutex::new([
+    (bar_gauge(0, CAP_BAR_MAX_MV, Rgb666::YELLOW), "cap volt"),
+    (
+        bar_gauge(1, EFFICIENCY_BAR_MAX, Rgb666::GREEN),
+        "efficiency",
+    ),
+]);
+
+/// The bar in row `row` of [`BAR_GRID`], centered across its cell
+const fn bar_gauge(row: u32, max: u32, fill_color: Rgb666) -> BarGauge {
+    let cell = BAR_GRID.cell(0, row);
+    let top_left = Point::new(
+        Anchor::TopCenter.point(cell).x - BAR_SIZE.width as i32 / 2,
+        cell.top_left.y + BAR_OFFSET,
+    );
+    BarGauge::new(Rectangle::new(top_left, BAR_SIZE), 0, max, fill_color)
+}
+
+// Every bar and its frame fits in its cell
+const _: () = {
+    let mut row = 0;
+    while row < 2 {
+        let cell = BAR_GRID.cell(0, row);
+        assert!(cell.size.width >= BAR_SIZE.width + 2);
+        assert!(cell.size.height as i32 > BAR_OFFSET + BAR_SIZE.height as i32);
+        row += 1;
+    }
+};
+
+fn label_style(theme: &Theme) -> MonoTextStyle<'static, Rgb666> {
+    MonoTextStyle::new(&FONT_9X15, theme.foreground)
+}
+
+/// A display page
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum Page {
+    /// The relay state dependent screen
+    Overview,
+    FuelCell,
+    Power,
+    Trip,
+    /// The lowest and highest values since the peaks were reset
+    Peaks,
+    Diagnostics,
+    /// Every CAN package's value, a group at a time
+    Packages(usize),
+}
+
+impl Page {
+    /// The page after this one, wrapping around to the overview
+    pub fn next(self) -> Self {
+        match self {
+            Page::Overview => Page::FuelCell,
+            Page::FuelCell => Page::Power,
+            Page::Power => Page::Trip,
+            Page::Trip => Page::Peaks,
+            Page::Peaks => Page::Diagnostics,
+            Page::Diagnostics => Page::Packages(0),
+            Page::Packages(_) => Page::Overview,
+        }
+    }
+
+    /// The next group of the packages page, other pages are unchanged
+    pub fn next_group(self) -> Self {
+        match self {
+            Page::Packages(group) => Page::Packages((group + 1) % PACKAGE_GROUPS),
+            page => page,
+        }
+    }
+}
+
+/// Renders a page other than the overview
+///
+/// `render_field_name` - If true then render the field name of each value, set after a clear
+pub async fn render_page(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    page: Page,
+    render_field_name: bool,
+) {
+    match page {
+        Page::Overview => return,
+        Page::FuelCell => {
+            render_fuel_cell_page(display, theme, &snapshot().await, render_field_name).await
+        }
+        Page::Power => {
+            render_power_page(display, theme, &snapshot().await, render_field_name).await
+        }
+        Page::Trip => render_trip_page(display, theme, render_field_name).await,
+        Page::Peaks => render_peaks_page(display, theme, render_field_name).await,
+        Page::Diagnostics => render_diagnostics_page(display, theme, render_field_name).await,
+        Page::Packages(group) => {
+            // The package lines span the screen, and already grey out stale packages
+            render_packages_page(display, theme, group, render_field_name).await;
+            return;
+        }
+    }
+    render_offline_nodes(display, theme, render_field_name).await;
+
+    // Reset Row number after each frame
+    *CURRENT_ROW.lock().await = 0;
+}
+
+/// Longest line of offline nodes, every node and the suffix
+const OFFLINE_LINE_CHARS: usize = {
+    let mut chars = " OFFLINE".len();
+    let mut i = 0;
+    while i < CanNode::ALL.len() {
+        chars += CanNode::ALL[i].name().len() + 1;
+        i += 1;
+    }
+    chars
+};
+
+/// Offline flags as they were last drawn, `None` if they need to be redrawn
+static DRAWN_OFFLINE: Mutex<ThreadModeRawMutex, Option<[bool; CanNode::ALL.len()]>> =
+    Mutex::new(None);
+
+/// Lists the offline nodes in the top right corner, e.g. "FCC H2 OFFLINE"
+///
+/// Only drawn when the set of offline nodes changes, or `redraw` is set after a clear.
+async fn render_offline_nodes(display: &mut DisplayDevice, theme: &Theme, redraw: bool) {
+    let offline = CanNode::ALL.map(is_offline);
+    let mut drawn = DRAWN_OFFLINE.lock().await;
+    if !redraw && *drawn == Some(offline) {
+        return;
+    }
+    *drawn = Some(offline);
+
+    let mut line: String<OFFLINE_LINE_CHARS> = String::new();
+    for node in CanNode::ALL.into_iter().filter(|&node| is_offline(node)) {
+        let _ = write!(line, "{} ", node.name());
+    }
+    if !line.is_empty() {
+        let _ = line.push_str("OFFLINE");
+    }
+    // Right aligned, so pad on the left to erase a longer previous line
+    let mut padded: String<OFFLINE_LINE_CHARS> = String::new();
+    for _ in line.len()..OFFLINE_LINE_CHARS {
+        let _ = padded.push(' ');
+    }
+    let _ = padded.push_str(&line);
+
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(theme.warning)
+        .background_color(theme.background)
+        .build();
+    Text::with_text_style(
+        &padded,
+        Anchor::TopRight.point(SCREEN),
+        style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Right)
+            .baseline(Baseline::Top)
+            .build(),
+    )
+    .draw(display)
+    .or_record();
+}
+
+async fn render_fuel_cell_page(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    telemetry: &TelemetrySnapshot,
+    render_field_name: bool,
+) {
+    let rel_fc = &telemetry.rel_fc;
+    let stale = telemetry.is_stale(rel_fc);
+    render_can_value(
+        "fc_volt",
+        rel_fc.fc_volt,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "fc_curr",
+        rel_fc.fc_curr,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let fcc1 = &telemetry.fcc1;
+    let stale = telemetry.is_stale(fcc1);
+    render_can_value(
+        "fc_temp",
+        fcc1.fc_temp as u32,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "fc_press",
+        fcc1.fc_press,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let fcc2 = &telemetry.fcc2;
+    let stale = telemetry.is_stale(fcc2);
+    render_can_value(
+        "fan_rpm1",
+        fcc2.fan_rpm1,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "fan_rpm2",
+        fcc2.fan_rpm2,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    let fan_rpm = [fcc2.fan_rpm1, fcc2.fan_rpm2];
+
+    let mut gauges = FAN_GAUGES.lock().await;
+    for ((gauge, label), rpm) in gauges.iter_mut().zip(["fan 1", "fan 2"]).zip(fan_rpm) {
+        if render_field_name {
+            gauge.draw_dial(display, theme);
+            gauge.invalidate();
+            Text::with_alignment(
+                label,
+                gauge.center() + FAN_LABEL_OFFSET,
+                label_style(theme),
+                Alignment::Center,
+            )
+            .draw(display)
+            .or_record();
+        }
+        // A stale fan rests at zero rather than showing its last speed
+        gauge.update(display, theme, if stale { 0 } else { rpm });
+    }
+    drop(gauges);
+
+    let h2 = &telemetry.h2_1;
+    let stale = telemetry.is_stale(h2);
+    for (field, value) in [
+        ("h2_sense_1", h2.h2_sense_1),
+        ("h2_sense_2", h2.h2_sense_2),
+        ("h2_sense_3", h2.h2_sense_3),
+        ("h2_sense_4", h2.h2_sense_4),
+    ] {
+        render_can_value(
+            field,
+            value as u32,
+            stale,
+            render_field_name,
+            display,
+            theme,
+        )
+        .await;
+    }
+}
+
+async fn render_power_page(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    telemetry: &TelemetrySnapshot,
+    render_field_name: bool,
+) {
+    let rel_cap = &telemetry.rel_cap;
+    let stale = telemetry.is_stale(rel_cap);
+    render_can_value(
+        "cap_volt",
+        rel_cap.cap_volt,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "cap_curr",
+        rel_cap.cap_curr as u32,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let rel_mtr = &telemetry.rel_mtr;
+    let stale = telemetry.is_stale(rel_mtr);
+    render_can_value(
+        "mtr_volt",
+        rel_mtr.mtr_volt,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "mtr_curr",
+        rel_mtr.mtr_curr,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let boost1 = &telemetry.boost1;
+    let stale = telemetry.is_stale(boost1);
+    render_can_value(
+        "in_volt",
+        boost1.in_volt,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "in_curr",
+        boost1.in_curr,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let boost2 = &telemetry.boost2;
+    let stale = telemetry.is_stale(boost2);
+    render_can_value(
+        "out_volt",
+        boost2.out_volt,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "out_curr",
+        boost2.out_curr,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let boost3 = &telemetry.boost3;
+    let stale = telemetry.is_stale(boost3);
+    render_can_value(
+        "efficiency",
+        boost3.efficiency,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let batt = &telemetry.batt;
+    let stale = telemetry.is_stale(batt);
+    render_can_value(
+        "batt_volt",
+        batt.out_volt as u32,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "batt_curr",
+        batt.out_curr as u32,
+        stale,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let (rel_fc, rel_cap, rel_mtr) = (&telemetry.rel_fc, &telemetry.rel_cap, &telemetry.rel_mtr);
+    let fc_stale = telemetry.is_stale(rel_fc);
+    let mtr_stale = telemetry.is_stale(rel_mtr);
+    for (field, power_mw, stale) in [
+        ("fc_w", fc_power_mw(rel_fc), fc_stale),
+        ("cap_w", cap_power_mw(rel_cap), telemetry.is_stale(rel_cap)),
+        ("mtr_w", mtr_power_mw(rel_mtr), mtr_stale),
+        (
+            "net_w",
+            net_power_mw(rel_fc, rel_mtr),
+            fc_stale || mtr_stale,
+        ),
+        ("batt_w", batt_power_mw(batt), telemetry.is_stale(batt)),
+    ] {
+        render_can_value(
+            field,
+            mw_to_w(power_mw),
+            stale,
+            render_field_name,
+            display,
+            theme,
+        )
+        .await;
+    }
+
+    let mut bars = POWER_BARS.lock().await;
+    let values = [
+        (rel_cap.cap_volt, telemetry.is_stale(rel_cap)),
+        (
+            telemetry.boost3.efficiency,
+            telemetry.is_stale(&telemetry.boost3),
+        ),
+    ];
+    for ((bar, label), (value, stale)) in bars.iter_mut().zip(values) {
+        if render_field_name {
+            bar.draw_frame(display, theme);
+            bar.invalidate();
+            Text::with_text_style(
+                label,
+                Point::new(
+                    bar.area().top_left.x,
+                    bar.area().top_left.y - BAR_OFFSET + 4,
+                ),
+                label_style(theme),
+                TextStyleBuilder::new().baseline(Baseline::Top).build(),
+            )
+            .draw(display)
+            .or_record();
+        }
+        // A stale bar empties rather than showing its last value
+        bar.update(display, theme, if stale { 0 } else { value });
+    }
+    drop(bars);
+
+    let fet = &telemetry.fet;
+    let stale = telemetry.is_stale(fet);
+    let mut indicators = FET_INDICATORS.lock().await;
+    for (indicator, on) in indicators.iter_mut().zip(fet.fet_states()) {
+        if render_field_name {
+            indicator.invalidate();
+        }
+        // A stale package's FETs are unknown, rather than showing their last state
+        indicator.update(display, theme, (!stale).then_some(on));
+    }
+}
+
+async fn render_trip_page(display: &mut DisplayDevice, theme: &Theme, render_field_name: bool) {
+    let now = Instant::now();
+    let trip = *TRIP.lock().await;
+    let totals = trip.totals(now);
+
+    for (field, total, extent) in [
+        ("trip_fc_j", totals.fc_joules, trip.fc_joules),
+        ("trip_cap_j", totals.cap_joules, trip.cap_joules),
+        ("trip_fc_c", totals.fc_coulombs, trip.fc_coulombs),
+        ("trip_cap_c", totals.cap_coulombs, trip.cap_coulombs),
+    ] {
+        // A restored total is shown even before its package is received again
+        let stale = extent.is_none() && !trip.is_restored();
+        render_can_value(
+            field,
+            total as u32,
+            stale,
+            render_field_name,
+            display,
+            theme,
+        )
+        .await;
+    }
+    render_can_value(
+        "trip_j",
+        trip.total_joules() as u32,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "trip_avg_w",
+        trip.average_power_w(now) as u32,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "trip_s",
+        trip.elapsed_secs(now) as u32,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+}
+
+/// Field names of the lowest and highest value of each peak channel
+const PEAK_FIELDS: [(PeakChannel, &str, &str); PeakChannel::COUNT] = [
+    (PeakChannel::FcVolt, "fc_volt_lo", "fc_volt_hi"),
+    (PeakChannel::FcCurr, "fc_curr_lo", "fc_curr_hi"),
+    (PeakChannel::FcPower, "fc_w_lo", "fc_w_hi"),
+    (PeakChannel::FcTemp, "fc_temp_lo", "fc_temp_hi"),
+    (PeakChannel::CapVolt, "cap_volt_lo", "cap_volt_hi"),
+    (PeakChannel::CapCurr, "cap_curr_lo", "cap_curr_hi"),
+    (PeakChannel::MtrCurr, "mtr_curr_lo", "mtr_curr_hi"),
+    (PeakChannel::MtrPower, "mtr_w_lo", "mtr_w_hi"),
+];
+
+async fn render_peaks_page(display: &mut DisplayDevice, theme: &Theme, render_field_name: bool) {
+    let peaks = *PEAKS.lock().await;
+    for (channel, lo_field, hi_field) in PEAK_FIELDS {
+        let peak = peaks.get(channel);
+        let (min, max) = peak.map_or((0, 0), |peak| match channel {
+            // Power is tracked in mW, but shown in W like the power page
+            PeakChannel::FcPower | PeakChannel::MtrPower => {
+                (mw_to_w(peak.min).into(), mw_to_w(peak.max).into())
+            }
+            _ => (peak.min, peak.max),
+        });
+        let stale = peak.is_none();
+        render_can_value(lo_field, min, stale, render_field_name, display, theme).await;
+        render_can_value(hi_field, max, stale, render_field_name, display, theme).await;
+    }
+}
+
+/// Longest line of the CAN error state
+const BUS_STATE_LINE_CHARS: usize = "CAN: ".len() + CanBusState::Warning.name().len();
+
+/// Shows the CAN error state in the bottom left corner, e.g. "CAN: error-passive"
+fn render_bus_state(display: &mut DisplayDevice, theme: &Theme, state: CanBusState) {
+    let mut line: String<BUS_STATE_LINE_CHARS> = String::new();
+    let _ = line.push_str("CAN: ");
+    let _ = line.push_str(state.name());
+    // Pad on the right to erase a longer previous state
+    while line.push(' ').is_ok() {}
+    let color = match state {
+        CanBusState::Active => theme.accent,
+        CanBusState::Warning => theme.caution,
+        CanBusState::Passive | CanBusState::BusOff => theme.warning,
+    };
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(color)
+        .background_color(theme.background)
+        .build();
+    Text::with_text_style(
+        &line,
+        Anchor::BottomLeft.point(SCREEN),
+        style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Left)
+            .baseline(Baseline::Bottom)
+            .build(),
+    )
+    .draw(display)
+    .or_record();
+}
+
+/// The event log fills the right half of the diagnostics page, beside the rows
+const EVENT_LOG_AREA: Rectangle = Grid::new(2, 1).cell(1, 0);
+/// Top of the first event, below the offline nodes
+const EVENT_LOG_TOP: i32 = 20;
+/// Height of each event's line
+const EVENT_LINE_HEIGHT: i32 = 12;
+/// Longest event line, the uptime, a space and the event
+const EVENT_LINE_CHARS: usize = 8 + EVENT_CHARS;
+
+/// Generation of the event log last drawn, `None` if it needs to be redrawn
+static DRAWN_EVENTS: Mutex<ThreadModeRawMutex, Option<u32>> = Mutex::new(None);
+
+/// Lists the most recent events, newest first, e.g. "   123s CAN decode error"
+///
+/// Only drawn when an event is logged, or `redraw` is set after a clear.
+async fn render_event_log(display: &mut DisplayDevice, theme: &Theme, redraw: bool) {
+    let log = event_log();
+    let mut drawn = DRAWN_EVENTS.lock().await;
+    if !redraw && *drawn == Some(log.generation()) {
+        return;
+    }
+    *drawn = Some(log.generation());
+    drop(drawn);
+
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(theme.foreground)
+        .background_color(theme.background)
+        .build();
+    let mut events = log.recent();
+    for i in 0..EVENT_LOG_LEN {
+        let mut line: String<EVENT_LINE_CHARS> = String::new();
+        if let Some(event) = events.next() {
+            let mut secs = itoa::Buffer::new();
+            let secs = secs.format(event.secs);
+            for _ in secs.len()..6 {
+                let _ = line.push(' ');
+            }
+            let _ = line.push_str(secs);
+            let _ = line.push_str("s ");
+            let _ = line.push_str(&event.text);
+        }
+        // Pad to the full width to erase a longer previous event
+        while line.push(' ').is_ok() {}
+        Text::with_text_style(
+            &line,
+            Anchor::TopLeft.at(
+                EVENT_LOG_AREA,
+                Point::new(0, EVENT_LOG_TOP + i as i32 * EVENT_LINE_HEIGHT),
+            ),
+            style,
+            TextStyleBuilder::new().baseline(Baseline::Top).build(),
+        )
+        .draw(display)
+        .or_record();
+    }
+}
+
+async fn render_diagnostics_page(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    render_field_name: bool,
+) {
+    render_event_log(display, theme, render_field_name).await;
+    let bus = *CAN_BUS_STATUS.lock().await;
+    render_bus_state(display, theme, bus.state);
+    render_can_value(
+        "tec",
+        bus.tx_errors,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "rec",
+        bus.rx_errors,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "supply_mv",
+        SUPPLY_MV.load(Relaxed),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "can_errors",
+        CAN_ERROR_COUNT.load(Relaxed),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "tx_errors",
+        CAN_TX_ERROR_COUNT.load(Relaxed),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "can_restart",
+        CAN_RESTART_COUNT.load(Relaxed),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let stats = CAN_STATS.lock().await.snapshot(Instant::now());
+    render_can_value(
+        "frames_s",
+        stats.frames_per_second,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "frame_hz",
+        stats.frame_rate_hz,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "dec_err_s",
+        stats.errors_per_second,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "dec_errors",
+        stats.decode_errors,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "sfty_lat_us",
+        stats.safety_latency.max_us(),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "sfty_p99_us",
+        stats.safety_latency.percentile_us(990),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+    render_can_value(
+        "sfty_jit_us",
+        stats.safety_jitter.max_us(),
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let timebase = CAN_TIMEBASE.lock().await;
+    let corrections = timebase.corrections();
+    drop(timebase);
+    render_can_value(
+        "ts_fixes",
+        corrections,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    let source = *POWER_SOURCE.lock().await;
+    render_can_value(
+        "source",
+        source as u32,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+
+    render_can_value(
+        "uptime_s",
+        Instant::now().as_secs() as u32,
+        false,
+        render_field_name,
+        display,
+        theme,
+    )
+    .await;
+}
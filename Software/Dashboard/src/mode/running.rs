@@ -1,4 +1,4 @@
-use eg_seven_segment::SevenSegmentStyleBuilder;
+use eg_seven_segment::{SevenSegmentStyle, SevenSegmentStyleBuilder};
 use embedded_graphics::prelude::Transform;
 use embedded_graphics::prelude::WebColors;
 use embedded_graphics::primitives::PrimitiveStyle;
@@ -17,46 +17,30 @@ use super::init_running::{
     BATT_HEIGHT, BATT_POS, BATT_WIDTH, EFF_FONT_HEIGHT, EFF_FONT_WIDTH, EFF_POS, SPEED_FONT_HEIGHT,
     SPEED_FONT_WIDTH,
 };
-use crate::display_mod::{CENTER_POINT, DisplayDevice};
+use crate::display_mod::{
+    CENTER_POINT, DIRTY_REGIONS, DisplayDevice, DrawResultExt, SevenSegField, Theme, Widget,
+    WidgetSlot, mark_dirty,
+};
+use crate::snapshot_mod::snapshot;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
 
-fn greater_than_10(val: u32) -> bool {
-    val >= 10
-}
+const SPEED_DIGIT_SPACING: u32 = 4;
 
-fn render_speed_widgets(display: &mut DisplayDevice, speed: u32, prev_speed: u32) {
-    const DIGIT_SPACING: u32 = 4;
-    let speed_style = SevenSegmentStyleBuilder::new()
+// Placeholders, no board sends the speed, the motor RPM or the battery's state of charge yet.
+// Replace them with the packages' values once they are on the bus.
+const PLACEHOLDER_RPM: u32 = 1500;
+const PLACEHOLDER_SPEED: u32 = 20;
+const PLACEHOLDER_BATTERY_HEALTH: u8 = 50;
+
+fn speed_style(theme: &Theme) -> SevenSegmentStyle<Rgb666> {
+    SevenSegmentStyleBuilder::new()
         .digit_size(Size::new(SPEED_FONT_WIDTH, SPEED_FONT_HEIGHT))
-        .digit_spacing(DIGIT_SPACING)
+        .digit_spacing(SPEED_DIGIT_SPACING)
         .segment_width(6)
         .segment_color(Rgb666::RED)
-        .inactive_segment_color(Rgb666::BLACK)
-        .build();
-    let mut clear_style = speed_style.clone();
-    clear_style.set_text_color(Some(Rgb666::BLACK));
-
-    const SPEED_POS: Point = Point::new(
-        CENTER_POINT.x + SPEED_FONT_WIDTH as i32,
-        CENTER_POINT.y + SPEED_FONT_HEIGHT as i32 / 2,
-    );
-    const CLEAR_TEXT_POS: Point = Point::new(
-        CENTER_POINT.x - (DIGIT_SPACING as i32),
-        CENTER_POINT.y + SPEED_FONT_HEIGHT as i32 / 2,
-    );
-
-    let mut str_buffer = itoa::Buffer::new();
-    let speed_str = str_buffer.format(speed);
-
-    // Clear dead digits
-    if greater_than_10(prev_speed) && !greater_than_10(speed) {
-        Text::with_alignment("8", CLEAR_TEXT_POS, clear_style, Alignment::Right)
-            .draw(display)
-            .unwrap();
-    }
-    // Render Speed
-    Text::with_alignment(speed_str, SPEED_POS, speed_style, Alignment::Right)
-        .draw(display)
-        .unwrap();
+        .inactive_segment_color(theme.background)
+        .build()
 }
 
 fn render_tach_widgets(display: &mut DisplayDevice, rpm: u32, _prev_rpm: u32) {
@@ -92,7 +76,7 @@ fn render_tach_widgets(display: &mut DisplayDevice, rpm: u32, _prev_rpm: u32) {
         };
         bar.translate(Point::new(i * tach_line_width as i32 * tach_spacer, 0))
             .draw_styled(&bar_style, display)
-            .unwrap();
+            .or_record();
     }
     for i in (display_rpm + 1)..=max_tach_lines {
         let tach_line = if (i % tach_lines) == 0 {
@@ -103,21 +87,31 @@ fn render_tach_widgets(display: &mut DisplayDevice, rpm: u32, _prev_rpm: u32) {
         tach_line
             .translate(Point::new(i * tach_line_width as i32 * tach_spacer, 0))
             .draw_styled(&tach_empty_style, display)
-            .unwrap();
+            .or_record();
     }
 }
 
-fn render_efficiency_gui(display: &mut DisplayDevice, efficiency: u8, prev_efficiency: u8) {
+/// Boost converter efficiencies at or above this are shown in the accent color, lower ones in
+/// the warning color
+const EFFICIENCY_GOOD_PERCENT: u8 = 90;
+
+fn render_efficiency_gui(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    color: Rgb666,
+    efficiency: u8,
+    prev_efficiency: u8,
+) {
     const DIGIT_SPACING: u32 = 2;
     let eff_style = SevenSegmentStyleBuilder::new()
         .digit_size(Size::new(EFF_FONT_WIDTH, EFF_FONT_HEIGHT))
         .digit_spacing(DIGIT_SPACING)
         .segment_width(3)
-        .segment_color(Rgb666::GREEN)
-        .inactive_segment_color(Rgb666::BLACK)
+        .segment_color(color)
+        .inactive_segment_color(theme.background)
         .build();
     let mut clear_style = eff_style.clone();
-    clear_style.set_text_color(Some(Rgb666::BLACK));
+    clear_style.set_text_color(Some(theme.background));
 
     let mut str_buffer = itoa::Buffer::new();
     let efficiency_str = str_buffer.format(efficiency);
@@ -135,24 +129,29 @@ fn render_efficiency_gui(display: &mut DisplayDevice, efficiency: u8, prev_effic
     if prev_efficiency >= 100 && efficiency < 100 {
         Text::with_alignment("88", CLEAR_TEXT_POS, clear_style, Alignment::Right)
             .draw(display)
-            .unwrap();
+            .or_record();
     } else if prev_efficiency >= 10 && efficiency < 10 {
         Text::with_alignment("8", CLEAR_TEXT_POS, clear_style, Alignment::Right)
             .draw(display)
-            .unwrap();
+            .or_record();
     }
     // Render Efficiency
     Text::with_alignment(efficiency_str, EFF_TEXT_POS, eff_style, Alignment::Right)
         .draw(display)
-        .unwrap();
+        .or_record();
 }
 
-fn render_battery_gui(display: &mut DisplayDevice, battery_health: u8, prev_battery_health: u8) {
+fn render_battery_gui(
+    display: &mut DisplayDevice,
+    theme: &Theme,
+    battery_health: u8,
+    prev_battery_health: u8,
+) {
     let mut str_buffer = itoa::Buffer::new();
     let battery_health_str = str_buffer.format(battery_health);
 
-    let clear_style = PrimitiveStyle::with_fill(Rgb666::BLACK);
-    let fill_style = PrimitiveStyle::with_fill(Rgb666::GREEN);
+    let clear_style = PrimitiveStyle::with_fill(theme.background);
+    let fill_style = PrimitiveStyle::with_fill(theme.accent);
 
     const BATT_FONT_WIDTH: u32 = 10;
     const BATT_FONT_HEIGHT: u32 = 20;
@@ -162,11 +161,11 @@ fn render_battery_gui(display: &mut DisplayDevice, battery_health: u8, prev_batt
         .digit_size(Size::new(BATT_FONT_WIDTH, BATT_FONT_HEIGHT))
         .digit_spacing(DIGIT_SPACING)
         .segment_width(2)
-        .segment_color(Rgb666::WHITE)
-        .inactive_segment_color(Rgb666::BLACK)
+        .segment_color(theme.foreground)
+        .inactive_segment_color(theme.background)
         .build();
     let mut clear_text_style = batt_text_style.clone();
-    clear_text_style.set_text_color(Some(Rgb666::BLACK));
+    clear_text_style.set_text_color(Some(theme.background));
 
     const BATT_TEXT_POS: Point = Point::new(
         BATT_POS.x - 1 * (BATT_WIDTH / 2 + BATT_FONT_WIDTH) as i32,
@@ -186,18 +185,18 @@ fn render_battery_gui(display: &mut DisplayDevice, battery_health: u8, prev_batt
     );
 
     // Render Battery Rating
-    batt_outline.draw_styled(&clear_style, display).unwrap();
-    batt_fill.draw_styled(&fill_style, display).unwrap();
+    batt_outline.draw_styled(&clear_style, display).or_record();
+    batt_fill.draw_styled(&fill_style, display).or_record();
 
     // Clear Dead Digits
     if prev_battery_health >= 100 && battery_health < 100 {
         Text::with_alignment("88", CLEAR_TEXT_POS, clear_text_style, Alignment::Right)
             .draw(display)
-            .unwrap();
+            .or_record();
     } else if prev_battery_health >= 10 && battery_health < 10 {
         Text::with_alignment("8", CLEAR_TEXT_POS, clear_text_style, Alignment::Right)
             .draw(display)
-            .unwrap();
+            .or_record();
     }
     // Render Battery Percentage
     Text::with_alignment(
@@ -207,20 +206,154 @@ fn render_battery_gui(display: &mut DisplayDevice, battery_health: u8, prev_batt
         Alignment::Right,
     )
     .draw(display)
-    .unwrap();
+    .or_record();
+}
+
+/// Speed readout, redrawn when the speed changes, and then only the digits that changed
+struct SpeedWidget {
+    speed: u32,
+    field: SevenSegField<2>,
 }
+impl SpeedWidget {
+    /// Covers both digits, centered on the screen
+    const BOUNDS: Rectangle = Rectangle::new(
+        Point::new(
+            CENTER_POINT.x - SPEED_DIGIT_SPACING as i32 - SPEED_FONT_WIDTH as i32,
+            CENTER_POINT.y - SPEED_FONT_HEIGHT as i32 / 2,
+        ),
+        Size::new(
+            2 * SPEED_FONT_WIDTH + SPEED_DIGIT_SPACING,
+            SPEED_FONT_HEIGHT,
+        ),
+    );
+}
+impl Widget for SpeedWidget {
+    fn draw(&mut self, display: &mut DisplayDevice, theme: &Theme) {
+        self.field
+            .update(display, theme, speed_style(theme), self.speed);
+    }
+    fn bounds(&self) -> Option<Rectangle> {
+        Some(Self::BOUNDS)
+    }
+}
+
+/// Tachometer bars, redrawn every frame
+struct TachWidget {
+    rpm: u32,
+    prev_rpm: u32,
+}
+impl Widget for TachWidget {
+    fn draw(&mut self, display: &mut DisplayDevice, _theme: &Theme) {
+        render_tach_widgets(display, self.rpm, self.prev_rpm);
+        self.prev_rpm = self.rpm;
+    }
+}
+
+/// Boost converter efficiency readout, changes slowly so it is only redrawn once a second
+struct EfficiencyWidget {
+    /// Whole percent with the tenths dropped, `None` while the boost package is stale
+    efficiency: Option<u8>,
+    prev_efficiency: u8,
+}
+impl Widget for EfficiencyWidget {
+    fn draw(&mut self, display: &mut DisplayDevice, theme: &Theme) {
+        let (efficiency, color) = match self.efficiency {
+            Some(efficiency) if efficiency >= EFFICIENCY_GOOD_PERCENT => (efficiency, theme.accent),
+            Some(efficiency) => (efficiency, theme.warning),
+            None => (0, theme.muted),
+        };
+        render_efficiency_gui(display, theme, color, efficiency, self.prev_efficiency);
+        self.prev_efficiency = efficiency;
+    }
+    fn update_interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+/// Battery meter, changes slowly so it is only redrawn once a second
+struct BatteryWidget {
+    battery_health: u8,
+    prev_battery_health: u8,
+}
+impl Widget for BatteryWidget {
+    fn draw(&mut self, display: &mut DisplayDevice, theme: &Theme) {
+        render_battery_gui(
+            display,
+            theme,
+            self.battery_health,
+            self.prev_battery_health,
+        );
+        self.prev_battery_health = self.battery_health;
+    }
+    fn update_interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+struct RunningWidgets {
+    speed: WidgetSlot<SpeedWidget>,
+    tach: WidgetSlot<TachWidget>,
+    efficiency: WidgetSlot<EfficiencyWidget>,
+    battery: WidgetSlot<BatteryWidget>,
+}
+
+static RUNNING_WIDGETS: Mutex<ThreadModeRawMutex, RunningWidgets> = Mutex::new(RunningWidgets {
+    speed: WidgetSlot::new(SpeedWidget {
+        speed: 0,
+        field: SevenSegField::new(SpeedWidget::BOUNDS.top_left),
+    }),
+    tach: WidgetSlot::new(TachWidget {
+        rpm: 0,
+        prev_rpm: 0,
+    }),
+    efficiency: WidgetSlot::new(EfficiencyWidget {
+        efficiency: None,
+        prev_efficiency: 0,
+    }),
+    battery: WidgetSlot::new(BatteryWidget {
+        battery_health: 0,
+        prev_battery_health: 0,
+    }),
+});
+
+/// Forces every widget to be redrawn on the next frame, used after the screen is cleared
+pub async fn invalidate_running_gui() {
+    let mut widgets = RUNNING_WIDGETS.lock().await;
+    widgets.speed.invalidate();
+    widgets.speed.widget.field.invalidate();
+    widgets.tach.invalidate();
+    widgets.efficiency.invalidate();
+    widgets.battery.invalidate();
+}
+
+pub async fn render_running_gui(display: &mut DisplayDevice, theme: &Theme) {
+    // Taken before locking the widgets, so no package is locked while they are
+    let telemetry = snapshot().await;
+    let now = Instant::now();
+    let mut widgets = RUNNING_WIDGETS.lock().await;
+
+    ///////////////////////////////
+    // Update Widget Data
+    ///////////////////////////////
+    widgets.tach.widget.rpm = PLACEHOLDER_RPM;
+    let speed = PLACEHOLDER_SPEED;
+    if widgets.speed.widget.speed != speed {
+        widgets.speed.widget.speed = speed;
+        mark_dirty(SpeedWidget::BOUNDS).await;
+    }
+    let boost3 = &telemetry.boost3;
+    widgets.efficiency.widget.efficiency = (!telemetry.is_stale(boost3)).then(|| {
+        let (percent, _) = boost3.efficiency_percent();
+        percent.min(u8::MAX as u16) as u8
+    });
+    widgets.battery.widget.battery_health = PLACEHOLDER_BATTERY_HEALTH;
 
-pub fn render_running_gui(display: &mut DisplayDevice) {
     ///////////////////////////////
     // Render Graphics
     ///////////////////////////////
-    let prev_rpm = 1500;
-    let prev_speed = 20;
-
-    let rpm = 1500;
-    let speed = 20;
-    render_tach_widgets(display, rpm as u32, prev_rpm as u32);
-    render_speed_widgets(display, speed as u32, prev_speed as u32);
-    render_efficiency_gui(display, 50, 50);
-    render_battery_gui(display, 50, 50);
+    let dirty = DIRTY_REGIONS.lock().await;
+    widgets.tach.render(display, theme, now, &dirty);
+    widgets.speed.render(display, theme, now, &dirty);
+    widgets.efficiency.render(display, theme, now, &dirty);
+    widgets.battery.render(display, theme, now, &dirty);
 }
@@ -4,7 +4,7 @@ use embedded_graphics::{
     primitives::{PrimitiveStyle, Rectangle, StyledDrawable},
 };
 
-use crate::display_mod::{DISPLAY_HEIGHT, DISPLAY_WIDTH, DisplayDevice};
+use crate::display_mod::{DISPLAY_HEIGHT, DISPLAY_WIDTH, DisplayDevice, DrawResultExt};
 
 fn linear_gradient(
     start_color: Rgb666,
@@ -38,7 +38,7 @@ fn render_linear_gradient(
         );
         let column_style = PrimitiveStyle::with_fill(column_color);
 
-        column_rect.draw_styled(&column_style, display).unwrap();
+        column_rect.draw_styled(&column_style, display).or_record();
     }
 }
 
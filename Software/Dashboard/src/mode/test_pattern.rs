@@ -0,0 +1,148 @@
+//! Test patterns for checking the display for dead pixels and color accuracy
+//!
+//! Each press of button 2 advances to the next pattern. After the last pattern the display
+//! returns to the normal screen. Holding button 2 while the dashboard boots starts on the first
+//! pattern, for bringing up a display before anything else on the car is working.
+use defmt::Format;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::iso_8859_13::FONT_10X20;
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    pixelcolor::Rgb666,
+    prelude::{Point, RgbColor, Size},
+    primitives::{PrimitiveStyle, Rectangle, StyledDrawable},
+    text::{Alignment, Text},
+};
+
+use super::startup::render_startup_gui;
+use crate::display_mod::{DISPLAY_HEIGHT, DISPLAY_WIDTH, DisplayDevice, DrawResultExt};
+use crate::log_mod::info;
+
+/// A test pattern
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Full screen fields, for spotting dead pixels
+    Red,
+    Green,
+    Blue,
+    White,
+    Black,
+    /// Color gradient, for checking color rendering
+    Gradient,
+    /// Labelled red, green, and blue bars. If the bars do not match their labels, the Rgb666
+    /// bytes are sent to the display in the wrong channel order.
+    ColorBars,
+    /// A grid of lines with the screen edges outlined, for checking geometry and orientation
+    Crosshatch,
+}
+
+impl TestPattern {
+    /// Every pattern, in the order button 2 steps through them
+    pub const ALL: [TestPattern; 8] = [
+        TestPattern::Red,
+        TestPattern::Green,
+        TestPattern::Blue,
+        TestPattern::White,
+        TestPattern::Black,
+        TestPattern::Gradient,
+        TestPattern::ColorBars,
+        TestPattern::Crosshatch,
+    ];
+
+    /// The pattern after this one, `None` after the last
+    pub const fn next(self) -> Option<Self> {
+        let next = self as usize + 1;
+        if next < Self::ALL.len() {
+            Some(Self::ALL[next])
+        } else {
+            None
+        }
+    }
+}
+
+// ALL is in declaration order, which next relies on
+const _: () = {
+    let mut i = 0;
+    while i < TestPattern::ALL.len() {
+        assert!(TestPattern::ALL[i] as usize == i);
+        i += 1;
+    }
+};
+
+/// Draws a test pattern
+pub fn display_test_pattern(display: &mut DisplayDevice, pattern: TestPattern) {
+    info!("Test pattern: {}", pattern);
+    match pattern {
+        TestPattern::Red => display.clear(Rgb666::RED).or_record(),
+        TestPattern::Green => display.clear(Rgb666::GREEN).or_record(),
+        TestPattern::Blue => display.clear(Rgb666::BLUE).or_record(),
+        TestPattern::White => display.clear(Rgb666::WHITE).or_record(),
+        TestPattern::Black => display.clear(Rgb666::BLACK).or_record(),
+        TestPattern::Gradient => render_startup_gui(display),
+        TestPattern::ColorBars => render_channel_order(display),
+        TestPattern::Crosshatch => render_crosshatch(display),
+    }
+}
+
+/// Draws red, green, and blue bars labelled with the color they should appear as
+fn render_channel_order(display: &mut DisplayDevice) {
+    const BAR_WIDTH: u32 = DISPLAY_WIDTH / 3;
+    let label_style = MonoTextStyle::new(&FONT_10X20, Rgb666::BLACK);
+    let bars = [
+        ("RED", Rgb666::RED),
+        ("GREEN", Rgb666::GREEN),
+        ("BLUE", Rgb666::BLUE),
+    ];
+
+    for (i, (label, color)) in bars.into_iter().enumerate() {
+        let bar = Rectangle::new(
+            Point::new((BAR_WIDTH * i as u32) as i32, 0),
+            Size::new(BAR_WIDTH, DISPLAY_HEIGHT),
+        );
+        bar.draw_styled(&PrimitiveStyle::with_fill(color), display)
+            .or_record();
+        Text::with_alignment(label, bar.center(), label_style, Alignment::Center)
+            .draw(display)
+            .or_record();
+    }
+}
+
+/// Spacing of the crosshatch lines, divides both sides of the screen
+const CROSSHATCH_PITCH: u32 = 32;
+const _: () = assert!(
+    DISPLAY_WIDTH.is_multiple_of(CROSSHATCH_PITCH)
+        && DISPLAY_HEIGHT.is_multiple_of(CROSSHATCH_PITCH)
+);
+
+/// Draws white lines every [`CROSSHATCH_PITCH`] pixels on black, with the last row and column
+/// outlined too
+///
+/// A missing edge line means rows or columns are cut off, and the red square marks the top left
+/// corner, so a rotated or mirrored display shows it elsewhere.
+fn render_crosshatch(display: &mut DisplayDevice) {
+    let line = PrimitiveStyle::with_fill(Rgb666::WHITE);
+    display.clear(Rgb666::BLACK).or_record();
+
+    for x in (0..DISPLAY_WIDTH)
+        .step_by(CROSSHATCH_PITCH as usize)
+        .chain([DISPLAY_WIDTH - 1])
+    {
+        Rectangle::new(Point::new(x as i32, 0), Size::new(1, DISPLAY_HEIGHT))
+            .draw_styled(&line, display)
+            .or_record();
+    }
+    for y in (0..DISPLAY_HEIGHT)
+        .step_by(CROSSHATCH_PITCH as usize)
+        .chain([DISPLAY_HEIGHT - 1])
+    {
+        Rectangle::new(Point::new(0, y as i32), Size::new(DISPLAY_WIDTH, 1))
+            .draw_styled(&line, display)
+            .or_record();
+    }
+
+    let corner = CROSSHATCH_PITCH - 1;
+    Rectangle::new(Point::new(1, 1), Size::new(corner, corner))
+        .draw_styled(&PrimitiveStyle::with_fill(Rgb666::RED), display)
+        .or_record();
+}
@@ -0,0 +1,55 @@
+//! The touch calibration screen
+//!
+//! Shows one of [`CALIBRATION_TARGETS`](crate::touch_mod::CALIBRATION_TARGETS) at a time, as a
+//! crosshair for the driver to touch, see [`CalibrationRun`](crate::touch_mod::CalibrationRun).
+use core::fmt::Write;
+
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    mono_font::{MonoTextStyle, iso_8859_1::FONT_10X20},
+    prelude::{Point, Size},
+    primitives::{PrimitiveStyle, Rectangle, StyledDrawable},
+    text::{Alignment, Text},
+};
+use heapless::String;
+
+use crate::display_mod::{CENTER_POINT, DisplayDevice, DrawResultExt, Theme};
+use crate::touch_mod::CALIBRATION_TARGETS;
+
+/// Length of each arm of the crosshair, either side of the target
+const ARM: u32 = 12;
+
+/// Clears the screen, and draws the crosshair for target `step` at `target`
+pub fn render_touch_target(display: &mut DisplayDevice, theme: &Theme, target: Point, step: usize) {
+    display.clear(theme.background).or_record();
+
+    let line = PrimitiveStyle::with_fill(theme.foreground);
+    let across = Size::new(ARM * 2 + 1, 1);
+    let down = Size::new(1, ARM * 2 + 1);
+    for bar in [
+        Rectangle::with_center(target, across),
+        Rectangle::with_center(target, down),
+    ] {
+        bar.draw_styled(&line, display).or_record();
+    }
+
+    let mut prompt: String<32> = String::new();
+    let _ = write!(
+        prompt,
+        "Touch the target {}/{}",
+        step + 1,
+        CALIBRATION_TARGETS.len()
+    );
+    let style = MonoTextStyle::new(&FONT_10X20, theme.foreground);
+    for (text, offset) in [(prompt.as_str(), -12), ("Button 2 to cancel", 12)] {
+        Text::with_alignment(
+            text,
+            CENTER_POINT + Point::new(0, offset),
+            style,
+            Alignment::Center,
+        )
+        .draw(display)
+        .or_record();
+    }
+}
@@ -0,0 +1,141 @@
+//! Module for CAN node supervision
+//!
+//! Groups the received packages by the board that sends them. A node is offline when none of
+//! its packages has been received within [`NODE_TIMEOUT`], so the display can show that the
+//! board is gone, rather than a page of stale values.
+//!
+//! Nodes start offline, and come online with the first package received from them.
+
+use core::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+use defmt::Format;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::can_mod::package_last_seen;
+use crate::eco_can::{
+    ECOCAN_H2Pack1_t, ECOCAN_H2Pack2_t, ECOCAN_RelPackChrg_t, FDCAN_BOOSTPack1_t,
+    FDCAN_BOOSTPack2_t, FDCAN_BOOSTPack3_t, FDCAN_FccPack1_t, FDCAN_FccPack2_t, FDCAN_FccPack3_t,
+    FDCAN_RelPackCap_t, FDCAN_RelPackFc_t, FDCAN_RelPackMtr_t, FDCAN_RelPackNrg_t, FDCANPack,
+};
+use crate::log_mod::{info, warn};
+
+/// A node is offline once none of its packages has been received for this long
+pub const NODE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the nodes are checked
+pub const NODE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A board on the bus that the dashboard receives packages from
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum CanNode {
+    Fcc,
+    Relay,
+    Boost,
+    H2,
+}
+
+impl CanNode {
+    pub const ALL: [CanNode; 4] = [CanNode::Fcc, CanNode::Relay, CanNode::Boost, CanNode::H2];
+
+    /// Short name shown on the display
+    pub const fn name(self) -> &'static str {
+        match self {
+            CanNode::Fcc => "FCC",
+            CanNode::Relay => "RELAY",
+            CanNode::Boost => "BOOST",
+            CanNode::H2 => "H2",
+        }
+    }
+
+    /// The IDs of the packages the node sends
+    pub const fn package_ids(self) -> &'static [u32] {
+        NODE_PACKAGES[self as usize].1
+    }
+}
+
+/// The packages sent by each node, in the order of [`CanNode::ALL`]
+pub const NODE_PACKAGES: [(CanNode, &[u32]); CanNode::ALL.len()] = [
+    (
+        CanNode::Fcc,
+        &[
+            FDCAN_FccPack1_t::FDCAN_ID,
+            FDCAN_FccPack2_t::FDCAN_ID,
+            FDCAN_FccPack3_t::FDCAN_ID,
+        ],
+    ),
+    (
+        CanNode::Relay,
+        &[
+            FDCAN_RelPackMtr_t::FDCAN_ID,
+            FDCAN_RelPackCap_t::FDCAN_ID,
+            FDCAN_RelPackFc_t::FDCAN_ID,
+            FDCAN_RelPackNrg_t::FDCAN_ID,
+            ECOCAN_RelPackChrg_t::FDCAN_ID,
+        ],
+    ),
+    (
+        CanNode::Boost,
+        &[
+            FDCAN_BOOSTPack1_t::FDCAN_ID,
+            FDCAN_BOOSTPack2_t::FDCAN_ID,
+            FDCAN_BOOSTPack3_t::FDCAN_ID,
+        ],
+    ),
+    (
+        CanNode::H2,
+        &[ECOCAN_H2Pack1_t::FDCAN_ID, ECOCAN_H2Pack2_t::FDCAN_ID],
+    ),
+];
+
+// The table is indexed by node
+const _: () = {
+    let mut i = 0;
+    while i < NODE_PACKAGES.len() {
+        assert!(NODE_PACKAGES[i].0 as usize == i);
+        i += 1;
+    }
+};
+
+/// True while a node is offline, indexed by [`CanNode`]
+static NODE_OFFLINE: [AtomicBool; CanNode::ALL.len()] = [
+    AtomicBool::new(true),
+    AtomicBool::new(true),
+    AtomicBool::new(true),
+    AtomicBool::new(true),
+];
+
+/// Returns true if none of the node's packages has been received within [`NODE_TIMEOUT`]
+pub fn is_offline(node: CanNode) -> bool {
+    NODE_OFFLINE[node as usize].load(Relaxed)
+}
+
+/// Time the node last sent any of its packages, `None` if it has never been heard from
+async fn node_last_seen(node: CanNode) -> Option<Instant> {
+    let mut last_seen = None;
+    for &id in node.package_ids() {
+        if let Some(Some(seen)) = package_last_seen(id).await {
+            last_seen = last_seen.max(Some(seen));
+        }
+    }
+    last_seen
+}
+
+/// Updates the offline flag of every node
+#[embassy_executor::task]
+pub async fn node_supervisor_task() {
+    loop {
+        let now = Instant::now();
+        for node in CanNode::ALL {
+            let offline = node_last_seen(node)
+                .await
+                .is_none_or(|seen| now.saturating_duration_since(seen) > NODE_TIMEOUT);
+            if NODE_OFFLINE[node as usize].swap(offline, Relaxed) != offline {
+                if offline {
+                    warn!("{} offline", node);
+                } else {
+                    info!("{} online", node);
+                }
+            }
+        }
+        Timer::after(NODE_CHECK_INTERVAL).await;
+    }
+}
@@ -0,0 +1,146 @@
+//! Module for peak values
+//!
+//! Tracks the lowest and highest value of a fixed set of telemetry channels, such as the peak
+//! motor current or the lowest fuel cell voltage, since the peaks were last reset. They are
+//! recorded as each package is decoded, so nothing is lost between screen refreshes.
+//!
+//! The peaks are shown on the peaks page, double press button 2 there to reset them.
+
+use defmt::Format;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+
+use crate::eco_can::{
+    FDCAN_FccPack1_t, FDCAN_RelPackCap_t, FDCAN_RelPackFc_t, FDCAN_RelPackMtr_t, signed_current_ma,
+    unsigned_current_ma,
+};
+use crate::log_mod::info;
+use crate::power_mod::{fc_power_mw, mtr_power_mw};
+
+/// A tracked channel, values are in the units sent on the bus
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum PeakChannel {
+    /// Fuel cell voltage, in mV
+    FcVolt,
+    /// Fuel cell current, in mA
+    FcCurr,
+    /// Fuel cell power, in mW
+    FcPower,
+    /// Fuel cell temperature, in 0.01 °C
+    FcTemp,
+    /// Supercapacitor voltage, in mV
+    CapVolt,
+    /// Supercapacitor current, in mA, negative while charging
+    CapCurr,
+    /// Motor current, in mA
+    MtrCurr,
+    /// Motor power, in mW
+    MtrPower,
+}
+
+impl PeakChannel {
+    /// Number of channels, `MtrPower` must stay the last
+    pub const COUNT: usize = PeakChannel::MtrPower as usize + 1;
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The lowest and highest value recorded on a channel
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub struct Peak {
+    pub min: i64,
+    pub max: i64,
+}
+
+/// The peaks of every [`PeakChannel`] since the last reset
+///
+/// Each peak is `None` until its channel is received after a reset.
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub struct PeakTracker {
+    peaks: [Option<Peak>; PeakChannel::COUNT],
+}
+
+impl PeakTracker {
+    pub const fn new() -> Self {
+        Self {
+            peaks: [None; PeakChannel::COUNT],
+        }
+    }
+
+    /// Clears every peak, they start again from the next packages received
+    pub fn reset(&mut self) {
+        *self = Self::new();
+        info!("Peaks reset");
+    }
+
+    /// Records a value, widening the channel's peak if it falls outside it
+    pub fn record(&mut self, channel: PeakChannel, value: i64) {
+        let peak = &mut self.peaks[channel.index()];
+        match peak {
+            Some(peak) => {
+                peak.min = peak.min.min(value);
+                peak.max = peak.max.max(value);
+            }
+            None => {
+                *peak = Some(Peak {
+                    min: value,
+                    max: value,
+                })
+            }
+        }
+    }
+
+    /// The channel's peak, `None` if it hasn't been received since the last reset
+    pub fn get(&self, channel: PeakChannel) -> Option<Peak> {
+        self.peaks[channel.index()]
+    }
+
+    /// Every channel's peak, in the order of [`PeakChannel`]
+    pub const fn peaks(&self) -> [Option<Peak>; PeakChannel::COUNT] {
+        self.peaks
+    }
+
+    /// A tracker continuing from peaks recorded earlier, see [`peaks`](Self::peaks)
+    pub const fn from_peaks(peaks: [Option<Peak>; PeakChannel::COUNT]) -> Self {
+        Self { peaks }
+    }
+}
+
+impl Default for PeakTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub static PEAKS: Mutex<ThreadModeRawMutex, PeakTracker> = Mutex::new(PeakTracker::new());
+
+/// Records the fuel cell's voltage, current and power from a received package
+pub async fn record_fc_peaks(fc: &FDCAN_RelPackFc_t) {
+    let mut peaks = PEAKS.lock().await;
+    peaks.record(PeakChannel::FcVolt, i64::from(fc.fc_volt));
+    peaks.record(PeakChannel::FcCurr, unsigned_current_ma(fc.fc_curr));
+    peaks.record(PeakChannel::FcPower, fc_power_mw(fc));
+}
+
+/// Records the fuel cell temperature from a received package
+pub async fn record_fcc_peaks(fcc: &FDCAN_FccPack1_t) {
+    PEAKS
+        .lock()
+        .await
+        .record(PeakChannel::FcTemp, i64::from(fcc.fc_temp));
+}
+
+/// Records the supercapacitors' voltage and current from a received package
+pub async fn record_cap_peaks(cap: &FDCAN_RelPackCap_t) {
+    let mut peaks = PEAKS.lock().await;
+    peaks.record(PeakChannel::CapVolt, i64::from(cap.cap_volt));
+    peaks.record(PeakChannel::CapCurr, signed_current_ma(cap.cap_curr));
+}
+
+/// Records the motor's current and power from a received package
+pub async fn record_mtr_peaks(mtr: &FDCAN_RelPackMtr_t) {
+    let mut peaks = PEAKS.lock().await;
+    peaks.record(PeakChannel::MtrCurr, unsigned_current_ma(mtr.mtr_curr));
+    peaks.record(PeakChannel::MtrPower, mtr_power_mw(mtr));
+}
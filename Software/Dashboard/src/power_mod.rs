@@ -0,0 +1,137 @@
+//! Module for power calculations
+//!
+//! Power is not sent on the bus, it is calculated from the voltage and current packages from
+//! the relay board and the battery. Voltages are sent in mV and currents in mA, so their product
+//! is in µW. The maths is done in integers, so the result is exact up to the final rounding.
+//!
+//! The capacitor current is signed, so capacitor power is positive when `cap_curr` is positive
+//! and negative when it is reversed.
+
+use crate::eco_can::{
+    FDCAN_BATTPack2_t, FDCAN_RelPackCap_t, FDCAN_RelPackFc_t, FDCAN_RelPackMtr_t,
+    saturating_sum_ma, signed_current_ma, unsigned_current_ma,
+};
+
+/// Power in mW from a voltage in mV and a current in mA, rounded towards zero
+///
+/// The product of two full range fields overflows an `i64`, so it is done in an `i128`. Dividing
+/// by 1000 always brings it back in range.
+pub const fn power_mw(volt_mv: u32, curr_ma: i64) -> i64 {
+    (volt_mv as i128 * curr_ma as i128 / 1_000) as i64
+}
+
+/// Power in whole watts, rounded to the nearest watt and saturated to fit an `i32`
+pub const fn mw_to_w(power_mw: i64) -> i32 {
+    let watts = if power_mw >= 0 {
+        (power_mw + 500) / 1_000
+    } else {
+        (power_mw - 500) / 1_000
+    };
+    if watts > i32::MAX as i64 {
+        i32::MAX
+    } else if watts < i32::MIN as i64 {
+        i32::MIN
+    } else {
+        watts as i32
+    }
+}
+
+/// Power delivered by the fuel cell, in mW
+pub const fn fc_power_mw(fc: &FDCAN_RelPackFc_t) -> i64 {
+    power_mw(fc.fc_volt, unsigned_current_ma(fc.fc_curr))
+}
+
+/// Power through the supercapacitors, in mW
+pub const fn cap_power_mw(cap: &FDCAN_RelPackCap_t) -> i64 {
+    power_mw(cap.cap_volt, signed_current_ma(cap.cap_curr))
+}
+
+/// Power drawn by the motor, in mW
+pub const fn mtr_power_mw(mtr: &FDCAN_RelPackMtr_t) -> i64 {
+    power_mw(mtr.mtr_volt, unsigned_current_ma(mtr.mtr_curr))
+}
+
+/// Power drawn from the battery, in mW
+pub const fn batt_power_mw(batt: &FDCAN_BATTPack2_t) -> i64 {
+    power_mw(
+        batt.out_volt as u32,
+        unsigned_current_ma(batt.out_curr as u32),
+    )
+}
+
+/// Current supplied by the fuel cell and the capacitors, in mA
+///
+/// The capacitor current is negative while they charge, so this is what reaches the motor.
+pub const fn supply_current_ma(fc: &FDCAN_RelPackFc_t, cap: &FDCAN_RelPackCap_t) -> i64 {
+    saturating_sum_ma(&[
+        unsigned_current_ma(fc.fc_curr),
+        signed_current_ma(cap.cap_curr),
+    ])
+}
+
+/// Fuel cell power left over after the motor, in mW
+///
+/// Positive when the fuel cell makes more than the motor uses, negative when the
+/// supercapacitors make up the difference.
+pub const fn net_power_mw(fc: &FDCAN_RelPackFc_t, mtr: &FDCAN_RelPackMtr_t) -> i64 {
+    fc_power_mw(fc) - mtr_power_mw(mtr)
+}
+
+// Scaling, signs, and the full range of the packages
+const _: () = {
+    // 48 V at 2.5 A is 120 W
+    let fc = FDCAN_RelPackFc_t {
+        fc_volt: 48_000,
+        fc_curr: 2_500,
+    };
+    assert!(fc_power_mw(&fc) == 120_000);
+    assert!(mw_to_w(fc_power_mw(&fc)) == 120);
+
+    // 20 V at -1.5 A is -30 W
+    let cap = FDCAN_RelPackCap_t {
+        cap_volt: 20_000,
+        cap_curr: -1_500,
+    };
+    assert!(cap_power_mw(&cap) == -30_000);
+    assert!(mw_to_w(cap_power_mw(&cap)) == -30);
+
+    let mtr = FDCAN_RelPackMtr_t {
+        mtr_volt: 48_000,
+        mtr_curr: 3_000,
+    };
+    assert!(net_power_mw(&fc, &mtr) == -24_000);
+
+    // 12.6 V at 0.8 A is about 10 W
+    let batt = FDCAN_BATTPack2_t {
+        out_curr: 800,
+        out_volt: 12_600,
+    };
+    assert!(batt_power_mw(&batt) == 10_080);
+    assert!(mw_to_w(batt_power_mw(&batt)) == 10);
+    // The full range of both fields fits easily
+    let batt = FDCAN_BATTPack2_t {
+        out_curr: u16::MAX,
+        out_volt: u16::MAX,
+    };
+    assert!(batt_power_mw(&batt) == 4_294_836);
+
+    // Regen charges the capacitors, so their power and share of the supply current are negative
+    let regen = FDCAN_RelPackCap_t {
+        cap_volt: 40_000,
+        cap_curr: -4_000,
+    };
+    assert!(cap_power_mw(&regen) < 0 && mw_to_w(cap_power_mw(&regen)) == -160);
+    assert!(supply_current_ma(&fc, &regen) == -1_500);
+    // A fuel cell current above i32::MAX doesn't wrap negative
+    let big_fc = FDCAN_RelPackFc_t {
+        fc_volt: 1_000,
+        fc_curr: u32::MAX,
+    };
+    assert!(fc_power_mw(&big_fc) == u32::MAX as i64);
+
+    // Rounds to the nearest watt, and the largest values don't overflow
+    assert!(mw_to_w(1_499) == 1 && mw_to_w(1_500) == 2 && mw_to_w(-1_500) == -2);
+    assert!(power_mw(u32::MAX, u32::MAX as i64) == 18_446_744_065_119_617);
+    assert!(power_mw(u32::MAX, i32::MIN as i64) == -9_223_372_034_707_292);
+    assert!(mw_to_w(i64::MAX / 2) == i32::MAX && mw_to_w(i64::MIN / 2) == i32::MIN);
+};
@@ -0,0 +1,79 @@
+//! Module for rate limiting per-ID logs
+//!
+//! A busy bus can carry many IDs the dashboard doesn't decode, and logging every one of their
+//! frames floods the RTT channel and stalls the receive task. [`IdRateLimiter`] lets each ID log
+//! at most once per [`ID_LOG_INTERVAL`], and counts the frames it held back in between.
+//!
+//! An ID seen for the first time logs immediately. Only the [`TRACKED_IDS`] most recently seen
+//! IDs are remembered, the least recently seen is forgotten to make room for a new one, so an ID
+//! that comes back after being forgotten logs immediately again.
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+
+/// Shortest time between two logs of the same ID
+pub const ID_LOG_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of IDs remembered by [`UNKNOWN_ID_LOG`]
+pub const TRACKED_IDS: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+struct TrackedId {
+    id: u32,
+    /// When the ID was last logged
+    logged: Instant,
+    /// When the ID was last seen, for eviction
+    seen: Instant,
+    /// Frames held back since the last log
+    suppressed: u32,
+}
+
+/// Decides which frames of each ID are logged, remembering up to `N` IDs
+pub struct IdRateLimiter<const N: usize> {
+    ids: Vec<TrackedId, N>,
+}
+
+impl<const N: usize> IdRateLimiter<N> {
+    pub const fn new() -> Self {
+        Self { ids: Vec::new() }
+    }
+
+    /// Records a frame with `id`, returns `Some` if it should be logged
+    ///
+    /// The value is the number of frames with `id` held back since it was last logged.
+    pub fn check(&mut self, id: u32, now: Instant) -> Option<u32> {
+        if let Some(tracked) = self.ids.iter_mut().find(|tracked| tracked.id == id) {
+            tracked.seen = now;
+            if now.saturating_duration_since(tracked.logged) < ID_LOG_INTERVAL {
+                tracked.suppressed = tracked.suppressed.saturating_add(1);
+                return None;
+            }
+            tracked.logged = now;
+            return Some(core::mem::take(&mut tracked.suppressed));
+        }
+
+        let tracked = TrackedId {
+            id,
+            logged: now,
+            seen: now,
+            suppressed: 0,
+        };
+        if let Err(tracked) = self.ids.push(tracked) {
+            // Full, replace the least recently seen ID
+            if let Some(oldest) = self.ids.iter_mut().min_by_key(|tracked| tracked.seen) {
+                *oldest = tracked;
+            }
+        }
+        Some(0)
+    }
+}
+
+impl<const N: usize> Default for IdRateLimiter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Limits the log of IDs received that the dashboard doesn't decode
+pub static UNKNOWN_ID_LOG: Mutex<ThreadModeRawMutex, IdRateLimiter<TRACKED_IDS>> =
+    Mutex::new(IdRateLimiter::new());
@@ -0,0 +1,107 @@
+//! Module for the safe state the dashboard enters on a critical fault
+//!
+//! A critical fault calls [`enter_safe_state`], which latches the fault until the driver clears
+//! it or the car is power cycled. While latched:
+//! - The display shows a full screen red banner naming the fault
+//! - Every LED flashes red
+//! - The relay board is commanded to standby, repeated until the safe state is cleared, and
+//!   button 1 can't request another relay state
+//!
+//! The display and LED tasks read the latch every frame. [`SAFE_STATE_SIGNAL`] wakes the
+//! transmit task, so the standby command goes out straight away rather than on its next period.
+//!
+//! The faults that trigger it are:
+//!
+//! | Fault                   | Trips when                                            |
+//! |-------------------------|-------------------------------------------------------|
+//! | [`Fault::H2Alarm`]      | The H2 board broadcasts a tripped alarm               |
+//! | [`Fault::Overtemp`]     | A channel reaches its critical [`ThermalLimit`]       |
+//!
+//! Double press button 2 to clear it. An over temperature can only be cleared once every
+//! channel has cooled below critical.
+//!
+//! [`ThermalLimit`]: crate::warning_mod::ThermalLimit
+
+use defmt::Format;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+
+use crate::can_mod::{clear_h2_alarm, h2_alarm_tripped};
+use crate::event_log_mod::log_event;
+use crate::log_mod::{error, info, warn};
+use crate::warning_mod::{ThermalChannel, ThermalLevel, channel_thermal_level};
+
+/// A fault that puts the dashboard in the safe state
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum Fault {
+    /// The H2 board's alarm tripped
+    H2Alarm,
+    /// A temperature reached its critical limit
+    Overtemp(ThermalChannel),
+}
+
+impl Fault {
+    /// Shown on the safe state banner
+    pub const fn name(self) -> &'static str {
+        match self {
+            Fault::H2Alarm => "H2 ALARM",
+            Fault::Overtemp(_) => "OVERTEMP",
+        }
+    }
+
+    /// Shown in the on-screen event log when the safe state is entered
+    const fn event(self) -> &'static str {
+        match self {
+            Fault::H2Alarm => "Safe state: H2 alarm",
+            Fault::Overtemp(ThermalChannel::FcTemp) => "Safe state: fuel cell overtemp",
+            Fault::Overtemp(ThermalChannel::FccBmeTemp) => "Safe state: FCC overtemp",
+            Fault::Overtemp(ThermalChannel::H2BmeTemp) => "Safe state: H2 board overtemp",
+        }
+    }
+}
+
+/// The fault that put the dashboard in the safe state, `None` outside of it
+///
+/// Only the first fault is kept, a later one doesn't replace it.
+pub static SAFE_STATE: Mutex<ThreadModeRawMutex, Option<Fault>> = Mutex::new(None);
+
+/// Signalled when the safe state is entered
+pub static SAFE_STATE_SIGNAL: Signal<ThreadModeRawMutex, Fault> = Signal::new();
+
+/// Latches `fault` and enters the safe state, if not already in it
+pub async fn enter_safe_state(fault: Fault) {
+    let mut latched = SAFE_STATE.lock().await;
+    if latched.is_some() {
+        return;
+    }
+    *latched = Some(fault);
+    drop(latched);
+    error!("Entering the safe state: {}", fault);
+    log_event(fault.event());
+    SAFE_STATE_SIGNAL.signal(fault);
+}
+
+/// The latched fault, `None` outside of the safe state
+pub async fn safe_state() -> Option<Fault> {
+    *SAFE_STATE.lock().await
+}
+
+/// Leaves the safe state, and clears the H2 alarm
+///
+/// Refused with the fault that is still present while a temperature is critical.
+pub async fn clear_safe_state() -> Result<(), Fault> {
+    if let Some(channel) = ThermalChannel::ALL
+        .into_iter()
+        .find(|&channel| channel_thermal_level(channel) == ThermalLevel::Critical)
+    {
+        warn!("Safe state not cleared, {} is still critical", channel);
+        return Err(Fault::Overtemp(channel));
+    }
+    if h2_alarm_tripped().await {
+        clear_h2_alarm().await;
+    }
+    SAFE_STATE_SIGNAL.reset();
+    if SAFE_STATE.lock().await.take().is_some() {
+        info!("Safe state cleared");
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Module for replaying a recorded bus session
+//!
+//! Enabled with the `sim` feature. Instead of reading the CAN bus, `can_receive_task` replays
+//! [`BUS_LOG`] on a timer, one frame every [`FRAME_INTERVAL`], and loops when it reaches the
+//! end. The frames go through the same decode path as received frames, so the display, LEDs,
+//! warnings and trip meter all react as they would in the car, without the rest of the car
+//! powered up.
+//!
+//! The session steps through standby, startup, charging and running, then runs with a low fuel
+//! cell voltage to raise the warning. Each package is repeated well within
+//! [`STALE_AFTER`](crate::can_mod::STALE_AFTER), so nothing shows as stale.
+//!
+//! ```sh
+//! cargo run --features sim
+//! ```
+
+use embassy_stm32::can::frame::FdFrame;
+use embassy_time::{Duration, Timer};
+
+use crate::can_mod::process_rx_can_frame;
+use crate::eco_can::{
+    ECOCAN_H2Pack1_t, FDCAN_FccPack1_t, FDCAN_FccPack2_t, FDCAN_RelPackCap_t, FDCAN_RelPackFc_t,
+    FDCAN_RelPackMtr_t, FDCANPack, RelayState,
+};
+use crate::log_mod::{error, info};
+use crate::watchdog_mod::{CriticalTask, check_in};
+
+/// Time between replayed frames
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Two big-endian 32 bit fields, the layout of most 8 byte packages
+const fn words(first: u32, second: u32) -> [u8; 8] {
+    let (a, b) = (first.to_be_bytes(), second.to_be_bytes());
+    [a[0], a[1], a[2], a[3], b[0], b[1], b[2], b[3]]
+}
+
+/// Four big-endian 16 bit fields
+const fn halves(fields: [u16; 4]) -> [u8; 8] {
+    let [a, b, c, d] = [
+        fields[0].to_be_bytes(),
+        fields[1].to_be_bytes(),
+        fields[2].to_be_bytes(),
+        fields[3].to_be_bytes(),
+    ];
+    [a[0], a[1], b[0], b[1], c[0], c[1], d[0], d[1]]
+}
+
+const RELAY: u32 = RelayState::FDCAN_ID;
+const STBY: &[u8] = &[RelayState::RELAY_STBY as u8];
+const STRTP: &[u8] = &[RelayState::RELAY_STRTP as u8];
+const CHRGE: &[u8] = &[RelayState::RELAY_CHRGE as u8];
+const RUN: &[u8] = &[RelayState::RELAY_RUN as u8];
+const FC: u32 = FDCAN_RelPackFc_t::FDCAN_ID;
+const CAP: u32 = FDCAN_RelPackCap_t::FDCAN_ID;
+const MTR: u32 = FDCAN_RelPackMtr_t::FDCAN_ID;
+const FCC1: u32 = FDCAN_FccPack1_t::FDCAN_ID;
+const FCC2: u32 = FDCAN_FccPack2_t::FDCAN_ID;
+const H2: u32 = ECOCAN_H2Pack1_t::FDCAN_ID;
+
+/// The recorded session, `(id, data)` in the order the frames were received
+///
+/// Voltages are in mV, currents in mA, the fuel cell temperature in hundredths of a degree.
+#[rustfmt::skip]
+pub const BUS_LOG: &[(u32, &[u8])] = &[
+    // Standby, supercapacitors partly charged
+    (RELAY, STBY),
+    (FC, &words(0, 0)),
+    (CAP, &words(12_000, 0)),
+    (MTR, &words(0, 0)),
+    (FCC1, &words(22_00, 0)),
+    (FCC2, &words(0, 0)),
+    (H2, &halves([10, 12, 11, 10])),
+
+    // Startup, the fuel cell comes up
+    (RELAY, STRTP),
+    (FC, &words(28_000, 0)),
+    (CAP, &words(12_000, 0)),
+    (MTR, &words(0, 0)),
+    (FCC1, &words(24_00, 0)),
+    (FCC2, &words(2_000, 2_000)),
+    (H2, &halves([10, 12, 11, 10])),
+
+    // Charging the supercapacitors, negative current flows into them
+    (RELAY, CHRGE),
+    (FC, &words(30_000, 3_000)),
+    (CAP, &words(15_000, -2_800_i32 as u32)),
+    (MTR, &words(0, 0)),
+    (FCC1, &words(30_00, 0)),
+    (FCC2, &words(4_000, 4_000)),
+    (H2, &halves([11, 12, 11, 10])),
+
+    (RELAY, CHRGE),
+    (FC, &words(30_000, 3_000)),
+    (CAP, &words(20_000, -2_800_i32 as u32)),
+    (MTR, &words(0, 0)),
+    (FCC1, &words(34_00, 0)),
+    (FCC2, &words(4_500, 4_500)),
+    (H2, &halves([11, 12, 11, 10])),
+
+    (RELAY, CHRGE),
+    (FC, &words(30_000, 2_000)),
+    (CAP, &words(25_000, -1_800_i32 as u32)),
+    (MTR, &words(0, 0)),
+    (FCC1, &words(38_00, 0)),
+    (FCC2, &words(5_000, 5_000)),
+    (H2, &halves([11, 12, 11, 10])),
+
+    // Running, the motor current rises as the car accelerates
+    (RELAY, RUN),
+    (FC, &words(29_000, 4_000)),
+    (CAP, &words(26_000, 1_000)),
+    (MTR, &words(24_000, 5_000)),
+    (FCC1, &words(42_00, 0)),
+    (FCC2, &words(6_000, 6_000)),
+    (H2, &halves([12, 13, 12, 11])),
+
+    (RELAY, RUN),
+    (FC, &words(28_500, 4_500)),
+    (CAP, &words(25_000, 3_500)),
+    (MTR, &words(24_000, 8_000)),
+    (FCC1, &words(45_00, 0)),
+    (FCC2, &words(6_500, 6_500)),
+    (H2, &halves([12, 13, 12, 11])),
+
+    (RELAY, RUN),
+    (FC, &words(28_000, 5_000)),
+    (CAP, &words(23_000, 7_000)),
+    (MTR, &words(24_000, 12_000)),
+    (FCC1, &words(48_00, 0)),
+    (FCC2, &words(7_000, 7_000)),
+    (H2, &halves([12, 13, 12, 11])),
+
+    // Running with the fuel cell voltage sagging below the low voltage warning
+    (RELAY, RUN),
+    (FC, &words(19_500, 6_000)),
+    (CAP, &words(21_000, 6_000)),
+    (MTR, &words(24_000, 12_000)),
+    (FCC1, &words(50_00, 0)),
+    (FCC2, &words(7_500, 7_500)),
+    (H2, &halves([12, 13, 12, 11])),
+
+    (RELAY, RUN),
+    (FC, &words(19_000, 6_000)),
+    (CAP, &words(19_000, 6_000)),
+    (MTR, &words(24_000, 10_000)),
+    (FCC1, &words(51_00, 0)),
+    (FCC2, &words(7_500, 7_500)),
+    (H2, &halves([12, 13, 12, 11])),
+];
+
+// Every frame fits a classic CAN frame with a standard ID
+const _: () = {
+    let mut i = 0;
+    while i < BUS_LOG.len() {
+        let (id, data) = BUS_LOG[i];
+        assert!(id <= 0x7FF && data.len() <= 8);
+        i += 1;
+    }
+};
+
+/// Replays [`BUS_LOG`] forever, in place of reading the bus
+pub async fn replay_bus_log() -> ! {
+    info!(
+        "Replaying a {} frame bus log, one frame every {} ms",
+        BUS_LOG.len(),
+        FRAME_INTERVAL.as_millis()
+    );
+    loop {
+        for &(id, data) in BUS_LOG {
+            check_in(CriticalTask::CanRx);
+            match FdFrame::new_standard(id as u16, data) {
+                Ok(frame) => {
+                    process_rx_can_frame(&frame).await;
+                }
+                Err(_) => error!("Can't replay frame {:#05x}", id),
+            }
+            Timer::after(FRAME_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Module for telemetry snapshots
+//!
+//! Rendering a page reads many packages, and drawing takes long enough for new frames to arrive
+//! in between. Rather than holding package locks while drawing, the render code takes a
+//! [`TelemetrySnapshot`] once per frame and works from that copy.
+//!
+//! Each package is locked only long enough to copy it, so the snapshot can't block the CAN
+//! receive task for longer than a single package update, and every value drawn in a frame is
+//! checked for staleness against the same [`TelemetrySnapshot::taken_at`].
+
+use embassy_time::Instant;
+
+use crate::can_mod::{
+    BATT_PACK2_DATA, BOOST_PACK1_DATA, BOOST_PACK2_DATA, BOOST_PACK3_DATA, FCC_PACK1_DATA,
+    FCC_PACK2_DATA, FCC_PACK3_DATA, FET_DATA, H2_PACK1_DATA, H2_PACK2_DATA, REL_CAP_PACK,
+    REL_CHRG_PACK, REL_FC_PACK, REL_NRG_PACK, RELAY_MOTOR_PACK, RELAY_STATE, Timestamped,
+    h2_alarm_tripped,
+};
+use crate::eco_can::{
+    ECOCAN_H2Pack1_t, ECOCAN_H2Pack2_t, ECOCAN_RelPackChrg_t, FDCAN_BATTPack2_t,
+    FDCAN_BOOSTPack1_t, FDCAN_BOOSTPack2_t, FDCAN_BOOSTPack3_t, FDCAN_FccPack1_t, FDCAN_FccPack2_t,
+    FDCAN_FccPack3_t, FDCAN_FetPack_t, FDCAN_RelPackCap_t, FDCAN_RelPackFc_t, FDCAN_RelPackMtr_t,
+    FDCAN_RelPackNrg_t, RelayState,
+};
+
+/// A copy of every received package, taken at one time
+#[derive(Clone, Debug)]
+pub struct TelemetrySnapshot {
+    /// When the snapshot was taken, packages are stale relative to this
+    pub taken_at: Instant,
+    pub relay_state: RelayState,
+    pub h2_alarm: bool,
+    pub fet: Timestamped<FDCAN_FetPack_t>,
+    pub fcc1: Timestamped<FDCAN_FccPack1_t>,
+    pub fcc2: Timestamped<FDCAN_FccPack2_t>,
+    pub fcc3: Timestamped<FDCAN_FccPack3_t>,
+    pub h2_1: Timestamped<ECOCAN_H2Pack1_t>,
+    pub h2_2: Timestamped<ECOCAN_H2Pack2_t>,
+    pub boost1: Timestamped<FDCAN_BOOSTPack1_t>,
+    pub boost2: Timestamped<FDCAN_BOOSTPack2_t>,
+    pub boost3: Timestamped<FDCAN_BOOSTPack3_t>,
+    pub rel_fc: Timestamped<FDCAN_RelPackFc_t>,
+    pub rel_cap: Timestamped<FDCAN_RelPackCap_t>,
+    pub rel_mtr: Timestamped<FDCAN_RelPackMtr_t>,
+    pub rel_nrg: Timestamped<FDCAN_RelPackNrg_t>,
+    pub rel_chrg: Timestamped<ECOCAN_RelPackChrg_t>,
+    pub batt: Timestamped<FDCAN_BATTPack2_t>,
+}
+
+impl TelemetrySnapshot {
+    /// Returns true if `package` had not been received recently when the snapshot was taken
+    pub fn is_stale<T>(&self, package: &Timestamped<T>) -> bool {
+        package.is_stale(self.taken_at)
+    }
+}
+
+/// Copies every package, locking each one only for the copy
+pub async fn snapshot() -> TelemetrySnapshot {
+    TelemetrySnapshot {
+        taken_at: Instant::now(),
+        relay_state: RELAY_STATE.lock().await.clone(),
+        h2_alarm: h2_alarm_tripped().await,
+        fet: FET_DATA.lock().await.clone(),
+        fcc1: FCC_PACK1_DATA.lock().await.clone(),
+        fcc2: FCC_PACK2_DATA.lock().await.clone(),
+        fcc3: FCC_PACK3_DATA.lock().await.clone(),
+        h2_1: H2_PACK1_DATA.lock().await.clone(),
+        h2_2: H2_PACK2_DATA.lock().await.clone(),
+        boost1: BOOST_PACK1_DATA.lock().await.clone(),
+        boost2: BOOST_PACK2_DATA.lock().await.clone(),
+        boost3: BOOST_PACK3_DATA.lock().await.clone(),
+        rel_fc: REL_FC_PACK.lock().await.clone(),
+        rel_cap: REL_CAP_PACK.lock().await.clone(),
+        rel_mtr: RELAY_MOTOR_PACK.lock().await.clone(),
+        rel_nrg: REL_NRG_PACK.lock().await.clone(),
+        rel_chrg: REL_CHRG_PACK.lock().await.clone(),
+        batt: BATT_PACK2_DATA.lock().await.clone(),
+    }
+}
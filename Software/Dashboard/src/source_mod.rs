@@ -0,0 +1,144 @@
+//! Module for selecting the primary power source
+//!
+//! The car runs on either the fuel cell or the battery. The active source is detected from
+//! which source's CAN packages are still arriving (see [`crate::can_mod::STALE_AFTER`]), and the
+//! state of the relay board.
+//! The display shows the selected source's readings prominently.
+
+use defmt::Format;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::Instant;
+
+use crate::{
+    can_mod::{
+        BATT_PACK2_DATA, FCC_PACK1_DATA, FCC_PACK2_DATA, FCC_PACK3_DATA, REL_FC_PACK, RELAY_STATE,
+    },
+    eco_can::RelayState,
+    log_mod::info,
+};
+
+/// The source currently powering the car
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PowerSource {
+    FuelCell,
+    Battery,
+    /// Neither source is reporting
+    Unknown,
+}
+
+pub static POWER_SOURCE: Mutex<ThreadModeRawMutex, PowerSource> = Mutex::new(PowerSource::Unknown);
+
+/// Selects the primary power source
+///
+/// If only one source is reporting, it is selected. If both are reporting, the fuel cell is
+/// selected when the relays connect it (charging or running), otherwise the current selection is
+/// kept so the display does not flicker between sources during a transition.
+pub const fn select_source(
+    fc_fresh: bool,
+    batt_fresh: bool,
+    relay_state: &RelayState,
+    current: PowerSource,
+) -> PowerSource {
+    match (fc_fresh, batt_fresh) {
+        (true, false) => PowerSource::FuelCell,
+        (false, true) => PowerSource::Battery,
+        (true, true) => match relay_state {
+            RelayState::RELAY_CHRGE | RelayState::RELAY_RUN => PowerSource::FuelCell,
+            RelayState::RELAY_STBY | RelayState::RELAY_STRTP => match current {
+                PowerSource::Unknown => PowerSource::FuelCell,
+                source => source,
+            },
+        },
+        (false, false) => PowerSource::Unknown,
+    }
+}
+
+/// Re-evaluates the primary power source, and returns it
+pub async fn update_power_source() -> PowerSource {
+    let now = Instant::now();
+    let fc_fresh = !REL_FC_PACK.lock().await.is_stale(now)
+        || !FCC_PACK1_DATA.lock().await.is_stale(now)
+        || !FCC_PACK2_DATA.lock().await.is_stale(now)
+        || !FCC_PACK3_DATA.lock().await.is_stale(now);
+    let batt_fresh = !BATT_PACK2_DATA.lock().await.is_stale(now);
+
+    let relay_state = RELAY_STATE.lock().await.clone();
+
+    let mut source = POWER_SOURCE.lock().await;
+    let selected = select_source(fc_fresh, batt_fresh, &relay_state, *source);
+    if selected != *source {
+        info!("Power source changed: {:?} -> {:?}", *source, selected);
+        *source = selected;
+    }
+    selected
+}
+
+// Every combination of freshness and relay state
+const _: () = {
+    use PowerSource::{Battery, FuelCell, Unknown};
+    use RelayState::{RELAY_CHRGE, RELAY_RUN, RELAY_STBY, RELAY_STRTP};
+    const RELAY_STATES: [RelayState; 4] = [RELAY_STRTP, RELAY_CHRGE, RELAY_STBY, RELAY_RUN];
+    const SOURCES: [PowerSource; 3] = [FuelCell, Battery, Unknown];
+
+    let mut i = 0;
+    while i < RELAY_STATES.len() {
+        let relay = &RELAY_STATES[i];
+        let mut j = 0;
+        while j < SOURCES.len() {
+            let current = SOURCES[j];
+            // A single fresh source is always selected, whatever was selected before
+            assert!(matches!(
+                select_source(true, false, relay, current),
+                FuelCell
+            ));
+            assert!(matches!(
+                select_source(false, true, relay, current),
+                Battery
+            ));
+            // Nothing is reporting
+            assert!(matches!(
+                select_source(false, false, relay, current),
+                Unknown
+            ));
+            j += 1;
+        }
+        i += 1;
+    }
+
+    // Both fresh with the fuel cell connected, the fuel cell wins
+    assert!(matches!(
+        select_source(true, true, &RELAY_RUN, Battery),
+        FuelCell
+    ));
+    assert!(matches!(
+        select_source(true, true, &RELAY_CHRGE, Battery),
+        FuelCell
+    ));
+    assert!(matches!(
+        select_source(true, true, &RELAY_CHRGE, Unknown),
+        FuelCell
+    ));
+    // Both fresh during a transition, the current source is kept
+    assert!(matches!(
+        select_source(true, true, &RELAY_STBY, Battery),
+        Battery
+    ));
+    assert!(matches!(
+        select_source(true, true, &RELAY_STRTP, Battery),
+        Battery
+    ));
+    assert!(matches!(
+        select_source(true, true, &RELAY_STBY, FuelCell),
+        FuelCell
+    ));
+    // Unless nothing was selected yet, then the fuel cell is
+    assert!(matches!(
+        select_source(true, true, &RELAY_STBY, Unknown),
+        FuelCell
+    ));
+    assert!(matches!(
+        select_source(true, true, &RELAY_STRTP, Unknown),
+        FuelCell
+    ));
+};
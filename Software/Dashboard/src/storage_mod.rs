@@ -0,0 +1,420 @@
+//! Module for keeping the trip and the touch calibration in flash
+//!
+//! The trip totals and the peaks are saved to the last page of flash every [`SAVE_INTERVAL`]
+//! while they change, and restored on boot, so a run's totals can still be reviewed after the
+//! car is switched off. Hold button 1 to reset the trip, which also erases the page.
+//!
+//! The touch calibrations are kept in the page before it, see [`touch_mod`](crate::touch_mod).
+//! They are saved as soon as a calibration finishes, with [`save_touch_calibration`], and
+//! resetting the trip leaves them alone.
+//!
+//! Each page is split into [`SLOT_SIZE`] byte slots, and each save is written to the next blank
+//! slot. A page is only erased once every slot is used, which spreads the wear of the
+//! ~10 000 erase cycles the flash is rated for. A slot holds:
+//!
+//! | Bytes | Contents                                                       |
+//! |-------|----------------------------------------------------------------|
+//! | 0-3   | [`MAGIC`] or [`CALIBRATION_MAGIC`], big-endian                 |
+//! | 4-5   | Length of the payload, big-endian                              |
+//! | 6-9   | CRC-32 of the payload, big-endian                              |
+//! | 10-   | [`StoredTrip`] or [`CalibrationTable`], as big-endian integers |
+//!
+//! On boot the last valid slot of each page is restored. A blank page, or one with no valid
+//! slot, such as after flashing new firmware or a save cut short by the power going off, starts
+//! a fresh trip, or leaves the touch screen uncalibrated.
+//!
+//! The pages are left out of the `FLASH` region in `memory.x`, so firmware that would grow into
+//! them fails to link rather than being overwritten by a save.
+
+use embassy_futures::select::{Either3, select3};
+use embassy_stm32::flash::{Blocking, FLASH_SIZE, Flash, MAX_ERASE_SIZE, WRITE_SIZE};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
+use embassy_time::{Duration, Instant, Ticker};
+
+use crate::event_log_mod::log_event;
+use crate::log_mod::{error, info, warn};
+use crate::peak_mod::{PEAKS, Peak, PeakChannel, PeakTracker};
+use crate::touch_mod::{CalibrationTable, TOUCH_CALIBRATION, TouchCalibration};
+use crate::trip_mod::{TRIP, TripAccumulator, TripTotals};
+
+/// How often the trip is saved, if it has changed
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Offset of the trip's page from the start of flash, the last page
+pub const STORAGE_OFFSET: u32 = (FLASH_SIZE - MAX_ERASE_SIZE) as u32;
+/// Offset of the touch calibration's page from the start of flash, the page before the trip's
+///
+/// `memory.x` ends the firmware's flash where this page starts, keep them in step.
+pub const CALIBRATION_OFFSET: u32 = STORAGE_OFFSET - MAX_ERASE_SIZE as u32;
+/// Size of each save in the page
+pub const SLOT_SIZE: usize = 256;
+const SLOTS: usize = MAX_ERASE_SIZE / SLOT_SIZE;
+
+/// Marks a slot holding a save, the last byte is the layout version
+///
+/// Bump the version when [`StoredTrip`] changes, so an old save isn't decoded as the new layout.
+pub const MAGIC: u32 = 0x5452_4901;
+/// Marks a slot holding a touch calibration, the last byte is the layout version
+pub const CALIBRATION_MAGIC: u32 = 0x5443_4101;
+const HEADER_LEN: usize = 10;
+
+// Slots fill the page and can be written on their own
+const _: () =
+    assert!(MAX_ERASE_SIZE.is_multiple_of(SLOT_SIZE) && SLOT_SIZE.is_multiple_of(WRITE_SIZE));
+// A save fits in a slot
+const _: () = assert!(HEADER_LEN + StoredTrip::ENCODED_LEN <= SLOT_SIZE);
+const _: () = assert!(HEADER_LEN + CALIBRATION_LEN <= SLOT_SIZE);
+// A blank slot reads as all ones, which must not look like a save, and neither page's saves
+// can be mistaken for the other's
+const _: () = assert!(MAGIC != u32::MAX && CALIBRATION_MAGIC != u32::MAX);
+const _: () = assert!(MAGIC != CALIBRATION_MAGIC);
+
+/// Everything kept across a power cycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoredTrip {
+    pub trip: TripTotals,
+    pub peaks: PeakTracker,
+}
+
+/// Encoded length of a peak, a flag byte then the lowest and highest value
+const PEAK_LEN: usize = 17;
+
+/// Reads the big-endian `i64` at `at`
+fn read_i64(bytes: &[u8], at: usize) -> i64 {
+    let mut field = [0; 8];
+    field.copy_from_slice(&bytes[at..at + 8]);
+    i64::from_be_bytes(field)
+}
+
+impl StoredTrip {
+    /// Encoded length, the trip totals then the peaks
+    pub const ENCODED_LEN: usize = 5 * 8 + PeakChannel::COUNT * PEAK_LEN;
+
+    /// Encodes every field as a big-endian integer
+    ///
+    /// Written by hand rather than with bincode, which is several KB larger in a debug build.
+    fn encode(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        let trip = &self.trip;
+        let fields = [
+            trip.fc_joules,
+            trip.cap_joules,
+            trip.fc_coulombs,
+            trip.cap_coulombs,
+            trip.secs as i64,
+        ];
+        for (i, field) in fields.into_iter().enumerate() {
+            bytes[i * 8..][..8].copy_from_slice(&field.to_be_bytes());
+        }
+        for (i, peak) in self.peaks.peaks().into_iter().enumerate() {
+            let at = fields.len() * 8 + i * PEAK_LEN;
+            if let Some(peak) = peak {
+                bytes[at] = 1;
+                bytes[at + 1..][..8].copy_from_slice(&peak.min.to_be_bytes());
+                bytes[at + 9..][..8].copy_from_slice(&peak.max.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a save written by [`encode`](Self::encode)
+    fn decode(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let trip = TripTotals {
+            fc_joules: read_i64(bytes, 0),
+            cap_joules: read_i64(bytes, 8),
+            fc_coulombs: read_i64(bytes, 16),
+            cap_coulombs: read_i64(bytes, 24),
+            secs: read_i64(bytes, 32) as u64,
+        };
+        let mut peaks = [None; PeakChannel::COUNT];
+        for (i, peak) in peaks.iter_mut().enumerate() {
+            let at = 40 + i * PEAK_LEN;
+            if bytes[at] == 1 {
+                *peak = Some(Peak {
+                    min: read_i64(bytes, at + 1),
+                    max: read_i64(bytes, at + 9),
+                });
+            }
+        }
+        Self {
+            trip,
+            peaks: PeakTracker::from_peaks(peaks),
+        }
+    }
+
+    /// The current trip and peaks
+    async fn capture() -> Self {
+        Self {
+            trip: TRIP.lock().await.totals(Instant::now()),
+            peaks: *PEAKS.lock().await,
+        }
+    }
+
+    /// Replaces the current trip and peaks with the saved ones
+    async fn restore(&self) {
+        *TRIP.lock().await = TripAccumulator::restored(self.trip);
+        *PEAKS.lock().await = self.peaks;
+    }
+}
+
+/// Encoded length of a calibration, a flag byte then the six coefficients
+const ENTRY_LEN: usize = 1 + 6 * 4;
+/// Encoded length of the touch calibrations, one entry per rotation
+const CALIBRATION_LEN: usize = CalibrationTable::ROTATIONS * ENTRY_LEN;
+
+/// Reads the big-endian `i32` at `at`
+fn read_i32(bytes: &[u8], at: usize) -> i32 {
+    let mut field = [0; 4];
+    field.copy_from_slice(&bytes[at..at + 4]);
+    i32::from_be_bytes(field)
+}
+
+/// Encodes every calibration's coefficients as big-endian integers
+fn encode_calibration(table: &CalibrationTable) -> [u8; CALIBRATION_LEN] {
+    let mut bytes = [0; CALIBRATION_LEN];
+    for (i, entry) in table.entries().into_iter().enumerate() {
+        let at = i * ENTRY_LEN;
+        if let Some(c) = entry {
+            bytes[at] = 1;
+            for (j, coeff) in [c.a, c.b, c.c, c.d, c.e, c.f].into_iter().enumerate() {
+                bytes[at + 1 + j * 4..][..4].copy_from_slice(&coeff.to_be_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// Decodes calibrations written by [`encode_calibration`]
+fn decode_calibration(bytes: &[u8; CALIBRATION_LEN]) -> CalibrationTable {
+    let mut entries = [None; CalibrationTable::ROTATIONS];
+    for (i, entry) in entries.iter_mut().enumerate() {
+        let at = i * ENTRY_LEN;
+        if bytes[at] == 1 {
+            let coeff = |j: usize| read_i32(bytes, at + 1 + j * 4);
+            *entry = Some(TouchCalibration {
+                a: coeff(0),
+                b: coeff(1),
+                c: coeff(2),
+                d: coeff(3),
+                e: coeff(4),
+                f: coeff(5),
+            });
+        }
+    }
+    CalibrationTable::from_entries(entries)
+}
+
+/// Signalled to erase the saved trip
+static ERASE_SIGNAL: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Erases the saved trip, so it isn't restored on the next boot
+pub fn erase_stored_trip() {
+    ERASE_SIGNAL.signal(());
+}
+
+/// Signalled to save the touch calibrations
+static CALIBRATION_SIGNAL: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Saves [`TOUCH_CALIBRATION`] now, rather than waiting for the next [`SAVE_INTERVAL`]
+pub fn save_touch_calibration() {
+    CALIBRATION_SIGNAL.signal(());
+}
+
+/// CRC-32 (IEEE 802.3, reflected, polynomial 0xEDB88320)
+const fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= bytes[i] as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    !crc
+}
+
+// CRC-32 check value
+const _: () = assert!(crc32(b"123456789") == 0xCBF4_3926);
+
+/// What a slot holds
+enum Slot<'a> {
+    Blank,
+    /// A save's payload, with a matching length and checksum
+    Valid(&'a [u8]),
+    /// Not blank, but not a valid save either
+    Invalid,
+}
+
+/// Reads a slot's header, and its payload if it holds a `len` byte save marked with `magic`
+fn parse_slot(bytes: &[u8; SLOT_SIZE], magic: u32, len: usize) -> Slot<'_> {
+    let [m0, m1, m2, m3, l0, l1, c0, c1, c2, c3, ..] = *bytes;
+    let slot_magic = u32::from_be_bytes([m0, m1, m2, m3]);
+    if slot_magic == u32::MAX {
+        return Slot::Blank;
+    }
+    let slot_len = usize::from(u16::from_be_bytes([l0, l1]));
+    let Some(payload) = bytes[HEADER_LEN..].get(..len) else {
+        return Slot::Invalid;
+    };
+    if slot_magic != magic
+        || slot_len != len
+        || crc32(payload) != u32::from_be_bytes([c0, c1, c2, c3])
+    {
+        return Slot::Invalid;
+    }
+    Slot::Valid(payload)
+}
+
+/// A storage page, and where its next save goes
+struct SlotPage {
+    /// What the page holds, for the log
+    name: &'static str,
+    /// Offset of the page from the start of flash
+    offset: u32,
+    /// Marks the page's saves
+    magic: u32,
+    /// The next blank slot, `SLOTS` if the page is full
+    next_slot: usize,
+}
+
+impl SlotPage {
+    const fn new(name: &'static str, offset: u32, magic: u32) -> Self {
+        Self {
+            name,
+            offset,
+            magic,
+            next_slot: 0,
+        }
+    }
+
+    const fn slot_offset(&self, slot: usize) -> u32 {
+        self.offset + (slot * SLOT_SIZE) as u32
+    }
+
+    /// Scans the page, returns the payload of the last valid save
+    fn open<const N: usize>(&mut self, flash: &mut Flash<'static, Blocking>) -> Option<[u8; N]> {
+        let mut last = None;
+        let mut bytes = [0; SLOT_SIZE];
+        for slot in 0..SLOTS {
+            if let Err(e) = flash.blocking_read(self.slot_offset(slot), &mut bytes) {
+                error!("Failed to read the {} storage: {:?}", self.name, e);
+                break;
+            }
+            match parse_slot(&bytes, self.magic, N) {
+                Slot::Blank => break,
+                Slot::Valid(payload) => last = payload.first_chunk().copied(),
+                Slot::Invalid => warn!("Skipping invalid {} save in slot {}", self.name, slot),
+            }
+            self.next_slot = slot + 1;
+        }
+        last
+    }
+
+    fn erase(&mut self, flash: &mut Flash<'static, Blocking>) {
+        let from = self.offset;
+        let to = from + MAX_ERASE_SIZE as u32;
+        match flash.blocking_erase(from, to) {
+            Ok(()) => self.next_slot = 0,
+            Err(e) => error!("Failed to erase the {} storage: {:?}", self.name, e),
+        }
+    }
+
+    /// Writes a save to the next blank slot, erasing the page first if it is full
+    fn save(&mut self, flash: &mut Flash<'static, Blocking>, payload: &[u8]) {
+        let len = payload.len();
+        let mut bytes = [u8::MAX; SLOT_SIZE];
+        bytes[HEADER_LEN..][..len].copy_from_slice(payload);
+        let crc = crc32(payload);
+        bytes[..4].copy_from_slice(&self.magic.to_be_bytes());
+        bytes[4..6].copy_from_slice(&(len as u16).to_be_bytes());
+        bytes[6..HEADER_LEN].copy_from_slice(&crc.to_be_bytes());
+
+        if self.next_slot >= SLOTS {
+            self.erase(flash);
+            if self.next_slot >= SLOTS {
+                return;
+            }
+        }
+        // Only the used bytes are written, rounded up to the flash's write size
+        let written = (HEADER_LEN + len).next_multiple_of(WRITE_SIZE);
+        let offset = self.slot_offset(self.next_slot);
+        if let Err(e) = flash.blocking_write(offset, &bytes[..written]) {
+            error!("Failed to save the {}: {:?}", self.name, e);
+            log_event("Flash save failed");
+        }
+        // A failed write may have left the slot part written, so it is never reused
+        self.next_slot += 1;
+    }
+}
+
+/// Restores the saved trip and touch calibration, then saves them whenever they change
+///
+/// Spawn before the CAN tasks, so the restore doesn't replace packages already recorded.
+/// Flash writes and erases stall the executor for up to ~25 ms.
+#[embassy_executor::task]
+pub async fn storage_task(mut flash: Flash<'static, Blocking>) {
+    let mut trip_page = SlotPage::new("trip", STORAGE_OFFSET, MAGIC);
+    let restored = trip_page
+        .open(&mut flash)
+        .map(|payload| StoredTrip::decode(&payload));
+    match restored {
+        Some(stored) => {
+            stored.restore().await;
+            info!("Restored trip of {} s", stored.trip.secs);
+        }
+        None => info!("No saved trip, starting a fresh one"),
+    }
+
+    let mut calibration_page =
+        SlotPage::new("touch calibration", CALIBRATION_OFFSET, CALIBRATION_MAGIC);
+    let mut saved_calibration = calibration_page
+        .open(&mut flash)
+        .map(|payload| decode_calibration(&payload));
+    match saved_calibration {
+        Some(table) => {
+            *TOUCH_CALIBRATION.lock().await = table;
+            info!("Restored the touch calibration");
+        }
+        None => info!("No saved touch calibration"),
+    }
+
+    let mut saved = restored;
+    let mut ticker = Ticker::every(SAVE_INTERVAL);
+    loop {
+        match select3(
+            ticker.next(),
+            ERASE_SIGNAL.wait(),
+            CALIBRATION_SIGNAL.wait(),
+        )
+        .await
+        {
+            Either3::First(()) => {
+                let current = StoredTrip::capture().await;
+                if saved != Some(current) {
+                    trip_page.save(&mut flash, &current.encode());
+                    saved = Some(current);
+                }
+            }
+            Either3::Second(()) => {
+                trip_page.erase(&mut flash);
+                saved = None;
+                info!("Saved trip erased");
+            }
+            Either3::Third(()) => {
+                let current = *TOUCH_CALIBRATION.lock().await;
+                if saved_calibration != Some(current) {
+                    calibration_page.save(&mut flash, &encode_calibration(&current));
+                    saved_calibration = Some(current);
+                    info!("Touch calibration saved");
+                }
+            }
+        }
+    }
+}
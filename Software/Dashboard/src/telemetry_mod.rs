@@ -0,0 +1,148 @@
+//! Module for CSV Telemetry
+//!
+//! Emits decoded telemetry as comma separated values over RTT, so it can be plotted with tools
+//! like a serial plotter. Enabled with the `csv-telemetry` feature.
+//!
+//! A header line naming each column is emitted first, followed by one line of values per sample:
+//!
+//! ```text
+//! fc_volt_v,fc_curr_a,cap_volt_v,cap_curr_a,mtr_volt_v,mtr_curr_a,efficiency_pct,supply_mv
+//! 36.0,12.4,40.0,-3.0,38.0,20.0,91.5,3298
+//! ```
+//!
+//! The columns are [`CSV_METRICS`] in order. Values are scaled from the fixed point units
+//! received over CAN to the units and decimals shown on the display, see
+//! [`units_mod`](crate::units_mod). Lines are formatted into a fixed size buffer, so nothing
+//! is allocated.
+
+use core::fmt::Write;
+use core::sync::atomic::Ordering::Relaxed;
+
+use defmt::println;
+use embassy_time::Timer;
+use heapless::String;
+
+use crate::{
+    adc_mod::SUPPLY_MV,
+    can_mod::{BOOST_PACK3_DATA, FCC_PACK1_DATA, REL_CAP_PACK, REL_FC_PACK, RELAY_MOTOR_PACK},
+    log_mod::warn,
+    units_mod::{
+        CENTI_DECIMALS, DISPLAY_DECIMALS, MILLI_DECIMALS, round_scaled_signed, write_scaled,
+    },
+};
+
+/// Time between samples
+pub const CSV_PERIOD_MS: u64 = 200;
+/// Maximum length of a line, keep [`CSV_METRICS`] short enough to fit
+const CSV_LINE_LEN: usize = 128;
+
+/// A telemetry value that can be emitted as a CSV column
+#[derive(Clone, Copy)]
+pub enum Metric {
+    FcVolt,
+    FcCurr,
+    FcTemp,
+    CapVolt,
+    CapCurr,
+    MtrVolt,
+    MtrCurr,
+    Efficiency,
+    SupplyMv,
+}
+
+impl Metric {
+    /// The column name in the header
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::FcVolt => "fc_volt_v",
+            Metric::FcCurr => "fc_curr_a",
+            Metric::FcTemp => "fc_temp_c",
+            Metric::CapVolt => "cap_volt_v",
+            Metric::CapCurr => "cap_curr_a",
+            Metric::MtrVolt => "mtr_volt_v",
+            Metric::MtrCurr => "mtr_curr_a",
+            Metric::Efficiency => "efficiency_pct",
+            Metric::SupplyMv => "supply_mv",
+        }
+    }
+
+    /// The decimals of the value read, and the decimals it is written with
+    const fn decimals(&self) -> (u8, u8) {
+        match self {
+            Metric::FcVolt
+            | Metric::FcCurr
+            | Metric::CapVolt
+            | Metric::CapCurr
+            | Metric::MtrVolt
+            | Metric::MtrCurr => (MILLI_DECIMALS, DISPLAY_DECIMALS),
+            Metric::FcTemp | Metric::Efficiency => (CENTI_DECIMALS, DISPLAY_DECIMALS),
+            // Shown in mV on the diagnostics page
+            Metric::SupplyMv => (0, 0),
+        }
+    }
+
+    /// Writes the latest value in display units
+    async fn write(&self, out: &mut dyn Write) -> core::fmt::Result {
+        let (raw_decimals, decimals) = self.decimals();
+        let (negative, whole, frac) =
+            round_scaled_signed(self.read().await, raw_decimals, decimals);
+        write_scaled(out, negative, whole, frac, decimals)
+    }
+
+    /// Reads the latest value, in the fixed point units it was received in
+    async fn read(&self) -> i64 {
+        match self {
+            Metric::FcVolt => REL_FC_PACK.lock().await.fc_volt as i64,
+            Metric::FcCurr => REL_FC_PACK.lock().await.fc_curr as i64,
+            Metric::FcTemp => FCC_PACK1_DATA.lock().await.fc_temp as i64,
+            Metric::CapVolt => REL_CAP_PACK.lock().await.cap_volt as i64,
+            Metric::CapCurr => REL_CAP_PACK.lock().await.cap_curr as i64,
+            Metric::MtrVolt => RELAY_MOTOR_PACK.lock().await.mtr_volt as i64,
+            Metric::MtrCurr => RELAY_MOTOR_PACK.lock().await.mtr_curr as i64,
+            Metric::Efficiency => BOOST_PACK3_DATA.lock().await.efficiency as i64,
+            Metric::SupplyMv => SUPPLY_MV.load(Relaxed) as i64,
+        }
+    }
+}
+
+/// The metrics emitted, in column order
+pub const CSV_METRICS: &[Metric] = &[
+    Metric::FcVolt,
+    Metric::FcCurr,
+    Metric::CapVolt,
+    Metric::CapCurr,
+    Metric::MtrVolt,
+    Metric::MtrCurr,
+    Metric::Efficiency,
+    Metric::SupplyMv,
+];
+
+/// Emits the selected metrics as CSV lines
+#[embassy_executor::task]
+pub async fn csv_telemetry_task() {
+    let mut line: String<CSV_LINE_LEN> = String::new();
+
+    // Header
+    for (i, metric) in CSV_METRICS.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        if write!(line, "{}{}", separator, metric.name()).is_err() {
+            warn!("CSV header is longer than {} bytes", CSV_LINE_LEN);
+            break;
+        }
+    }
+    println!("{=str}", line.as_str());
+
+    loop {
+        line.clear();
+        for (i, metric) in CSV_METRICS.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            if line.push_str(separator).is_err() || metric.write(&mut line).await.is_err() {
+                warn!("CSV line is longer than {} bytes", CSV_LINE_LEN);
+                break;
+            }
+        }
+        println!("{=str}", line.as_str());
+
+        Timer::after_millis(CSV_PERIOD_MS).await;
+    }
+}
@@ -0,0 +1,124 @@
+//! Module for timed state machines
+//!
+//! Alarms, LED cooldowns, and page transitions all move between states after a period of time.
+//! [`TimedStateMachine`] holds the current state, when it was entered, and an optional timed
+//! transition, so these features don't each need their own [`Instant`] bookkeeping. The H2
+//! alarm latch, [`H2_ALARM`](crate::can_mod::H2_ALARM), is one.
+//!
+//! The current time is always passed in by the caller, rather than read from the clock,
+//! which makes the timing deterministic.
+//!
+//! ```rust,ignore
+//! let mut blink = TimedStateMachine::new(Led::On, Instant::now());
+//! blink.transition_on_timeout(Duration::from_millis(500), Led::Off);
+//! loop {
+//!     if let Some(state) = blink.tick(Instant::now()) {
+//!         // state just changed
+//!     }
+//! }
+//! ```
+
+use embassy_time::{Duration, Instant};
+
+/// A state machine whose states can change after a timeout
+pub struct TimedStateMachine<S: Copy + PartialEq> {
+    state: S,
+    /// When the current state was entered
+    entered: Instant,
+    /// The state to move to, and how long after entering the current state
+    timeout: Option<(Duration, S)>,
+}
+
+impl<S: Copy + PartialEq> TimedStateMachine<S> {
+    pub const fn new(initial: S, now: Instant) -> Self {
+        Self {
+            state: initial,
+            entered: now,
+            timeout: None,
+        }
+    }
+
+    /// The current state
+    pub const fn state(&self) -> S {
+        self.state
+    }
+
+    /// How long the machine has been in the current state
+    pub const fn elapsed(&self, now: Instant) -> Duration {
+        // In ticks, so it can be used in const checks
+        Duration::from_ticks(now.as_ticks().saturating_sub(self.entered.as_ticks()))
+    }
+
+    /// Moves to `next` immediately, and cancels any pending timed transition.
+    ///
+    /// Transitioning to the current state restarts its timer.
+    pub const fn transition(&mut self, next: S, now: Instant) {
+        self.state = next;
+        self.entered = now;
+        self.timeout = None;
+    }
+
+    /// Moves to `next` once the machine has been in the current state for `after`
+    pub const fn transition_on_timeout(&mut self, after: Duration, next: S) {
+        self.timeout = Some((after, next));
+    }
+
+    /// Applies a pending timed transition if it is due.
+    ///
+    /// Returns the new state if the state changed.
+    pub const fn tick(&mut self, now: Instant) -> Option<S> {
+        let Some((after, next)) = self.timeout else {
+            return None;
+        };
+        if self.elapsed(now).as_ticks() < after.as_ticks() {
+            return None;
+        }
+        // Enter the next state at the time it was due, so that chained timeouts don't drift
+        let due = Instant::from_ticks(self.entered.as_ticks() + after.as_ticks());
+        self.transition(next, due);
+        Some(next)
+    }
+}
+
+// Transition timing, with the time passed in as a mock clock
+const _: () = {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Led {
+        On,
+        Off,
+    }
+    const fn at(ms: u64) -> Instant {
+        Instant::from_millis(ms)
+    }
+    // Compared in ticks, a millisecond isn't a whole number of ticks at every tick rate
+    const fn ticks_between(from: u64, to: u64) -> u64 {
+        at(to).as_ticks() - at(from).as_ticks()
+    }
+
+    let mut blink = TimedStateMachine::new(Led::On, at(1_000));
+    // Nothing happens without a timed transition
+    assert!(blink.tick(at(60_000)).is_none());
+
+    // Not due until the timeout has fully passed
+    blink.transition_on_timeout(Duration::from_millis(500), Led::Off);
+    assert!(blink.tick(at(1_499)).is_none());
+    assert!(matches!(blink.state(), Led::On));
+    assert!(matches!(blink.tick(at(1_500)), Some(Led::Off)));
+    assert!(blink.elapsed(at(1_600)).as_ticks() == ticks_between(1_500, 1_600));
+    // Applied once
+    assert!(blink.tick(at(5_000)).is_none());
+
+    // A late tick enters the state when it was due, so the next timeout doesn't drift
+    blink.transition_on_timeout(Duration::from_millis(500), Led::On);
+    assert!(matches!(blink.tick(at(2_300)), Some(Led::On)));
+    assert!(blink.elapsed(at(2_300)).as_ticks() == ticks_between(2_000, 2_300));
+
+    // An immediate transition cancels the pending one, and restarts the timer
+    blink.transition_on_timeout(Duration::from_millis(500), Led::Off);
+    blink.transition(Led::On, at(2_400));
+    assert!(blink.tick(at(10_000)).is_none());
+    assert!(blink.elapsed(at(2_450)).as_ticks() == ticks_between(2_400, 2_450));
+
+    // Time running backwards reads as no time passed
+    assert!(blink.elapsed(at(0)).as_ticks() == 0);
+};
@@ -0,0 +1,170 @@
+//! Module for CAN Timestamps
+//!
+//! Keeps the timestamps of received CAN frames on the same timebase as [`embassy_time::Instant`],
+//! so that logged and telemetered frames can be correlated with each other.
+//!
+//! The FDCAN peripheral stamps each frame with a 16-bit counter that increments once per nominal
+//! bit time. `embassy-stm32` converts that counter into an [`Instant`] when the frame is read, by
+//! subtracting the counter delta from the current time. Because the counter is only 16 bits wide,
+//! the conversion is only correct if the frame is read within one counter period
+//! (65536 bit times, 655 ms at 100 kbit/s). A frame that waited in the FIFO for longer than one
+//! period is stamped too late by a multiple of the period.
+//!
+//! [`CanTimebase`] undoes those wraps. A frame can't have been received before the previous
+//! frame, or before the receive task last found the FIFOs empty, so its stamp is moved back by
+//! whole periods to the earliest time after both. While the task keeps up, that bound is less
+//! than a period old, and only one time fits. The offset between the corrected timestamp and the
+//! time the frame was read is tracked too, so a growing backlog is visible.
+//!
+//! The board has no RTC configured, so [`Instant`] is the only system timebase.
+
+use embassy_stm32::can::frame::Timestamp;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
+
+/// Number of counts before the FDCAN timestamp counter wraps
+const CAN_TS_COUNTER_PERIOD: u64 = 1 << 16;
+
+/// Weight of a new sample in the offset average, as a power of two (1/8)
+const OFFSET_FILTER_SHIFT: u32 = 3;
+
+/// Shared timebase for the CAN receive task, and anything that logs received frames.
+pub static CAN_TIMEBASE: Mutex<ThreadModeRawMutex, CanTimebase> =
+    Mutex::new(CanTimebase::new(crate::can_mod::CAN_BAUD_RATE));
+
+/// Time it takes the timestamp counter to wrap on a bus running at `bitrate` bits/s
+pub const fn wrap_period(bitrate: u32) -> Duration {
+    Duration::from_micros(CAN_TS_COUNTER_PERIOD * 1_000_000 / bitrate as u64)
+}
+
+/// Estimates the offset between CAN hardware timestamps and [`Instant`]
+pub struct CanTimebase {
+    /// Time it takes the timestamp counter to wrap
+    wrap_period: Duration,
+    /// Smoothed delay between a frame being received, and it being read (in ticks)
+    offset_ticks: u64,
+    /// Last corrected timestamp
+    last: Option<Instant>,
+    /// When the receive task last found the FIFOs empty
+    empty_at: Option<Instant>,
+    /// Number of frames whose timestamp was corrected
+    corrections: u32,
+}
+
+impl CanTimebase {
+    /// Creates a timebase for a bus running at `bitrate` bits/s
+    pub const fn new(bitrate: u32) -> Self {
+        Self {
+            wrap_period: wrap_period(bitrate),
+            offset_ticks: 0,
+            last: None,
+            empty_at: None,
+            corrections: 0,
+        }
+    }
+
+    /// Records that the FIFOs were empty at `now`, so every frame read later arrived after it
+    pub const fn fifo_empty(&mut self, now: Instant) {
+        self.empty_at = Some(now);
+    }
+
+    /// Records a frame's hardware timestamp, and the time it was read from the FIFO.
+    ///
+    /// Returns the corrected timestamp of the frame.
+    pub const fn update(&mut self, can_ts: Timestamp, now: Instant) -> Instant {
+        let ts = self.to_system_time(can_ts);
+        if ts.as_ticks() != can_ts.as_ticks() {
+            self.corrections = self.corrections.wrapping_add(1);
+        }
+
+        // Exponential moving average of the read delay
+        let sample = now.as_ticks().saturating_sub(ts.as_ticks());
+        self.offset_ticks = match self.last {
+            None => sample,
+            Some(_) => {
+                self.offset_ticks - (self.offset_ticks >> OFFSET_FILTER_SHIFT)
+                    + (sample >> OFFSET_FILTER_SHIFT)
+            }
+        };
+        self.last = Some(ts);
+        ts
+    }
+
+    /// Converts a CAN hardware timestamp into system time.
+    ///
+    /// Frames are read from the FIFO in the order they were received, so a frame can't be
+    /// earlier than the previous frame, or than the FIFOs were last seen empty. The stamp is
+    /// moved back by every whole wrap period that still leaves it after both. A stamp already
+    /// before them, which only rounding can cause, is clamped to keep the timeline monotonic.
+    pub const fn to_system_time(&self, can_ts: Timestamp) -> Instant {
+        let earliest = match (self.last, self.empty_at) {
+            (Some(last), Some(empty)) if last.as_ticks() > empty.as_ticks() => last,
+            (_, Some(empty)) => empty,
+            (Some(last), None) => last,
+            (None, None) => return can_ts,
+        };
+        let (ts, earliest) = (can_ts.as_ticks(), earliest.as_ticks());
+        if ts < earliest {
+            return Instant::from_ticks(earliest);
+        }
+        let period = self.wrap_period.as_ticks();
+        Instant::from_ticks(ts - (ts - earliest) / period * period)
+    }
+
+    /// The smoothed delay between a frame being received and it being read
+    pub const fn offset(&self) -> Duration {
+        Duration::from_ticks(self.offset_ticks)
+    }
+
+    /// Returns true if frames are read so late that their timestamps may have wrapped
+    pub const fn is_backlogged(&self) -> bool {
+        self.offset_ticks >= self.wrap_period.as_ticks()
+    }
+
+    /// The number of timestamps that have been corrected
+    pub const fn corrections(&self) -> u32 {
+        self.corrections
+    }
+}
+
+// Timestamps across counter wraps, at 100 kbit/s so a period is 655 ms
+const _: () = {
+    const fn at(ms: u64) -> Instant {
+        Instant::from_millis(ms)
+    }
+    const fn plus(instant: Instant, periods: u64) -> Instant {
+        Instant::from_ticks(instant.as_ticks() + periods * wrap_period(100_000).as_ticks())
+    }
+    const fn same(a: Instant, b: Instant) -> bool {
+        a.as_ticks() == b.as_ticks()
+    }
+
+    let mut timebase = CanTimebase::new(100_000);
+    // Nothing to go on yet, the stamp is taken as is
+    assert!(same(timebase.update(at(1_000), at(1_001)), at(1_000)));
+
+    // Read promptly, within a period of the FIFOs being empty
+    timebase.fifo_empty(at(1_500));
+    assert!(same(timebase.update(at(1_600), at(1_601)), at(1_600)));
+    assert!(timebase.corrections() == 0);
+
+    // The task was held up after the FIFOs were last empty. A frame received at 1.7 s, read
+    // after the counter wrapped once, is stamped a period late by the driver
+    timebase.fifo_empty(at(1_650));
+    let read = plus(at(1_720), 1);
+    assert!(same(timebase.update(plus(at(1_700), 1), read), at(1_700)));
+    // The frame behind it waited long enough for two wraps
+    assert!(same(
+        timebase.update(plus(at(1_710), 2), plus(at(1_720), 2)),
+        at(1_710)
+    ));
+    assert!(timebase.corrections() == 2);
+
+    // A quiet bus doesn't look like a wrap, the task found the FIFOs empty while waiting
+    timebase.fifo_empty(at(9_000));
+    assert!(same(timebase.update(at(9_100), at(9_100)), at(9_100)));
+    assert!(timebase.corrections() == 2);
+
+    // A stamp before the previous frame is clamped, so the timeline stays monotonic
+    assert!(same(timebase.update(at(9_050), at(9_110)), at(9_100)));
+};
@@ -0,0 +1,385 @@
+//! Module for the Touch Screen
+//!
+//! # Calibration
+//! Raw touch readings are mapped to screen coordinates with an affine transform:
+//!
+//! ```text
+//! screen_x = a * raw_x + b * raw_y + c
+//! screen_y = d * raw_x + e * raw_y + f
+//! ```
+//!
+//! The coefficients are found by touching three known points on the screen
+//! (see [`TouchCalibration::from_points`]). Since the mapping changes when the panel is rotated,
+//! a calibration is kept for each orientation, and touches are mapped with the one for
+//! [`DISPLAY_ROTATION`].
+//!
+//! If the current orientation has no calibration, touching the screen starts a
+//! [`CalibrationRun`], which shows each of [`CALIBRATION_TARGETS`] in turn. Holding a touch for
+//! [`TOUCH_LONG_PRESS_MS`] starts one too, to redo a poor calibration. The table is saved to
+//! flash by [`storage_mod`](crate::storage_mod) and restored on boot.
+//!
+//! # Driver
+//! The XPT2046 touch controller shares the SPI bus with the display. It pulls its IRQ pin low
+//! while the panel is touched, so [`touch_task`] sleeps until then, and samples the position
+//! every [`TOUCH_POLL_MS`] until it is released. Touches are published to [`TOUCH_EVENTS`], which
+//! the display task reads, a tap on a calibrated screen switches to the next page.
+
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::time::Hertz;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, mutex::Mutex};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::prelude::Point;
+use embedded_hal::spi::SpiDevice;
+use mipidsi::options::Rotation;
+
+use crate::display_mod::{DISPLAY_HEIGHT, DISPLAY_ROTATION, DISPLAY_WIDTH, SharedSpiDevice};
+use crate::log_mod::{info, trace, warn};
+
+/// Fractional bits of the calibration coefficients
+const COEFF_SHIFT: u32 = 16;
+
+/// Coefficients mapping raw touch readings to screen coordinates.
+///
+/// Coefficients are fixed point, with [`COEFF_SHIFT`] fractional bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+#[repr(C)]
+pub struct TouchCalibration {
+    pub a: i32,
+    pub b: i32,
+    pub c: i32,
+    pub d: i32,
+    pub e: i32,
+    pub f: i32,
+}
+
+impl TouchCalibration {
+    /// Calculates the calibration from three raw touch readings, and the screen points
+    /// that were touched.
+    ///
+    /// Returns `None` if the points are in a line, since they cannot define a transform.
+    pub fn from_points(raw: [Point; 3], screen: [Point; 3]) -> Option<Self> {
+        let [(x0, y0), (x1, y1), (x2, y2)] = raw.map(|p| (p.x as i64, p.y as i64));
+        let det = (x0 - x2) * (y1 - y2) - (x1 - x2) * (y0 - y2);
+        if det == 0 {
+            return None;
+        }
+
+        // Solves for the coefficients of one screen axis, using Cramer's rule
+        let solve = |s0: i64, s1: i64, s2: i64| -> (i32, i32, i32) {
+            let a = ((s0 - s2) * (y1 - y2) - (s1 - s2) * (y0 - y2)) << COEFF_SHIFT;
+            let b = ((x0 - x2) * (s1 - s2) - (s0 - s2) * (x1 - x2)) << COEFF_SHIFT;
+            let c =
+                (y0 * (x2 * s1 - x1 * s2) + y1 * (x0 * s2 - x2 * s0) + y2 * (x1 * s0 - x0 * s1))
+                    << COEFF_SHIFT;
+            ((a / det) as i32, (b / det) as i32, (c / det) as i32)
+        };
+        let [sx, sy] = [screen.map(|p| p.x as i64), screen.map(|p| p.y as i64)];
+        let (a, b, c) = solve(sx[0], sx[1], sx[2]);
+        let (d, e, f) = solve(sy[0], sy[1], sy[2]);
+
+        Some(Self { a, b, c, d, e, f })
+    }
+
+    /// Maps a raw touch reading to a screen coordinate
+    pub fn apply(&self, raw: Point) -> Point {
+        let (x, y) = (raw.x as i64, raw.y as i64);
+        let map = |a: i32, b: i32, c: i32| {
+            ((a as i64 * x + b as i64 * y + c as i64) >> COEFF_SHIFT) as i32
+        };
+        Point::new(map(self.a, self.b, self.c), map(self.d, self.e, self.f))
+    }
+}
+
+/// A touch calibration for each screen orientation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct CalibrationTable {
+    entries: [Option<TouchCalibration>; Self::ROTATIONS],
+}
+
+impl CalibrationTable {
+    /// Number of orientations, one entry each
+    pub const ROTATIONS: usize = 4;
+
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; Self::ROTATIONS],
+        }
+    }
+
+    /// A table holding `entries`, indexed by rotation, such as one restored from flash
+    pub const fn from_entries(entries: [Option<TouchCalibration>; Self::ROTATIONS]) -> Self {
+        Self { entries }
+    }
+
+    /// Every entry, indexed by rotation, `None` for an orientation that isn't calibrated
+    pub const fn entries(&self) -> [Option<TouchCalibration>; Self::ROTATIONS] {
+        self.entries
+    }
+
+    const fn index(rotation: Rotation) -> usize {
+        match rotation {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => 1,
+            Rotation::Deg180 => 2,
+            Rotation::Deg270 => 3,
+        }
+    }
+
+    /// The calibration for `rotation`, or `None` if it must be re-calibrated
+    pub const fn get(&self, rotation: Rotation) -> Option<TouchCalibration> {
+        self.entries[Self::index(rotation)]
+    }
+
+    /// Stores the calibration for `rotation`
+    pub const fn set(&mut self, rotation: Rotation, calibration: TouchCalibration) {
+        self.entries[Self::index(rotation)] = Some(calibration);
+    }
+
+    /// Removes the calibration for `rotation`, forcing it to be re-calibrated
+    pub const fn clear(&mut self, rotation: Rotation) {
+        self.entries[Self::index(rotation)] = None;
+    }
+}
+
+// Each orientation gets its own calibration, switching orientation never picks up another's
+const _: () = {
+    const fn calibration(c: i32) -> TouchCalibration {
+        TouchCalibration {
+            a: 1,
+            b: 0,
+            c,
+            d: 0,
+            e: 1,
+            f: 0,
+        }
+    }
+    const fn offset(table: &CalibrationTable, rotation: Rotation) -> Option<i32> {
+        match table.get(rotation) {
+            Some(calibration) => Some(calibration.c),
+            None => None,
+        }
+    }
+
+    let mut table = CalibrationTable::new();
+    table.set(Rotation::Deg90, calibration(90));
+    table.set(Rotation::Deg270, calibration(270));
+    assert!(matches!(offset(&table, Rotation::Deg90), Some(90)));
+    assert!(matches!(offset(&table, Rotation::Deg270), Some(270)));
+    // Orientations never calibrated have to be
+    assert!(offset(&table, Rotation::Deg0).is_none());
+    assert!(offset(&table, Rotation::Deg180).is_none());
+
+    // Re-calibrating one orientation leaves the others alone
+    table.set(Rotation::Deg90, calibration(-90));
+    table.clear(Rotation::Deg270);
+    assert!(matches!(offset(&table, Rotation::Deg90), Some(-90)));
+    assert!(offset(&table, Rotation::Deg270).is_none());
+};
+
+impl Default for CalibrationTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The calibrations, restored from flash on boot
+pub static TOUCH_CALIBRATION: Mutex<ThreadModeRawMutex, CalibrationTable> =
+    Mutex::new(CalibrationTable::new());
+
+/// Screen points touched to calibrate, spread over the screen and not in a line
+pub const CALIBRATION_TARGETS: [Point; 3] = [
+    Point::new(DISPLAY_WIDTH as i32 / 8, DISPLAY_HEIGHT as i32 / 8),
+    Point::new(DISPLAY_WIDTH as i32 * 7 / 8, DISPLAY_HEIGHT as i32 / 2),
+    Point::new(DISPLAY_WIDTH as i32 / 2, DISPLAY_HEIGHT as i32 * 7 / 8),
+];
+
+/// What a [`CalibrationRun`] needs next
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum CalibrationStep {
+    /// Touch the next target
+    Next,
+    /// Every target has been touched
+    Done(TouchCalibration),
+    /// The readings were in a line, so every target has to be touched again
+    Failed,
+}
+
+/// A calibration in progress, collecting a raw reading for each of [`CALIBRATION_TARGETS`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CalibrationRun {
+    raw: [Point; 3],
+    /// Number of targets touched so far
+    taken: usize,
+}
+
+impl CalibrationRun {
+    pub const fn new() -> Self {
+        Self {
+            raw: [Point::zero(); 3],
+            taken: 0,
+        }
+    }
+
+    /// Index of the target to touch next
+    pub const fn step(&self) -> usize {
+        self.taken
+    }
+
+    /// The screen point to touch next
+    pub const fn target(&self) -> Point {
+        CALIBRATION_TARGETS[self.taken]
+    }
+
+    /// Records the raw reading for the current target
+    pub fn record(&mut self, raw: Point) -> CalibrationStep {
+        self.raw[self.taken] = raw;
+        self.taken += 1;
+        if self.taken < CALIBRATION_TARGETS.len() {
+            return CalibrationStep::Next;
+        }
+        self.taken = 0;
+        match TouchCalibration::from_points(self.raw, CALIBRATION_TARGETS) {
+            Some(calibration) => CalibrationStep::Done(calibration),
+            None => CalibrationStep::Failed,
+        }
+    }
+}
+
+impl Default for CalibrationRun {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SPI clock for the touch controller, the XPT2046 is limited to 2.5 MHz
+pub const TOUCH_SPI_FREQ: Hertz = Hertz::mhz(2);
+// The XPT2046's maximum DCLK is 2.5 MHz
+const _: () = assert!(TOUCH_SPI_FREQ.0 <= 2_500_000);
+/// How often the position is sampled while the panel is touched
+pub const TOUCH_POLL_MS: u64 = 20;
+/// Readings averaged into each sample, to smooth out noise
+const TOUCH_SAMPLES: i32 = 4;
+/// How long a touch is held before a [`TouchEvent::LongPress`] is published
+pub const TOUCH_LONG_PRESS_MS: u64 = 5_000;
+/// Raw distance a touch has to move before a [`TouchEvent::Move`] is published
+const MOVE_THRESHOLD: u32 = 16;
+/// Number of touch events buffered, newer events are dropped if the channel is full
+pub const TOUCH_EVENT_CAPACITY: usize = 8;
+
+/// Control byte to measure X, 12 bit differential, powering down between conversions
+const CMD_READ_X: u8 = 0xD0;
+/// Control byte to measure Y, 12 bit differential, powering down between conversions
+const CMD_READ_Y: u8 = 0x90;
+
+/// A touched point on the panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct TouchPoint {
+    /// The controller's reading, 0 to 4095 on each axis
+    pub raw: Point,
+    /// The point on the screen, `None` if the current orientation isn't calibrated
+    pub position: Option<Point>,
+}
+
+/// Events published to [`TOUCH_EVENTS`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum TouchEvent {
+    /// The panel was touched
+    Down(TouchPoint),
+    /// The touch moved by more than [`MOVE_THRESHOLD`]
+    Move(TouchPoint),
+    /// The touch has been held for [`TOUCH_LONG_PRESS_MS`], sent once per touch
+    LongPress(TouchPoint),
+    /// The touch was released
+    Up,
+}
+
+/// Touch events for the display to act on, read by
+/// [`display_task`](crate::display_mod::display_task)
+pub static TOUCH_EVENTS: Channel<ThreadModeRawMutex, TouchEvent, TOUCH_EVENT_CAPACITY> =
+    Channel::new();
+
+/// Runs a conversion, and returns its 12 bit result
+fn read_channel(device: &mut SharedSpiDevice, command: u8) -> Option<i32> {
+    // The result is clocked out over the two bytes after the control byte
+    let mut buf = [command, 0, 0];
+    device.transfer_in_place(&mut buf).ok()?;
+    Some(i32::from(u16::from_be_bytes([buf[1], buf[2]]) >> 3))
+}
+
+/// Reads the raw touch position, averaged over [`TOUCH_SAMPLES`] readings
+fn read_raw(device: &mut SharedSpiDevice) -> Option<Point> {
+    let mut sum = Point::zero();
+    for _ in 0..TOUCH_SAMPLES {
+        sum += Point::new(
+            read_channel(device, CMD_READ_X)?,
+            read_channel(device, CMD_READ_Y)?,
+        );
+    }
+    Some(sum / TOUCH_SAMPLES)
+}
+
+/// Manhattan distance between two raw readings
+fn distance(a: Point, b: Point) -> u32 {
+    let delta = a - b;
+    delta.x.unsigned_abs() + delta.y.unsigned_abs()
+}
+
+/// Publishes a touch event, dropping it if nothing is reading the events
+fn publish(event: TouchEvent) {
+    if TOUCH_EVENTS.try_send(event).is_err() {
+        trace!("Touch event dropped: {}", event);
+    }
+}
+
+/// Reads the touch controller, and publishes touches to [`TOUCH_EVENTS`]
+#[embassy_executor::task]
+pub async fn touch_task(mut device: SharedSpiDevice, mut irq: ExtiInput<'static>) -> ! {
+    info!("Starting Touch Task");
+    loop {
+        irq.wait_for_low().await;
+
+        let long_press = Instant::now() + Duration::from_millis(TOUCH_LONG_PRESS_MS);
+        let mut long_pressed = false;
+        // Raw position of the last published event
+        let mut last: Option<Point> = None;
+        while irq.is_low() {
+            let Some(raw) = read_raw(&mut device) else {
+                warn!("Touch controller read failed");
+                break;
+            };
+            // The panel may have been released during the reading, so it's not trustworthy
+            if irq.is_high() {
+                break;
+            }
+
+            let calibration = TOUCH_CALIBRATION.lock().await.get(DISPLAY_ROTATION);
+            let point = TouchPoint {
+                raw,
+                position: calibration.map(|c| c.apply(raw)),
+            };
+            let event = match last {
+                None => Some(TouchEvent::Down(point)),
+                Some(prev) if distance(raw, prev) >= MOVE_THRESHOLD => {
+                    Some(TouchEvent::Move(point))
+                }
+                Some(_) => None,
+            };
+            if let Some(event) = event {
+                publish(event);
+                last = Some(raw);
+            }
+            if !long_pressed && Instant::now() >= long_press {
+                publish(TouchEvent::LongPress(point));
+                long_pressed = true;
+            }
+            Timer::after_millis(TOUCH_POLL_MS).await;
+        }
+
+        if last.is_some() {
+            publish(TouchEvent::Up);
+        }
+        // Wait out the release, so a read failure doesn't spin on a held touch
+        irq.wait_for_high().await;
+    }
+}
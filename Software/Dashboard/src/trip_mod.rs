@@ -0,0 +1,293 @@
+//! Module for the trip meter
+//!
+//! The relay board sends running totals of the energy and charge from the fuel cell and the
+//! supercapacitors. [`TripAccumulator`] tracks how far each total has moved since the trip was
+//! last reset, which is what the driver cares about during a run. Hold button 1 to reset it.
+//!
+//! The totals are `i32`s, which wrap on the relay board after ~2 GJ. Each step between packages
+//! is taken the short way round the wrap and added to an `i64`, see [`counter_step`], so a wrap
+//! doesn't show up as a jump. The fuel cell's totals only count up, so a decrease in them is the
+//! relay board restarting, and the trip carries on from the new value rather than losing the
+//! difference.
+//!
+//! The trip is saved to flash by [`storage_mod`](crate::storage_mod) and restored on boot as
+//! [`TripTotals`]. The relay board's totals may have restarted in the meantime, so the restored
+//! totals are carried over and the extents start again from the next packages received.
+
+use defmt::Format;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::Instant;
+
+use crate::eco_can::{ECOCAN_RelPackChrg_t, FDCAN_RelPackNrg_t};
+use crate::log_mod::{info, warn};
+
+/// How a sender's running total can move
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum Counting {
+    /// Only ever increases, such as the energy from the fuel cell. A decrease means the sender
+    /// restarted its total.
+    Up,
+    /// Increases and decreases, such as the supercapacitors charging and discharging
+    Both,
+}
+
+/// The step between two readings of a running `i32` total, allowing for it to wrap
+///
+/// The sender's total wraps from `i32::MAX` to `i32::MIN` and back, so the step is the shortest
+/// way between the readings, e.g. `i32::MAX` to `i32::MIN` is a step of 1. A step of more than
+/// 2^31 can't be told apart from one the other way round the wrap.
+pub const fn counter_step(last: i32, value: i32) -> i64 {
+    value.wrapping_sub(last) as i64
+}
+
+/// Adds a step to a total, saturating at the `i64` limits rather than wrapping
+///
+/// An `i64` of joules saturates after ~290 million years at 1 kW, so this is only a guard.
+pub const fn accumulate(total: i64, step: i64) -> i64 {
+    total.saturating_add(step)
+}
+
+/// The range a running total has covered since the trip was reset
+#[derive(Clone, Copy, Debug, Format, Default)]
+pub struct Extent {
+    pub first: i32,
+    pub last: i32,
+    pub min: i32,
+    pub max: i32,
+    /// Change since the trip was reset, the sum of every step
+    delta: i64,
+}
+
+impl Extent {
+    pub const fn new(value: i32) -> Self {
+        Self {
+            first: value,
+            last: value,
+            min: value,
+            max: value,
+            delta: 0,
+        }
+    }
+
+    /// Adds a reading, returns true if the sender restarted its total
+    ///
+    /// A restart isn't counted as a step, the total carries on from the new reading.
+    pub const fn record(&mut self, value: i32, counting: Counting) -> bool {
+        let step = counter_step(self.last, value);
+        let restarted = matches!(counting, Counting::Up) && step < 0;
+        if !restarted {
+            self.delta = accumulate(self.delta, step);
+        }
+        self.last = value;
+        if value < self.min {
+            self.min = value;
+        }
+        if value > self.max {
+            self.max = value;
+        }
+        restarted
+    }
+
+    /// Change since the trip was reset
+    pub const fn delta(&self) -> i64 {
+        self.delta
+    }
+}
+
+/// Records an extent, starting it if it has no values yet
+fn record(extent: &mut Option<Extent>, value: i32, counting: Counting, name: &str) {
+    match extent {
+        Some(extent) => {
+            if extent.record(value, counting) {
+                warn!(
+                    "{} restarted at {}, continuing the trip from it",
+                    name, value
+                );
+            }
+        }
+        None => *extent = Some(Extent::new(value)),
+    }
+}
+
+// Steps across the sender's wrap are small, and restarts aren't counted
+const _: () = {
+    assert!(counter_step(10, 15) == 5);
+    assert!(counter_step(15, 10) == -5);
+    assert!(counter_step(i32::MAX, i32::MIN) == 1);
+    assert!(counter_step(i32::MIN, i32::MAX) == -1);
+    assert!(counter_step(i32::MAX - 1, i32::MIN + 2) == 4);
+    assert!(accumulate(i64::MAX - 1, 5) == i64::MAX);
+    assert!(accumulate(i64::MIN + 1, -5) == i64::MIN);
+
+    // A counting up total wraps and keeps counting
+    let mut energy = Extent::new(i32::MAX - 10);
+    assert!(!energy.record(i32::MAX, Counting::Up));
+    assert!(!energy.record(i32::MIN + 9, Counting::Up));
+    assert!(energy.delta() == 20);
+    // A sender restart is skipped rather than subtracted
+    let mut energy = Extent::new(1_000_000);
+    assert!(!energy.record(1_000_020, Counting::Up));
+    assert!(energy.record(0, Counting::Up));
+    assert!(energy.delta() == 20);
+    assert!(!energy.record(100, Counting::Up));
+    assert!(energy.delta() == 120);
+
+    // A total that moves both ways can go back down, including across the wrap
+    let mut cap = Extent::new(i32::MIN + 5);
+    assert!(!cap.record(i32::MAX - 4, Counting::Both));
+    assert!(cap.delta() == -10);
+    assert!(!cap.record(i32::MIN, Counting::Both));
+    assert!(cap.delta() == -5);
+    assert!(cap.min == i32::MIN && cap.max == i32::MAX - 4);
+};
+
+/// How far each total has moved this trip, and for how long
+#[derive(Clone, Copy, Debug, Format, Default, PartialEq, Eq)]
+pub struct TripTotals {
+    pub fc_joules: i64,
+    pub cap_joules: i64,
+    pub fc_coulombs: i64,
+    pub cap_coulombs: i64,
+    pub secs: u64,
+}
+
+/// Energy and charge totals since the trip was last reset
+///
+/// Each extent is `None` until its package is received after a reset or a restore.
+#[derive(Clone, Copy, Debug, Format)]
+pub struct TripAccumulator {
+    pub fc_joules: Option<Extent>,
+    pub cap_joules: Option<Extent>,
+    pub fc_coulombs: Option<Extent>,
+    pub cap_coulombs: Option<Extent>,
+    /// When the trip was reset, `None` until the first package after a reset
+    started: Option<Instant>,
+    /// Totals restored from flash, `None` if the trip was started since power up
+    carried: Option<TripTotals>,
+}
+
+impl TripAccumulator {
+    pub const fn new() -> Self {
+        Self {
+            fc_joules: None,
+            cap_joules: None,
+            fc_coulombs: None,
+            cap_coulombs: None,
+            started: None,
+            carried: None,
+        }
+    }
+
+    /// Continues a trip saved before the last power down
+    pub const fn restored(carried: TripTotals) -> Self {
+        Self {
+            carried: Some(carried),
+            ..Self::new()
+        }
+    }
+
+    /// True if the trip continues one restored from flash
+    pub const fn is_restored(&self) -> bool {
+        self.carried.is_some()
+    }
+
+    /// Starts a new trip from the next packages received
+    pub fn reset(&mut self) {
+        *self = Self::new();
+        info!("Trip reset");
+    }
+
+    pub fn record_energy(&mut self, energy: &FDCAN_RelPackNrg_t, now: Instant) {
+        self.started.get_or_insert(now);
+        record(
+            &mut self.fc_joules,
+            energy.fc_joules,
+            Counting::Up,
+            "FC joules",
+        );
+        record(
+            &mut self.cap_joules,
+            energy.cap_joules,
+            Counting::Both,
+            "Cap joules",
+        );
+    }
+
+    pub fn record_charge(&mut self, charge: &ECOCAN_RelPackChrg_t, now: Instant) {
+        self.started.get_or_insert(now);
+        record(
+            &mut self.fc_coulombs,
+            charge.fc_coloumbs,
+            Counting::Up,
+            "FC coulombs",
+        );
+        record(
+            &mut self.cap_coulombs,
+            charge.cap_coloumbs,
+            Counting::Both,
+            "Cap coulombs",
+        );
+    }
+
+    /// Each total this trip, including any carried over from before the last power down
+    pub fn totals(&self, now: Instant) -> TripTotals {
+        let carried = self.carried.unwrap_or_default();
+        let delta = |extent: Option<Extent>| extent.map_or(0, |extent| extent.delta());
+        TripTotals {
+            fc_joules: accumulate(carried.fc_joules, delta(self.fc_joules)),
+            cap_joules: accumulate(carried.cap_joules, delta(self.cap_joules)),
+            fc_coulombs: accumulate(carried.fc_coulombs, delta(self.fc_coulombs)),
+            cap_coulombs: accumulate(carried.cap_coulombs, delta(self.cap_coulombs)),
+            secs: carried
+                .secs
+                .saturating_add(self.started.map_or(0, |started| {
+                    now.saturating_duration_since(started).as_secs()
+                })),
+        }
+    }
+
+    /// Energy delivered by the fuel cell and the supercapacitors this trip
+    pub fn total_joules(&self) -> i64 {
+        let carried = self.carried.map_or(0, |carried| {
+            accumulate(carried.fc_joules, carried.cap_joules)
+        });
+        [self.fc_joules, self.cap_joules]
+            .iter()
+            .flatten()
+            .map(Extent::delta)
+            .fold(carried, accumulate)
+    }
+
+    /// Seconds since the trip started
+    pub fn elapsed_secs(&self, now: Instant) -> u64 {
+        self.totals(now).secs
+    }
+
+    /// Average power this trip, the lower the more efficient the run
+    ///
+    /// There is no distance on the bus, so this is the running efficiency estimate.
+    pub fn average_power_w(&self, now: Instant) -> i64 {
+        match self.elapsed_secs(now) {
+            0 => 0,
+            secs => self.total_joules() / secs as i64,
+        }
+    }
+}
+
+impl Default for TripAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub static TRIP: Mutex<ThreadModeRawMutex, TripAccumulator> = Mutex::new(TripAccumulator::new());
+
+/// Adds a received energy package to the trip
+pub async fn record_trip_energy(energy: &FDCAN_RelPackNrg_t) {
+    TRIP.lock().await.record_energy(energy, Instant::now());
+}
+
+/// Adds a received charge package to the trip
+pub async fn record_trip_charge(charge: &ECOCAN_RelPackChrg_t) {
+    TRIP.lock().await.record_charge(charge, Instant::now());
+}
@@ -0,0 +1,274 @@
+//! Module for fixed point units
+//!
+//! Fields are sent as scaled integers, see [Engineering Units](crate::eco_can#engineering-units).
+//! These helpers split a raw field into whole units and a fixed number of decimals with integer
+//! maths only, so a value such as 12 345 mV can be shown as `12.3` without going through a
+//! float, and every widget rounds the same way.
+//!
+//! Rounding is to the nearest shown decimal, halves away from zero, and carries into the whole
+//! part, so 12 950 mV to one decimal is 13.0 V. [`fixed_width`] then lays the parts out as
+//! right aligned characters for a [`SevenSegField`](crate::display_mod::SevenSegField) sized
+//! widget, and [`write_scaled`] writes them as text.
+
+use core::fmt::{self, Write};
+
+/// Decimals in a field sent in thousandths, mV or mA
+pub const MILLI_DECIMALS: u8 = 3;
+/// Decimals in a field sent in hundredths, such as 0.01 °C
+pub const CENTI_DECIMALS: u8 = 2;
+/// Decimals shown for volts, amps and degrees
+pub const DISPLAY_DECIMALS: u8 = 1;
+
+/// `10^exp`, `exp` must be at most 9
+pub const fn pow10(exp: u8) -> u32 {
+    let mut value = 1;
+    let mut i = 0;
+    while i < exp {
+        value *= 10;
+        i += 1;
+    }
+    value
+}
+
+/// A raw magnitude with `raw_decimals` decimals, rounded to `decimals` decimals
+///
+/// Returns the whole part and the decimals as an integer, e.g. 12 345 with 3 raw decimals to 1
+/// decimal is `(12, 3)`. Halves round up, and the rounding carries into the whole part.
+pub const fn round_scaled(raw: u32, raw_decimals: u8, decimals: u8) -> (u32, u32) {
+    let rounded = if decimals >= raw_decimals {
+        raw as u64 * pow10(decimals - raw_decimals) as u64
+    } else {
+        let divisor = pow10(raw_decimals - decimals) as u64;
+        (raw as u64 + divisor / 2) / divisor
+    };
+    let unit = pow10(decimals) as u64;
+    ((rounded / unit) as u32, (rounded % unit) as u32)
+}
+
+/// A signed raw value rounded like [`round_scaled`], returns whether it is negative too
+///
+/// Takes an `i64` so any `u32` or `i32` field can be passed, a magnitude above `u32::MAX`
+/// saturates. Halves round away from zero. A value that rounds to zero is not negative, so
+/// -0.04 is shown as `0.0` rather than `-0.0`.
+pub const fn round_scaled_signed(raw: i64, raw_decimals: u8, decimals: u8) -> (bool, u32, u32) {
+    let magnitude = raw.unsigned_abs();
+    let magnitude = if magnitude > u32::MAX as u64 {
+        u32::MAX
+    } else {
+        magnitude as u32
+    };
+    let (whole, frac) = round_scaled(magnitude, raw_decimals, decimals);
+    (raw < 0 && (whole != 0 || frac != 0), whole, frac)
+}
+
+/// Writes a rounded value as text, with `decimals` digits after the point, e.g. `-1.50`
+///
+/// The counterpart of [`fixed_width`] without padding, for values in a line of text.
+pub fn write_scaled(
+    out: &mut dyn Write,
+    negative: bool,
+    whole: u32,
+    frac: u32,
+    decimals: u8,
+) -> fmt::Result {
+    if negative {
+        out.write_char('-')?;
+    }
+    out.write_str(itoa::Buffer::new().format(whole))?;
+    if decimals > 0 {
+        out.write_char('.')?;
+        let mut place = pow10(decimals - 1);
+        while place > 0 {
+            out.write_char(char::from(b'0' + (frac / place % 10) as u8))?;
+            place /= 10;
+        }
+    }
+    Ok(())
+}
+
+/// Narrows the whole part to a `u16`, saturating along with the decimals
+const fn saturate_u16((whole, frac): (u32, u32), decimals: u8) -> (u16, u16) {
+    if whole > u16::MAX as u32 {
+        (u16::MAX, (pow10(decimals) - 1) as u16)
+    } else {
+        (whole as u16, frac as u16)
+    }
+}
+
+/// A voltage in mV as volts and tenths of a volt
+pub const fn millivolts_to_volts(raw_mv: u32) -> (u16, u16) {
+    saturate_u16(
+        round_scaled(raw_mv, MILLI_DECIMALS, DISPLAY_DECIMALS),
+        DISPLAY_DECIMALS,
+    )
+}
+
+/// A current in mA as amps and tenths of an amp
+pub const fn milliamps_to_amps(raw_ma: u32) -> (u16, u16) {
+    saturate_u16(
+        round_scaled(raw_ma, MILLI_DECIMALS, DISPLAY_DECIMALS),
+        DISPLAY_DECIMALS,
+    )
+}
+
+/// A field in hundredths, such as a temperature in 0.01 °C, as whole units and tenths
+pub const fn centi_to_units(raw: u32) -> (u16, u16) {
+    saturate_u16(
+        round_scaled(raw, CENTI_DECIMALS, DISPLAY_DECIMALS),
+        DISPLAY_DECIMALS,
+    )
+}
+
+/// Lays out a value as `WIDTH` right aligned characters, padded with spaces
+///
+/// There is always a digit before the point, and `decimals` digits after it, e.g. `" 0.5"`.
+/// No point is shown with 0 decimals. A value that doesn't fit, sign included, is shown as all
+/// 9s with the point in the same place, like [`SevenSegField`](crate::display_mod::SevenSegField).
+pub const fn fixed_width<const WIDTH: usize>(
+    negative: bool,
+    whole: u32,
+    frac: u32,
+    decimals: u8,
+) -> [u8; WIDTH] {
+    let mut chars = [b' '; WIDTH];
+    let mut fits = true;
+    let mut at = WIDTH;
+
+    let mut frac = frac;
+    let mut i = 0;
+    while i < decimals {
+        if at == 0 {
+            fits = false;
+            break;
+        }
+        at -= 1;
+        chars[at] = b'0' + (frac % 10) as u8;
+        frac /= 10;
+        i += 1;
+    }
+    if decimals > 0 && fits {
+        if at == 0 {
+            fits = false;
+        } else {
+            at -= 1;
+            chars[at] = b'.';
+        }
+    }
+
+    let mut whole = whole;
+    while fits {
+        if at == 0 {
+            fits = false;
+            break;
+        }
+        at -= 1;
+        chars[at] = b'0' + (whole % 10) as u8;
+        whole /= 10;
+        if whole == 0 {
+            break;
+        }
+    }
+    if negative && fits {
+        if at == 0 {
+            fits = false;
+        } else {
+            at -= 1;
+            chars[at] = b'-';
+        }
+    }
+
+    if !fits {
+        fill_overflow(&mut chars, decimals);
+    }
+    chars
+}
+
+/// Fills `chars` with 9s, keeping the point `decimals` from the end if there is room for a
+/// digit before it
+///
+/// Shown in place of a value too wide for its field, so it reads as the largest value that fits.
+pub const fn fill_overflow(chars: &mut [u8], decimals: u8) {
+    let point = match chars.len().checked_sub(decimals as usize + 1) {
+        Some(point) if decimals > 0 && point > 0 => Some(point),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        chars[i] = match point {
+            Some(point) if i == point => b'.',
+            _ => b'9',
+        };
+        i += 1;
+    }
+}
+
+/// Compares laid out characters, `==` on arrays isn't const
+const fn same<const WIDTH: usize>(a: [u8; WIDTH], b: &[u8; WIDTH]) -> bool {
+    let mut i = 0;
+    while i < WIDTH {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+// Rounding at the boundaries
+const _: () = {
+    // Just below and at a half, and the carry into the whole part
+    assert!(matches!(millivolts_to_volts(12_349), (12, 3)));
+    assert!(matches!(millivolts_to_volts(12_350), (12, 4)));
+    assert!(matches!(millivolts_to_volts(12_949), (12, 9)));
+    assert!(matches!(millivolts_to_volts(12_950), (13, 0)));
+    assert!(matches!(millivolts_to_volts(999_950), (1_000, 0)));
+    assert!(matches!(millivolts_to_volts(0), (0, 0)));
+    assert!(matches!(millivolts_to_volts(49), (0, 0)));
+    assert!(matches!(millivolts_to_volts(50), (0, 1)));
+    // Too many volts for a u16 saturates, rather than wrapping
+    assert!(matches!(millivolts_to_volts(u32::MAX), (u16::MAX, 9)));
+    assert!(matches!(milliamps_to_amps(65_535_949), (u16::MAX, 9)));
+    assert!(matches!(centi_to_units(2_345), (23, 5)));
+    assert!(matches!(centi_to_units(2_344), (23, 4)));
+    assert!(matches!(centi_to_units(9_995), (100, 0)));
+    // Adding decimals pads with zeros, and a full range field doesn't overflow
+    assert!(matches!(round_scaled(7, 0, 2), (7, 0)));
+    assert!(matches!(round_scaled(123, 2, 3), (1, 230)));
+    assert!(matches!(round_scaled(u32::MAX, 0, 1), (u32::MAX, 0)));
+    assert!(matches!(round_scaled(u32::MAX, 3, 0), (4_294_967, 0)));
+    // Halves round away from zero, and nothing rounds to -0
+    assert!(matches!(round_scaled_signed(-12_350, 3, 1), (true, 12, 4)));
+    assert!(matches!(round_scaled_signed(-12_349, 3, 1), (true, 12, 3)));
+    assert!(matches!(round_scaled_signed(-49, 3, 1), (false, 0, 0)));
+    assert!(matches!(
+        round_scaled_signed(i32::MIN as i64, 3, 0),
+        (true, 2_147_484, 0)
+    ));
+    // Every u32 fits, anything wider saturates
+    assert!(matches!(
+        round_scaled_signed(u32::MAX as i64, 0, 0),
+        (false, u32::MAX, 0)
+    ));
+    assert!(matches!(
+        round_scaled_signed(i64::MIN, 0, 0),
+        (true, u32::MAX, 0)
+    ));
+};
+
+// Fixed width layout
+const _: () = {
+    assert!(same(fixed_width::<4>(false, 12, 3, 1), b"12.3"));
+    assert!(same(fixed_width::<5>(false, 12, 3, 1), b" 12.3"));
+    // Leading and trailing zeros
+    assert!(same(fixed_width::<4>(false, 0, 5, 1), b" 0.5"));
+    assert!(same(fixed_width::<5>(false, 3, 5, 2), b" 3.05"));
+    assert!(same(fixed_width::<5>(false, 100, 0, 1), b"100.0"));
+    assert!(same(fixed_width::<3>(false, 7, 0, 0), b"  7"));
+    assert!(same(fixed_width::<3>(false, 0, 0, 0), b"  0"));
+    assert!(same(fixed_width::<5>(true, 1, 5, 1), b" -1.5"));
+    // Too wide, including the sign
+    assert!(same(fixed_width::<4>(false, 123, 4, 1), b"99.9"));
+    assert!(same(fixed_width::<4>(true, 12, 3, 1), b"99.9"));
+    assert!(same(fixed_width::<2>(false, 123, 0, 0), b"99"));
+    assert!(same(fixed_width::<2>(false, 1, 5, 2), b"99"));
+};
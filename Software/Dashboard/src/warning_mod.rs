@@ -0,0 +1,293 @@
+//! Module for driver warnings raised from measured values
+//!
+//! A warning trips when a value drops below its threshold, and only clears once the value
+//! recovers above the threshold plus a hysteresis, so it doesn't flicker on and off while the
+//! value hovers around the threshold.
+//!
+//! Temperatures work the other way around, with a warning and a critical tier above them, see
+//! [`THERMAL_LIMITS`]. A critical temperature enters the [safe state](crate::safe_state_mod).
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering::Relaxed};
+
+use defmt::Format;
+
+use crate::eco_can::{ECOCAN_H2Pack2_t, FDCAN_FccPack1_t, FDCAN_FccPack3_t};
+use crate::log_mod::{error, info, warn};
+use crate::safe_state_mod::{Fault, enter_safe_state};
+
+/// Fuel cell voltage below which the low voltage warning is raised
+pub const FC_LOW_MV: u32 = 20_000;
+/// The warning is cleared once the fuel cell recovers above `FC_LOW_MV + FC_HYST_MV`
+pub const FC_HYST_MV: u32 = 1_000;
+
+/// True while the fuel cell voltage is low
+pub static FC_VOLTAGE_LOW: AtomicBool = AtomicBool::new(false);
+
+/// Whether a low warning should be active, given whether it is currently active
+pub const fn low_with_hysteresis(low: bool, value: u32, threshold: u32, hysteresis: u32) -> bool {
+    if low {
+        value <= threshold + hysteresis
+    } else {
+        value < threshold
+    }
+}
+
+/// Updates a low warning with a new value, returns true if the warning changed
+pub fn update_low_warning(
+    warning: &AtomicBool,
+    value: u32,
+    threshold: u32,
+    hysteresis: u32,
+) -> bool {
+    let low = warning.load(Relaxed);
+    let new_low = low_with_hysteresis(low, value, threshold, hysteresis);
+    warning.store(new_low, Relaxed);
+    new_low != low
+}
+
+/// Updates the fuel cell low voltage warning with a received voltage
+pub fn update_fc_voltage(fc_volt_mv: u32) {
+    if update_low_warning(&FC_VOLTAGE_LOW, fc_volt_mv, FC_LOW_MV, FC_HYST_MV) {
+        if FC_VOLTAGE_LOW.load(Relaxed) {
+            warn!("Fuel cell voltage low: {} mV", fc_volt_mv);
+        } else {
+            info!("Fuel cell voltage recovered: {} mV", fc_volt_mv);
+        }
+    }
+}
+
+// Trips below the threshold, and holds until above the hysteresis band
+const _: () = {
+    let (low, hyst) = (FC_LOW_MV, FC_HYST_MV);
+    assert!(!low_with_hysteresis(false, low, low, hyst));
+    assert!(low_with_hysteresis(false, low - 1, low, hyst));
+    assert!(low_with_hysteresis(true, low + hyst, low, hyst));
+    assert!(!low_with_hysteresis(true, low + hyst + 1, low, hyst));
+};
+
+/// A temperature that is checked against its [`ThermalLimit`]
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ThermalChannel {
+    /// Fuel cell stack temperature, from the FCC
+    FcTemp,
+    /// Air temperature in the FCC enclosure
+    FccBmeTemp,
+    /// Air temperature at the H2 board
+    H2BmeTemp,
+}
+
+impl ThermalChannel {
+    pub const ALL: [ThermalChannel; 3] = [
+        ThermalChannel::FcTemp,
+        ThermalChannel::FccBmeTemp,
+        ThermalChannel::H2BmeTemp,
+    ];
+}
+
+/// How hot a channel is, ordered so the hottest level is the greatest
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum ThermalLevel {
+    Normal = 0,
+    Warning = 1,
+    Critical = 2,
+}
+
+impl ThermalLevel {
+    const fn from_u8(level: u8) -> Self {
+        match level {
+            0 => ThermalLevel::Normal,
+            1 => ThermalLevel::Warning,
+            _ => ThermalLevel::Critical,
+        }
+    }
+}
+
+/// Temperature limits of a channel, all in hundredths of a degree Celsius
+#[derive(Clone, Copy, Debug, Format)]
+pub struct ThermalLimit {
+    /// Above this the warning is raised
+    pub warning: i32,
+    /// Above this the critical warning is raised
+    pub critical: i32,
+    /// A level is only left once the temperature is this far back below its limit
+    pub hysteresis: i32,
+}
+
+/// The limits of each channel, in the order of [`ThermalChannel::ALL`]
+pub const THERMAL_LIMITS: [(ThermalChannel, ThermalLimit); ThermalChannel::ALL.len()] = [
+    (
+        ThermalChannel::FcTemp,
+        ThermalLimit {
+            warning: 65_00,
+            critical: 75_00,
+            hysteresis: 2_00,
+        },
+    ),
+    (
+        ThermalChannel::FccBmeTemp,
+        ThermalLimit {
+            warning: 50_00,
+            critical: 60_00,
+            hysteresis: 2_00,
+        },
+    ),
+    (
+        ThermalChannel::H2BmeTemp,
+        ThermalLimit {
+            warning: 45_00,
+            critical: 55_00,
+            hysteresis: 2_00,
+        },
+    ),
+];
+
+// The table is indexed by channel, and each channel's tiers are in order
+const _: () = {
+    let mut i = 0;
+    while i < THERMAL_LIMITS.len() {
+        let (channel, limit) = THERMAL_LIMITS[i];
+        assert!(channel as usize == i);
+        assert!(limit.warning < limit.critical);
+        assert!(0 <= limit.hysteresis && limit.hysteresis < limit.critical - limit.warning);
+        i += 1;
+    }
+};
+
+/// The thermal level of each channel, indexed by [`ThermalChannel`]
+static THERMAL_LEVELS: [AtomicU8; ThermalChannel::ALL.len()] =
+    [AtomicU8::new(0), AtomicU8::new(0), AtomicU8::new(0)];
+
+/// The level a channel should be at, given the level it is currently at
+///
+/// A level is entered as soon as the temperature is above its limit, and only left once the
+/// temperature is [`ThermalLimit::hysteresis`] below it.
+pub const fn thermal_level_with_hysteresis(
+    level: ThermalLevel,
+    temp: i32,
+    limit: &ThermalLimit,
+) -> ThermalLevel {
+    if tier_held(
+        temp,
+        limit.critical,
+        limit.hysteresis,
+        level,
+        ThermalLevel::Critical,
+    ) {
+        ThermalLevel::Critical
+    } else if tier_held(
+        temp,
+        limit.warning,
+        limit.hysteresis,
+        level,
+        ThermalLevel::Warning,
+    ) {
+        ThermalLevel::Warning
+    } else {
+        ThermalLevel::Normal
+    }
+}
+
+/// Whether `tier` applies, it is held down to its threshold minus the hysteresis once reached
+const fn tier_held(
+    temp: i32,
+    threshold: i32,
+    hysteresis: i32,
+    level: ThermalLevel,
+    tier: ThermalLevel,
+) -> bool {
+    temp > threshold || (level as u8 >= tier as u8 && temp > threshold - hysteresis)
+}
+
+/// The thermal level of a channel
+pub fn channel_thermal_level(channel: ThermalChannel) -> ThermalLevel {
+    ThermalLevel::from_u8(THERMAL_LEVELS[channel as usize].load(Relaxed))
+}
+
+/// The hottest level of any channel, shown on the display and LEDs
+pub fn thermal_level() -> ThermalLevel {
+    ThermalChannel::ALL
+        .into_iter()
+        .map(channel_thermal_level)
+        .max()
+        .unwrap_or(ThermalLevel::Normal)
+}
+
+/// Updates a channel's thermal level with a received temperature, in hundredths of a degree
+///
+/// Reaching the critical level enters the safe state, see [`enter_safe_state`].
+pub async fn update_thermal(channel: ThermalChannel, temp_centi: i32) {
+    let limit = &THERMAL_LIMITS[channel as usize].1;
+    let level = channel_thermal_level(channel);
+    let new_level = thermal_level_with_hysteresis(level, temp_centi, limit);
+    if new_level == level {
+        return;
+    }
+    THERMAL_LEVELS[channel as usize].store(new_level as u8, Relaxed);
+    match new_level {
+        ThermalLevel::Critical => {
+            error!("{} critical: {} C/100", channel, temp_centi);
+            enter_safe_state(Fault::Overtemp(channel)).await;
+        }
+        ThermalLevel::Warning => warn!("{} warning: {} C/100", channel, temp_centi),
+        ThermalLevel::Normal => info!("{} recovered: {} C/100", channel, temp_centi),
+    }
+}
+
+/// Checks the fuel cell temperature from a received package
+pub async fn update_fc_temp(fcc: &FDCAN_FccPack1_t) {
+    update_thermal(ThermalChannel::FcTemp, fcc.fc_temp).await;
+}
+
+/// Checks the FCC enclosure temperature from a received package
+pub async fn update_fcc_bme_temp(fcc: &FDCAN_FccPack3_t) {
+    let temp = i32::try_from(fcc.bme_temp).unwrap_or(i32::MAX);
+    update_thermal(ThermalChannel::FccBmeTemp, temp).await;
+}
+
+/// Checks the H2 board temperature from a received package
+///
+/// The H2 board sends its temperature in hundredths of a degree, like the FCC.
+pub async fn update_h2_bme_temp(h2: &ECOCAN_H2Pack2_t) {
+    update_thermal(ThermalChannel::H2BmeTemp, i32::from(h2.bme_temp)).await;
+}
+
+// Each tier trips above its limit, and holds until below it by the hysteresis
+const _: () = {
+    use ThermalLevel::{Critical, Normal, Warning};
+    let limit = THERMAL_LIMITS[ThermalChannel::FcTemp as usize].1;
+    let (warn, crit, hyst) = (limit.warning, limit.critical, limit.hysteresis);
+    assert!(matches!(
+        thermal_level_with_hysteresis(Normal, warn, &limit),
+        Normal
+    ));
+    assert!(matches!(
+        thermal_level_with_hysteresis(Normal, warn + 1, &limit),
+        Warning
+    ));
+    assert!(matches!(
+        thermal_level_with_hysteresis(Normal, crit + 1, &limit),
+        Critical
+    ));
+    assert!(matches!(
+        thermal_level_with_hysteresis(Warning, warn - hyst + 1, &limit),
+        Warning
+    ));
+    assert!(matches!(
+        thermal_level_with_hysteresis(Warning, warn - hyst, &limit),
+        Normal
+    ));
+    assert!(matches!(
+        thermal_level_with_hysteresis(Critical, crit - hyst + 1, &limit),
+        Critical
+    ));
+    // Falling out of critical lands in the warning band
+    assert!(matches!(
+        thermal_level_with_hysteresis(Critical, crit - hyst, &limit),
+        Warning
+    ));
+    assert!(matches!(
+        thermal_level_with_hysteresis(Critical, warn - hyst, &limit),
+        Normal
+    ));
+};
@@ -0,0 +1,96 @@
+//! Module for the independent watchdog
+//!
+//! Resets the dashboard if a critical task stalls, for example on a deadlocked mutex, so the
+//! car is never left with a frozen display.
+//!
+//! Each critical task calls [`check_in`] every time around its loop. Every [`FEED_INTERVAL`]
+//! the watchdog task checks that every critical task has checked in within [`TASK_DEADLINE`],
+//! and only then feeds the IWDG. If any task misses its deadline the IWDG is no longer fed,
+//! and resets the MCU after [`WATCHDOG_TIMEOUT_US`].
+//!
+//! If the display didn't come up at boot its task isn't run, so it isn't waited for.
+//!
+//! The IWDG runs from its own LSI clock, so it still resets the board if the executor itself
+//! is stuck and the watchdog task never runs.
+
+use core::sync::atomic::{AtomicU32, Ordering::Relaxed};
+
+use defmt::Format;
+use embassy_stm32::peripherals::IWDG;
+use embassy_stm32::wdg::IndependentWatchdog;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::display_mod::display_failed;
+use crate::log_mod::{error, info, trace};
+
+/// Time without being fed before the IWDG resets the MCU
+pub const WATCHDOG_TIMEOUT_US: u32 = 1_000_000;
+/// How often the watchdog task checks the critical tasks and feeds the IWDG
+pub const FEED_INTERVAL: Duration = Duration::from_millis(250);
+/// Maximum time a critical task can go without checking in
+///
+/// The slowest check-ins are every 250 ms, from the CAN receive task on a quiet bus and the LED
+/// task in the safe state. The display checks in every frame, and this leaves room for a frame
+/// with a full screen redraw.
+pub const TASK_DEADLINE: Duration = Duration::from_secs(2);
+
+/// The tasks that must keep running for the dashboard to be usable
+#[derive(Clone, Copy, Debug, Format)]
+pub enum CriticalTask {
+    CanRx,
+    Display,
+    Led,
+}
+
+impl CriticalTask {
+    const ALL: [CriticalTask; 3] = [
+        CriticalTask::CanRx,
+        CriticalTask::Display,
+        CriticalTask::Led,
+    ];
+}
+
+/// Time each critical task last checked in, in milliseconds since boot
+static LAST_CHECK_IN: [AtomicU32; CriticalTask::ALL.len()] =
+    [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)];
+
+/// Records that a critical task is still running
+pub fn check_in(task: CriticalTask) {
+    LAST_CHECK_IN[task as usize].store(Instant::now().as_millis() as u32, Relaxed);
+}
+
+/// The first critical task that hasn't checked in within [`TASK_DEADLINE`]
+fn stalled_task() -> Option<CriticalTask> {
+    let now = Instant::now().as_millis() as u32;
+    CriticalTask::ALL.into_iter().find(|&task| {
+        if matches!(task, CriticalTask::Display) && display_failed() {
+            return false;
+        }
+        let last = LAST_CHECK_IN[task as usize].load(Relaxed);
+        u64::from(now.wrapping_sub(last)) > TASK_DEADLINE.as_millis()
+    })
+}
+
+/// Feeds the IWDG while every critical task is checking in
+#[embassy_executor::task]
+pub async fn watchdog_task(mut watchdog: IndependentWatchdog<'static, IWDG>) {
+    // Tasks are given a full deadline from when the watchdog starts
+    for task in CriticalTask::ALL {
+        check_in(task);
+    }
+    watchdog.unleash();
+    info!("Watchdog started");
+
+    loop {
+        if let Some(task) = stalled_task() {
+            error!("{} task stalled, waiting for watchdog reset", task);
+            // Stop feeding, the IWDG resets the MCU
+            loop {
+                Timer::after(FEED_INTERVAL).await;
+            }
+        }
+        watchdog.pet();
+        trace!("Watchdog fed");
+        Timer::after(FEED_INTERVAL).await;
+    }
+}